@@ -105,7 +105,7 @@ async fn main() {
     common::configure_emulator(&connection_manager, "robot", Some(300), DONT_EMULATE_FAILURE).await;
 
     println!("Two auto operations bouncing the robot between a and b, {MOVES} times.");
-    main_runner(&SP_ID.to_string(), model, 1, &connection_manager).await;
+    main_runner(&SP_ID.to_string(), model, 1, OperationHandlers::default(), &connection_manager).await;
 
     let done = common::wait_until(&connection_manager, &["counter"], 30_000, |state| {
         state.get_value("counter", TARGET) == Some(MOVES.to_spvalue())
@@ -71,7 +71,7 @@ async fn main() {
     let connection_manager = common::boot(SP_ID, &model, domain, 1).await;
 
     println!("Blinking the light {BLINKS} times, with nothing driving it.");
-    main_runner(&SP_ID.to_string(), model, 1, &connection_manager).await;
+    main_runner(&SP_ID.to_string(), model, 1, OperationHandlers::default(), &connection_manager).await;
 
     // The counter stops at BLINKS and the light ends up off - both halves
     // matter, since a counter at BLINKS with the light still on means the
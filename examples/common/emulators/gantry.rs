@@ -1,8 +1,7 @@
-use micro_sp::{ConnectionManager, ServiceRequestState, StateManager, ToSPValue};
+use micro_sp::*;
 use rand::Rng;
 use rand::prelude::SliceRandom;
 use std::{sync::Arc, time::Duration};
-use tokio::time::interval;
 
 use super::super::EMULATOR_TICK_INTERVAL;
 
@@ -29,133 +28,123 @@ pub struct GantryResponse {
 pub async fn gantry_emulator(
     connection_manager: &Arc<ConnectionManager>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut interval = interval(Duration::from_millis(EMULATOR_TICK_INTERVAL));
     let log_target = "gantry_emulator";
-    log::info!(target: &log_target, "Online.");
-
-    let keys: Vec<String> = vec![
-        "gantry_request_trigger",
-        "gantry_request_state",
-        "gantry_total_fail_counter",
-        "gantry_subsequent_fail_counter",
-        "gantry_command_command",
-        "gantry_speed_command",
-        "gantry_position_command",
-        "gantry_position_estimated",
-        "gantry_calibrated_estimated",
-        "gantry_locked_estimated",
-        "gantry_emulate_execution_time",
-        "gantry_emulated_execution_time",
-        "gantry_emulate_failure_rate",
-        "gantry_emulated_failure_rate",
-        "gantry_emulate_failure_cause",
-        "gantry_emulated_failure_cause",
-    ]
-    .iter()
-    .map(|k| k.to_string())
-    .collect();
-
-    loop {
-        interval.tick().await;
-        if connection_manager.check_redis_health(log_target).await.is_err() {
-            continue;
-        }
-        let mut con = connection_manager.get_connection().await;
-        let state = match StateManager::get_state_for_keys(&mut con, &keys, log_target).await {
-            Some(s) => s,
-            None => continue,
-        };
-
-        let mut request_trigger =
-            state.get_bool_or_default_to_false("gantry_request_trigger", log_target);
-        let mut request_state =
-            state.get_string_or_default_to_unknown("gantry_request_state", log_target);
-
-        let mut total_fail_counter =
-            state.get_int_or_default_to_zero("gantry_total_fail_counter", log_target);
-        let mut subsequent_fail_counter =
-            state.get_int_or_default_to_zero("gantry_subsequent_fail_counter", log_target);
-        let mut gantry_position_estimated =
-            state.get_string_or_default_to_unknown("gantry_position_estimated", log_target);
-        let mut gantry_calibrated_estimated =
-            state.get_bool_or_default_to_false("gantry_calibrated_estimated", log_target);
-        let mut gantry_locked_estimated =
-            state.get_bool_or_default_to_false("gantry_locked_estimated", log_target);
-
-        if request_trigger {
-            request_trigger = false;
-            if request_state == ServiceRequestState::Initial.to_string() {
-                let emulated_failure_cause_sp_value = state
-                    .get_array_or_default_to_empty("gantry_emulated_failure_cause", log_target);
-
-                let emulated_failure_cause: Vec<String> = emulated_failure_cause_sp_value
-                    .iter()
-                    .filter(|val| val.is_string())
-                    .map(|y| y.to_string())
-                    .collect();
-
-                let request = GantryRequest {
-                    command: state
-                        .get_string_or_default_to_unknown("gantry_command_command", log_target),
-                    speed: state.get_float_or_default_to_zero("gantry_speed_command", log_target),
-                    position: state
-                        .get_string_or_default_to_unknown("gantry_position_command", log_target),
-                    emulate_execution_time: state
-                        .get_int_or_default_to_zero("gantry_emulate_execution_time", log_target),
-                    emulated_execution_time: state
-                        .get_int_or_default_to_zero("gantry_emulated_execution_time", log_target),
-                    emulate_failure_rate: state
-                        .get_int_or_default_to_zero("gantry_emulate_failure_rate", log_target),
-                    emulated_failure_rate: state
-                        .get_int_or_default_to_zero("gantry_emulated_failure_rate", log_target),
-                    emulate_failure_cause: state
-                        .get_int_or_default_to_zero("gantry_emulate_failure_cause", log_target),
-                    emulated_failure_cause,
-                };
-
-                let response = emulate_gantry_operation(&request).await;
-
-                request_state = if response.success {
-                    subsequent_fail_counter = 0;
-                    match request.command.as_str() {
-                        "move" => gantry_position_estimated = request.position,
-                        "calibrate" => gantry_calibrated_estimated = true,
-                        "lock" => gantry_locked_estimated = true,
-                        "unlock" => gantry_locked_estimated = false,
-                        _ => (),
-                    }
-                    ServiceRequestState::Succeeded.to_string()
-                } else {
-                    subsequent_fail_counter += 1;
-                    total_fail_counter += 1;
-                    ServiceRequestState::Failed.to_string()
-                };
+    let response_variables = vec![
+        iv!("gantry_total_fail_counter"),
+        iv!("gantry_subsequent_fail_counter"),
+        v!("gantry_position_estimated"),
+        bv!("gantry_calibrated_estimated"),
+        bv!("gantry_locked_estimated"),
+    ];
+
+    let server = ServiceServer {
+        tick_interval_ms: Some(EMULATOR_TICK_INTERVAL),
+        ..ServiceServer::new(
+            "gantry",
+            [
+                vec![
+                    v!("gantry_command_command"),
+                    fv!("gantry_speed_command"),
+                    v!("gantry_position_command"),
+                ],
+                response_variables.clone(),
+                vec![
+                    iv!("gantry_emulate_execution_time"),
+                    iv!("gantry_emulated_execution_time"),
+                    iv!("gantry_emulate_failure_rate"),
+                    iv!("gantry_emulated_failure_rate"),
+                    iv!("gantry_emulate_failure_cause"),
+                    av!("gantry_emulated_failure_cause"),
+                ],
+            ]
+            .concat(),
+            response_variables,
+            None,
+        )
+    };
+
+    server
+        .run(connection_manager, |state: State| async move {
+            let emulated_failure_cause: Vec<String> = state
+                .get_array_or_default_to_empty("gantry_emulated_failure_cause", log_target)
+                .iter()
+                .filter(|val| val.is_string())
+                .map(|y| y.to_string())
+                .collect();
+
+            let request = GantryRequest {
+                command: state.get_string_or_default_to_unknown("gantry_command_command", log_target),
+                speed: state.get_float_or_default_to_zero("gantry_speed_command", log_target),
+                position: state
+                    .get_string_or_default_to_unknown("gantry_position_command", log_target),
+                emulate_execution_time: state
+                    .get_int_or_default_to_zero("gantry_emulate_execution_time", log_target),
+                emulated_execution_time: state
+                    .get_int_or_default_to_zero("gantry_emulated_execution_time", log_target),
+                emulate_failure_rate: state
+                    .get_int_or_default_to_zero("gantry_emulate_failure_rate", log_target),
+                emulated_failure_rate: state
+                    .get_int_or_default_to_zero("gantry_emulated_failure_rate", log_target),
+                emulate_failure_cause: state
+                    .get_int_or_default_to_zero("gantry_emulate_failure_cause", log_target),
+                emulated_failure_cause,
+            };
+
+            let response = emulate_gantry_operation(&request).await;
+
+            let mut total_fail_counter =
+                state.get_int_or_default_to_zero("gantry_total_fail_counter", log_target);
+            let mut subsequent_fail_counter =
+                state.get_int_or_default_to_zero("gantry_subsequent_fail_counter", log_target);
+            let mut gantry_position_estimated =
+                state.get_string_or_default_to_unknown("gantry_position_estimated", log_target);
+            let mut gantry_calibrated_estimated =
+                state.get_bool_or_default_to_false("gantry_calibrated_estimated", log_target);
+            let mut gantry_locked_estimated =
+                state.get_bool_or_default_to_false("gantry_locked_estimated", log_target);
+
+            if response.success {
+                subsequent_fail_counter = 0;
+                match request.command.as_str() {
+                    "move" => gantry_position_estimated = request.position,
+                    "calibrate" => gantry_calibrated_estimated = true,
+                    "lock" => gantry_locked_estimated = true,
+                    "unlock" => gantry_locked_estimated = false,
+                    _ => (),
+                }
+            } else {
+                subsequent_fail_counter += 1;
+                total_fail_counter += 1;
             }
-        }
-        let new_state = state
-            .update("gantry_request_trigger", request_trigger.to_spvalue())
-            .update("gantry_request_state", request_state.to_spvalue())
-            .update("gantry_total_fail_counter", total_fail_counter.to_spvalue())
-            .update(
-                "gantry_subsequent_fail_counter",
-                subsequent_fail_counter.to_spvalue(),
-            )
-            .update(
-                "gantry_position_estimated",
-                gantry_position_estimated.to_spvalue(),
-            )
-            .update(
-                "gantry_calibrated_estimated",
-                gantry_calibrated_estimated.to_spvalue(),
-            )
-            .update(
-                "gantry_locked_estimated",
-                gantry_locked_estimated.to_spvalue(),
-            );
-
-        let modified_state = state.get_diff_partial_state(&new_state);
-        StateManager::set_state(&mut con, &modified_state).await;
-    }
+
+            let values = State::from_vec(&vec![
+                (iv!("gantry_total_fail_counter"), total_fail_counter.to_spvalue()),
+                (
+                    iv!("gantry_subsequent_fail_counter"),
+                    subsequent_fail_counter.to_spvalue(),
+                ),
+                (
+                    v!("gantry_position_estimated"),
+                    gantry_position_estimated.to_spvalue(),
+                ),
+                (
+                    bv!("gantry_calibrated_estimated"),
+                    gantry_calibrated_estimated.to_spvalue(),
+                ),
+                (
+                    bv!("gantry_locked_estimated"),
+                    gantry_locked_estimated.to_spvalue(),
+                ),
+            ]);
+            ServiceResponse {
+                succeeded: response.success,
+                values,
+                information: response.info,
+            }
+        })
+        .await;
+
+    Ok(())
 }
 
 pub async fn emulate_gantry_operation(request: &GantryRequest) -> GantryResponse {
@@ -1,8 +1,7 @@
-use micro_sp::{ConnectionManager, ServiceRequestState, StateManager, ToSPValue};
+use micro_sp::*;
 use rand::Rng;
 use rand::prelude::SliceRandom;
 use std::{sync::Arc, time::Duration};
-use tokio::time::interval;
 
 use super::super::EMULATOR_TICK_INTERVAL;
 
@@ -32,135 +31,123 @@ pub struct RobotResponse {
 pub async fn robot_emulator(
     connection_manager: &Arc<ConnectionManager>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut interval = interval(Duration::from_millis(EMULATOR_TICK_INTERVAL));
     let log_target = "robot_emulator";
-    log::info!(target: &log_target, "Online.");
+    let response_variables = vec![
+        iv!("robot_total_fail_counter"),
+        iv!("robot_subsequent_fail_counter"),
+        v!("robot_position_estimated"),
+        v!("robot_mounted_one_time_measured"),
+    ];
 
-    let keys: Vec<String> = vec![
-        "robot_request_trigger",
-        "robot_request_state",
-        "robot_total_fail_counter",
-        "robot_subsequent_fail_counter",
-        "robot_command_command",
-        "robot_speed_command",
-        "robot_position_command",
-        "robot_position_estimated",
-        "robot_mounted_one_time_measured",
-        "robot_emulate_execution_time",
-        "robot_emulated_execution_time",
-        "robot_emulate_failure_rate",
-        "robot_emulated_failure_rate",
-        "robot_emulate_failure_cause",
-        "robot_emulated_failure_cause",
-        "robot_emulate_mounted_tool",
-        "robot_emulated_mounted_tool",
-    ]
-    .iter()
-    .map(|k| k.to_string())
-    .collect();
-
-    loop {
-        interval.tick().await;
-        if connection_manager.check_redis_health(log_target).await.is_err() {
-            continue;
-        }
-        let mut con = connection_manager.get_connection().await;
-        let state = match StateManager::get_state_for_keys(&mut con, &keys, log_target).await {
-            Some(s) => s,
-            None => continue,
-        };
-
-        let mut request_trigger =
-            state.get_bool_or_default_to_false("robot_request_trigger", log_target);
-        let mut request_state =
-            state.get_string_or_default_to_unknown("robot_request_state", log_target);
-
-        let mut total_fail_counter =
-            state.get_int_or_default_to_zero("robot_total_fail_counter", log_target);
-        let mut subsequent_fail_counter =
-            state.get_int_or_default_to_zero("robot_subsequent_fail_counter", log_target);
-        let mut robot_position_estimated =
-            state.get_string_or_default_to_unknown("robot_position_estimated", log_target);
-        let mut robot_mounted_one_time_measured =
-            state.get_string_or_default_to_unknown("robot_mounted_one_time_measured", log_target);
+    let server = ServiceServer {
+        tick_interval_ms: Some(EMULATOR_TICK_INTERVAL),
+        ..ServiceServer::new(
+            "robot",
+            [
+                vec![
+                    v!("robot_command_command"),
+                    fv!("robot_speed_command"),
+                    v!("robot_position_command"),
+                ],
+                response_variables.clone(),
+                vec![
+                    iv!("robot_emulate_execution_time"),
+                    iv!("robot_emulated_execution_time"),
+                    iv!("robot_emulate_failure_rate"),
+                    iv!("robot_emulated_failure_rate"),
+                    iv!("robot_emulate_failure_cause"),
+                    av!("robot_emulated_failure_cause"),
+                    bv!("robot_emulate_mounted_tool"),
+                    v!("robot_emulated_mounted_tool"),
+                ],
+            ]
+            .concat(),
+            response_variables,
+            None,
+        )
+    };
 
-        if request_trigger {
-            request_trigger = false;
-            if request_state == ServiceRequestState::Initial.to_string() {
-                let emulated_failure_cause_sp_value = state
-                    .get_array_or_default_to_empty("robot_emulated_failure_cause", log_target);
+    server
+        .run(connection_manager, |state: State| async move {
+            let emulated_failure_cause: Vec<String> = state
+                .get_array_or_default_to_empty("robot_emulated_failure_cause", log_target)
+                .iter()
+                .filter(|val| val.is_string())
+                .map(|y| y.to_string())
+                .collect();
 
-                let emulated_failure_cause: Vec<String> = emulated_failure_cause_sp_value
-                    .iter()
-                    .filter(|val| val.is_string())
-                    .map(|y| y.to_string())
-                    .collect();
+            let request = RobotRequest {
+                command: state.get_string_or_default_to_unknown("robot_command_command", log_target),
+                speed: state.get_float_or_default_to_zero("robot_speed_command", log_target),
+                position: state
+                    .get_string_or_default_to_unknown("robot_position_command", log_target),
+                emulate_execution_time: state
+                    .get_int_or_default_to_zero("robot_emulate_execution_time", log_target),
+                emulated_execution_time: state
+                    .get_int_or_default_to_zero("robot_emulated_execution_time", log_target),
+                emulate_failure_rate: state
+                    .get_int_or_default_to_zero("robot_emulate_failure_rate", log_target),
+                emulated_failure_rate: state
+                    .get_int_or_default_to_zero("robot_emulated_failure_rate", log_target),
+                emulate_mounted_tool: state
+                    .get_bool_or_default_to_false("robot_emulate_mounted_tool", log_target),
+                emulated_mounted_tool: state
+                    .get_string_or_default_to_unknown("robot_emulated_mounted_tool", log_target),
+                emulate_failure_cause: state
+                    .get_int_or_default_to_zero("robot_emulate_failure_cause", log_target),
+                emulated_failure_cause,
+            };
 
-                let request = RobotRequest {
-                    command: state
-                        .get_string_or_default_to_unknown("robot_command_command", log_target),
-                    speed: state.get_float_or_default_to_zero("robot_speed_command", log_target),
-                    position: state
-                        .get_string_or_default_to_unknown("robot_position_command", log_target),
-                    emulate_execution_time: state
-                        .get_int_or_default_to_zero("robot_emulate_execution_time", log_target),
-                    emulated_execution_time: state
-                        .get_int_or_default_to_zero("robot_emulated_execution_time", log_target),
-                    emulate_failure_rate: state
-                        .get_int_or_default_to_zero("robot_emulate_failure_rate", log_target),
-                    emulated_failure_rate: state
-                        .get_int_or_default_to_zero("robot_emulated_failure_rate", log_target),
-                    emulate_mounted_tool: state
-                        .get_bool_or_default_to_false("robot_emulate_mounted_tool", log_target),
-                    emulated_mounted_tool: state.get_string_or_default_to_unknown(
-                        "robot_emulated_mounted_tool",
-                        &log_target,
-                    ),
-                    emulate_failure_cause: state
-                        .get_int_or_default_to_zero("robot_emulate_failure_cause", log_target),
-                    emulated_failure_cause,
-                };
+            let response = emulate_robot_operation(&request).await;
 
-                let response = emulate_robot_operation(&request).await;
+            let mut total_fail_counter =
+                state.get_int_or_default_to_zero("robot_total_fail_counter", log_target);
+            let mut subsequent_fail_counter =
+                state.get_int_or_default_to_zero("robot_subsequent_fail_counter", log_target);
+            let mut robot_position_estimated =
+                state.get_string_or_default_to_unknown("robot_position_estimated", log_target);
+            let mut robot_mounted_one_time_measured = state
+                .get_string_or_default_to_unknown("robot_mounted_one_time_measured", log_target);
 
-                request_state = if response.success {
-                    subsequent_fail_counter = 0;
-                    match request.command.as_str() {
-                        "move" => robot_position_estimated = request.position,
-                        "check_mounted_tool" => {
-                            robot_mounted_one_time_measured = response.checked_mounted_tool
-                        }
-                        "pick" | "place" | "mount" | "unmount" => (),
-                        _ => (),
+            if response.success {
+                subsequent_fail_counter = 0;
+                match request.command.as_str() {
+                    "move" => robot_position_estimated = request.position,
+                    "check_mounted_tool" => {
+                        robot_mounted_one_time_measured = response.checked_mounted_tool
                     }
-                    ServiceRequestState::Succeeded.to_string()
-                } else {
-                    subsequent_fail_counter += 1;
-                    total_fail_counter += 1;
-                    ServiceRequestState::Failed.to_string()
-                };
+                    "pick" | "place" | "mount" | "unmount" => (),
+                    _ => (),
+                }
+            } else {
+                subsequent_fail_counter += 1;
+                total_fail_counter += 1;
             }
-        }
-        let new_state = state
-            .update("robot_request_trigger", request_trigger.to_spvalue())
-            .update("robot_request_state", request_state.to_spvalue())
-            .update("robot_total_fail_counter", total_fail_counter.to_spvalue())
-            .update(
-                "robot_subsequent_fail_counter",
-                subsequent_fail_counter.to_spvalue(),
-            )
-            .update(
-                "robot_position_estimated",
-                robot_position_estimated.to_spvalue(),
-            )
-            .update(
-                "robot_mounted_one_time_measured",
-                robot_mounted_one_time_measured.to_spvalue(),
-            );
 
-        let modified_state = state.get_diff_partial_state(&new_state);
-        StateManager::set_state(&mut con, &modified_state).await;
-    }
+            let values = State::from_vec(&vec![
+                (iv!("robot_total_fail_counter"), total_fail_counter.to_spvalue()),
+                (
+                    iv!("robot_subsequent_fail_counter"),
+                    subsequent_fail_counter.to_spvalue(),
+                ),
+                (
+                    v!("robot_position_estimated"),
+                    robot_position_estimated.to_spvalue(),
+                ),
+                (
+                    v!("robot_mounted_one_time_measured"),
+                    robot_mounted_one_time_measured.to_spvalue(),
+                ),
+            ]);
+            ServiceResponse {
+                succeeded: response.success,
+                values,
+                information: response.info,
+            }
+        })
+        .await;
+
+    Ok(())
 }
 
 pub async fn emulate_robot_operation(request: &RobotRequest) -> RobotResponse {
@@ -274,7 +274,7 @@ async fn main() {
 
     common::spawn_gantry(&connection_manager);
 
-    main_runner(&SP_ID.to_string(), model, 1, &connection_manager).await;
+    main_runner(&SP_ID.to_string(), model, 1, OperationHandlers::default(), &connection_manager).await;
     tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
     let mut ok = true;
@@ -128,7 +128,7 @@ async fn main() {
         .collect();
 
     println!("Posting {} goals; the planner decides how to reach each.", goals.len());
-    main_runner(&SP_ID.to_string(), model, 1, &connection_manager).await;
+    main_runner(&SP_ID.to_string(), model, 1, OperationHandlers::default(), &connection_manager).await;
 
     // Post after the runners are up, so the goal runner sees the write rather
     // than finding a queue already there on its first tick.
@@ -368,7 +368,7 @@ async fn main() {
     .await;
 
     println!("Asking for the {WANTED_TOOL}; the robot is really holding the {ACTUAL_TOOL}.");
-    main_runner(&SP_ID.to_string(), model, 1, &connection_manager).await;
+    main_runner(&SP_ID.to_string(), model, 1, OperationHandlers::default(), &connection_manager).await;
 
     tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     StateManager::set_sp_value(
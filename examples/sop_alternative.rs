@@ -150,7 +150,7 @@ async fn main() {
     common::configure_emulator(&connection_manager, "robot", Some(300), DONT_EMULATE_FAILURE).await;
 
     println!("Three alternative routes; the first is blocked, so another has to close the node.");
-    main_runner(&SP_ID.to_string(), model, 1, &connection_manager).await;
+    main_runner(&SP_ID.to_string(), model, 1, OperationHandlers::default(), &connection_manager).await;
 
     let done = common::wait_until(&connection_manager, &["done"], 40_000, |state| {
         state.get_value("done", TARGET) == Some(true.to_spvalue())
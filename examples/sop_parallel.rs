@@ -186,7 +186,7 @@ async fn main() {
         .await;
 
     println!("Robot to a and gantry to b, at the same time; the node waits for both.");
-    main_runner(&SP_ID.to_string(), model, 1, &connection_manager).await;
+    main_runner(&SP_ID.to_string(), model, 1, OperationHandlers::default(), &connection_manager).await;
 
     let done = common::wait_until(&connection_manager, &["done"], 40_000, |state| {
         state.get_value("done", TARGET) == Some(true.to_spvalue())
@@ -4,9 +4,12 @@
 //! [`SPValue`](crate::SPValue) is what fits in it, an
 //! [`SPAssignment`](crate::SPAssignment) pairs the two, and a
 //! [`State`](crate::State) is a map of assignments. [`SPWrapped`](crate::SPWrapped)
-//! is the operand form used inside predicates and actions.
+//! is the operand form used inside predicates and actions, and a
+//! [`PathSegment`](crate::PathSegment) list picks a part out of a structured
+//! value.
 
 pub mod sp_assignment;
+pub mod sp_path;
 pub mod sp_state;
 pub mod sp_value;
 pub mod sp_variable;
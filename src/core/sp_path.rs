@@ -0,0 +1,313 @@
+//! Paths into structured values.
+//!
+//! A [`PathSegment`] list picks one part out of a map, an array or a
+//! transform: `config.speed` is the entry of the map `config` with key
+//! `"speed"`, `queue[0]` is the first element of the array `queue`, and
+//! `robot_pose.translation.z` is one coordinate of a transform. The DSL writes
+//! them after a variable (`var:robot_pose.translation.z > 0.3`), and
+//! [`SPWrapped::Path`] carries them in predicates and actions.
+//!
+//! ```
+//! use micro_sp::*;
+//!
+//! let config = SPValue::Map(MapOrUnknown::Map(vec![
+//!     ("speed".to_spvalue(), 0.5.to_spvalue()),
+//! ]));
+//! let path = vec![PathSegment::Field("speed".to_string())];
+//! assert_eq!(config.get_path(&path), Some(0.5.to_spvalue()));
+//!
+//! let mut config = config;
+//! assert!(config.set_path(&path, 0.8.to_spvalue()));
+//! assert_eq!(config.get_path(&path), Some(0.8.to_spvalue()));
+//! ```
+
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One step of a path into a structured value.
+#[derive(Debug, PartialEq, PartialOrd, Ord, Eq, Clone, Hash, Serialize, Deserialize)]
+pub enum PathSegment {
+    /// `.name`: the map entry whose key is the string `name`, or the
+    /// transform field `name`.
+    Field(String),
+    /// `[i]`: the `i`-th element of an array, counting from zero.
+    Index(usize),
+}
+
+/// Renders as the DSL writes it: `.name` or `[i]`.
+impl fmt::Display for PathSegment {
+    fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Field(name) => write!(fmtr, ".{}", name),
+            PathSegment::Index(i) => write!(fmtr, "[{}]", i),
+        }
+    }
+}
+
+/// The type found at `path` inside a value of type `root`.
+///
+/// `Ok(Some(t))` when the type is fixed, as for every transform field;
+/// `Ok(None)` when it depends on the contents, as for map entries and array
+/// elements; and `Err` with the reason when no value of type `root` has such a
+/// part - a path into an `i64`, or a transform field that does not exist.
+pub fn path_type(root: SPValueType, path: &[PathSegment]) -> Result<Option<SPValueType>, String> {
+    let Some((first, rest)) = path.split_first() else {
+        return Ok(Some(root));
+    };
+    match (root, first) {
+        (SPValueType::Map, PathSegment::Field(_)) | (SPValueType::Array, PathSegment::Index(_)) => {
+            Ok(None)
+        }
+        (SPValueType::Transform, PathSegment::Field(field)) => {
+            let leaf = |t: SPValueType| match rest.is_empty() {
+                true => Ok(Some(t)),
+                false => Err(format!("a transform's '{field}' has no parts")),
+            };
+            match (field.as_str(), rest) {
+                ("translation", [PathSegment::Field(axis)])
+                    if matches!(axis.as_str(), "x" | "y" | "z") =>
+                {
+                    Ok(Some(SPValueType::Float64))
+                }
+                ("rotation", [PathSegment::Field(axis)])
+                    if matches!(axis.as_str(), "x" | "y" | "z" | "w") =>
+                {
+                    Ok(Some(SPValueType::Float64))
+                }
+                ("translation", _) => Err("a translation has the parts .x, .y and .z".to_string()),
+                ("rotation", _) => {
+                    Err("a rotation has the parts .x, .y, .z and .w".to_string())
+                }
+                ("parent_frame_id" | "child_frame_id", _) => leaf(SPValueType::String),
+                ("active_transform" | "enable_transform", _) => leaf(SPValueType::Bool),
+                ("time_stamp", _) => leaf(SPValueType::Time),
+                ("metadata", _) => path_type(SPValueType::Map, rest),
+                _ => Err(format!("a transform has no field '{field}'")),
+            }
+        }
+        (root, segment) => Err(format!("a {root} value has no part '{segment}'")),
+    }
+}
+
+impl SPValue {
+    /// The part of this value at `path`, or `None` if there is no such part:
+    /// a missing map key, an index past the end, an `UNKNOWN` along the way or
+    /// a path that does not fit the value.
+    pub fn get_path(&self, path: &[PathSegment]) -> Option<SPValue> {
+        let Some((first, rest)) = path.split_first() else {
+            return Some(self.clone());
+        };
+        match (self, first) {
+            (SPValue::Map(MapOrUnknown::Map(pairs)), PathSegment::Field(key)) => pairs
+                .iter()
+                .find(|(k, _)| *k == key.to_spvalue())
+                .and_then(|(_, v)| v.get_path(rest)),
+            (SPValue::Array(ArrayOrUnknown::Array(items)), PathSegment::Index(i)) => {
+                items.get(*i).and_then(|item| item.get_path(rest))
+            }
+            (
+                SPValue::Transform(TransformOrUnknown::Transform(tf)),
+                PathSegment::Field(field),
+            ) => {
+                let t = &tf.transform;
+                let part = match (field.as_str(), rest) {
+                    ("translation", [PathSegment::Field(axis)]) => match axis.as_str() {
+                        "x" => t.translation.x.0.to_spvalue(),
+                        "y" => t.translation.y.0.to_spvalue(),
+                        "z" => t.translation.z.0.to_spvalue(),
+                        _ => return None,
+                    },
+                    ("rotation", [PathSegment::Field(axis)]) => match axis.as_str() {
+                        "x" => t.rotation.x.0.to_spvalue(),
+                        "y" => t.rotation.y.0.to_spvalue(),
+                        "z" => t.rotation.z.0.to_spvalue(),
+                        "w" => t.rotation.w.0.to_spvalue(),
+                        _ => return None,
+                    },
+                    ("metadata", _) => return SPValue::Map(tf.metadata.clone()).get_path(rest),
+                    (_, [_, ..]) => return None,
+                    ("parent_frame_id", []) => tf.parent_frame_id.to_spvalue(),
+                    ("child_frame_id", []) => tf.child_frame_id.to_spvalue(),
+                    ("active_transform", []) => tf.active_transform.to_spvalue(),
+                    ("enable_transform", []) => tf.enable_transform.to_spvalue(),
+                    ("time_stamp", []) => tf.time_stamp.to_spvalue(),
+                    _ => return None,
+                };
+                Some(part)
+            }
+            _ => None,
+        }
+    }
+
+    /// Replaces the part of this value at `path` with `value`, and returns
+    /// whether it could.
+    ///
+    /// A map entry that does not exist yet is appended when it is the last
+    /// step; an array is never extended, so an index past the end fails. A
+    /// transform field only takes a value of its own type. On failure the
+    /// value is left as it was.
+    pub fn set_path(&mut self, path: &[PathSegment], value: SPValue) -> bool {
+        let Some((first, rest)) = path.split_first() else {
+            *self = value;
+            return true;
+        };
+        match (self, first) {
+            (SPValue::Map(MapOrUnknown::Map(pairs)), PathSegment::Field(key)) => {
+                let key = key.to_spvalue();
+                match pairs.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, v)) => v.set_path(rest, value),
+                    None if rest.is_empty() => {
+                        pairs.push((key, value));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            (SPValue::Array(ArrayOrUnknown::Array(items)), PathSegment::Index(i)) => {
+                match items.get_mut(*i) {
+                    Some(item) => item.set_path(rest, value),
+                    None => false,
+                }
+            }
+            (
+                SPValue::Transform(TransformOrUnknown::Transform(tf)),
+                PathSegment::Field(field),
+            ) => {
+                if field == "metadata" {
+                    let mut metadata = SPValue::Map(tf.metadata.clone());
+                    let set = metadata.set_path(rest, value);
+                    if let SPValue::Map(metadata) = metadata {
+                        tf.metadata = metadata;
+                    }
+                    return set;
+                }
+                let t = &mut tf.transform;
+                let coordinate = match (field.as_str(), rest) {
+                    ("translation", [PathSegment::Field(axis)]) => match axis.as_str() {
+                        "x" => Some(&mut t.translation.x),
+                        "y" => Some(&mut t.translation.y),
+                        "z" => Some(&mut t.translation.z),
+                        _ => None,
+                    },
+                    ("rotation", [PathSegment::Field(axis)]) => match axis.as_str() {
+                        "x" => Some(&mut t.rotation.x),
+                        "y" => Some(&mut t.rotation.y),
+                        "z" => Some(&mut t.rotation.z),
+                        "w" => Some(&mut t.rotation.w),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match (coordinate, value, field.as_str(), rest) {
+                    (Some(c), SPValue::Float64(FloatOrUnknown::Float64(f)), _, _) => *c = f,
+                    (None, SPValue::String(StringOrUnknown::String(s)), "parent_frame_id", []) => {
+                        tf.parent_frame_id = s
+                    }
+                    (None, SPValue::String(StringOrUnknown::String(s)), "child_frame_id", []) => {
+                        tf.child_frame_id = s
+                    }
+                    (None, SPValue::Bool(BoolOrUnknown::Bool(b)), "active_transform", []) => {
+                        tf.active_transform = b
+                    }
+                    (None, SPValue::Bool(BoolOrUnknown::Bool(b)), "enable_transform", []) => {
+                        tf.enable_transform = b
+                    }
+                    (None, SPValue::Time(TimeOrUnknown::Time(t)), "time_stamp", []) => {
+                        tf.time_stamp = t
+                    }
+                    _ => return false,
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use ordered_float::OrderedFloat;
+    use std::time::SystemTime;
+
+    fn field(name: &str) -> PathSegment {
+        PathSegment::Field(name.to_string())
+    }
+
+    fn pose() -> SPValue {
+        SPValue::Transform(TransformOrUnknown::Transform(SPTransformStamped {
+            active_transform: true,
+            enable_transform: true,
+            time_stamp: SystemTime::UNIX_EPOCH,
+            parent_frame_id: "world".to_string(),
+            child_frame_id: "tcp".to_string(),
+            transform: SPTransform {
+                translation: SPTranslation {
+                    x: OrderedFloat(0.1),
+                    y: OrderedFloat(0.2),
+                    z: OrderedFloat(0.3),
+                },
+                rotation: SPRotation {
+                    x: OrderedFloat(0.0),
+                    y: OrderedFloat(0.0),
+                    z: OrderedFloat(0.0),
+                    w: OrderedFloat(1.0),
+                },
+            },
+            metadata: MapOrUnknown::Map(vec![("tool".to_spvalue(), "gripper".to_spvalue())]),
+        }))
+    }
+
+    #[test]
+    fn get_path_reaches_into_maps_arrays_and_transforms() {
+        let nested = SPValue::Map(MapOrUnknown::Map(vec![(
+            "queue".to_spvalue(),
+            vec!["p1".to_spvalue(), "p2".to_spvalue()].to_spvalue(),
+        )]));
+        assert_eq!(
+            nested.get_path(&[field("queue"), PathSegment::Index(1)]),
+            Some("p2".to_spvalue())
+        );
+        assert_eq!(nested.get_path(&[field("queue"), PathSegment::Index(2)]), None);
+        assert_eq!(nested.get_path(&[field("missing")]), None);
+        assert_eq!(nested.get_path(&[PathSegment::Index(0)]), None);
+
+        let pose = pose();
+        assert_eq!(pose.get_path(&[field("translation"), field("z")]), Some(0.3.to_spvalue()));
+        assert_eq!(pose.get_path(&[field("rotation"), field("w")]), Some(1.0.to_spvalue()));
+        assert_eq!(pose.get_path(&[field("child_frame_id")]), Some("tcp".to_spvalue()));
+        assert_eq!(pose.get_path(&[field("metadata"), field("tool")]), Some("gripper".to_spvalue()));
+        assert_eq!(pose.get_path(&[field("translation")]), None);
+        assert_eq!(pose.get_path(&[field("child_frame_id"), field("x")]), None);
+    }
+
+    #[test]
+    fn set_path_replaces_only_what_exists() {
+        let mut pose = pose();
+        assert!(pose.set_path(&[field("translation"), field("z")], 0.5.to_spvalue()));
+        assert!(pose.set_path(&[field("metadata"), field("speed")], 2.to_spvalue()));
+        assert!(!pose.set_path(&[field("translation"), field("z")], 1.to_spvalue()));
+        assert!(!pose.set_path(&[field("color")], "red".to_spvalue()));
+        assert_eq!(pose.get_path(&[field("translation"), field("z")]), Some(0.5.to_spvalue()));
+        assert_eq!(pose.get_path(&[field("metadata"), field("speed")]), Some(2.to_spvalue()));
+
+        let mut queue = vec!["p1".to_spvalue()].to_spvalue();
+        assert!(queue.set_path(&[PathSegment::Index(0)], "p9".to_spvalue()));
+        assert!(!queue.set_path(&[PathSegment::Index(1)], "p2".to_spvalue()));
+        assert_eq!(queue, vec!["p9".to_spvalue()].to_spvalue());
+    }
+
+    #[test]
+    fn path_types() {
+        use SPValueType::*;
+        assert_eq!(path_type(Transform, &[field("translation"), field("x")]), Ok(Some(Float64)));
+        assert_eq!(path_type(Transform, &[field("time_stamp")]), Ok(Some(Time)));
+        assert_eq!(path_type(Transform, &[field("metadata"), field("tool")]), Ok(None));
+        assert_eq!(path_type(Map, &[field("a"), PathSegment::Index(3)]), Ok(None));
+        assert_eq!(path_type(Int64, &[]), Ok(Some(Int64)));
+        assert!(path_type(Int64, &[field("x")]).is_err());
+        assert!(path_type(Transform, &[field("translation"), field("w")]).is_err());
+        assert!(path_type(Array, &[field("x")]).is_err());
+    }
+}
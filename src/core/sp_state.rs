@@ -216,14 +216,61 @@ impl State {
 
     /// Overwrite the value of an existing variable, keeping its `SPVariable`.
     ///
-    /// Panics if the variable is not in the state, matching [`State::update`].
+    /// A value outside the [`SPVariable::domain`] declared in the state is
+    /// refused and logged as an error under the `sp_state` target. A state
+    /// read back from Redis has no domains to check against; see
+    /// [`State::declare_mut`] and [`State::update_in_domain_mut`]. Panics if
+    /// the variable is not in the state, matching [`State::update`].
     pub fn update_mut(&mut self, name: &str, val: SPValue) {
         match self.state.get_mut(name) {
-            Some(assignment) => assignment.val = val,
+            Some(assignment) => match &assignment.var.domain {
+                Some(domain) if !domain.contains(&val) => log::error!(target: "sp_state",
+                    "Value {} is outside the domain {} of '{}'. Skipped update.", val, domain, name),
+                _ => assignment.val = val,
+            },
             None => panic!("Variable {} not in state.", name),
         }
     }
 
+    /// Overwrite the value of `var` unless it is outside the variable's
+    /// [`SPVariable::domain`], in which case the state is left unchanged and
+    /// the error says why, for the caller to log under its own target.
+    ///
+    /// The domain is the one declared in the state or else the one `var` was
+    /// built with, so a write checks out even against a state read back from
+    /// Redis, which only knows names and types. Panics if the variable is not
+    /// in the state, matching [`State::update`].
+    pub fn update_in_domain_mut(&mut self, var: &SPVariable, val: SPValue) -> Result<(), String> {
+        let assignment = self
+            .state
+            .get_mut(&var.name)
+            .unwrap_or_else(|| panic!("Variable {} not in state.", var.name));
+        match assignment.var.domain.as_ref().or(var.domain.as_ref()) {
+            Some(domain) if !domain.contains(&val) => Err(format!(
+                "Value {} is outside the domain {} of '{}'.",
+                val, domain, var.name
+            )),
+            _ => {
+                assignment.val = val;
+                Ok(())
+            }
+        }
+    }
+
+    /// Give the variables of this state the declarations in `variables` - the
+    /// domain, tolerance and metadata a state read from Redis has lost -
+    /// matching them by name and type. The values are left alone.
+    pub fn declare_mut(&mut self, variables: &[SPVariable]) {
+        for var in variables {
+            match self.state.get_mut(&var.name) {
+                Some(assignment) if assignment.var.value_type == var.value_type => {
+                    assignment.var = var.clone()
+                }
+                _ => (),
+            }
+        }
+    }
+
     /// Merge `other` into `self`, matching [`State::extend`]: when
     /// `overwrite_existing` is true the values from `other` win, otherwise the
     /// values already in `self` are kept.
@@ -1407,4 +1454,37 @@ mod accessor_tests {
         state.update_mut("i", 8.to_spvalue());
         assert!(!goal.eval(&state, TARGET));
     }
+
+    #[test]
+    fn an_update_outside_the_domain_is_refused() {
+        let mut state = State::from_vec(&vec![
+            (v!("pos", ["a", "b"]), "a".to_spvalue()),
+            (iv!("slot"), 1.to_spvalue()),
+        ]);
+        assert!(state.update_in_domain_mut(&v!("pos"), "c".to_spvalue()).is_err());
+        let slot = iv!("slot", 1..=3);
+        assert!(state.update_in_domain_mut(&slot, 4.to_spvalue()).is_err());
+        assert_eq!(state.get_value("pos", TARGET), Some("a".to_spvalue()));
+        assert_eq!(state.get_value("slot", TARGET), Some(1.to_spvalue()));
+
+        assert_eq!(state.update_in_domain_mut(&slot, 3.to_spvalue()), Ok(()));
+        state.update_mut("pos", "c".to_spvalue());
+        assert_eq!(state.get_value("pos", TARGET), Some("a".to_spvalue()));
+        state.update_mut("pos", "b".to_spvalue());
+        assert_eq!(state.get_value("pos", TARGET), Some("b".to_spvalue()));
+        let moved = Action::new(v!("pos"), "b".wrap()).assign(&state, TARGET);
+        assert_eq!(moved.get_value("pos", TARGET), Some("b".to_spvalue()));
+    }
+
+    #[test]
+    fn declarations_are_matched_by_name_and_type() {
+        let mut state = State::from_vec(&vec![
+            (v!("pos"), "a".to_spvalue()),
+            (iv!("slot"), 1.to_spvalue()),
+        ]);
+        state.declare_mut(&[v!("pos", ["a", "b"]), v!("slot", ["1"])]);
+        assert_eq!(state.get_assignment("pos", TARGET).var, v!("pos", ["a", "b"]));
+        assert_eq!(state.get_assignment("slot", TARGET).var, iv!("slot"));
+        assert_eq!(state.get_value("pos", TARGET), Some("a".to_spvalue()));
+    }
 }
@@ -340,6 +340,20 @@ impl SPValueType {
             _ => panic!("Unsupported SPValueType!"),
         }
     }
+
+    /// The `UNKNOWN` value of this type.
+    pub fn unknown(&self) -> SPValue {
+        match self {
+            SPValueType::Bool => SPValue::Bool(BoolOrUnknown::UNKNOWN),
+            SPValueType::Float64 => SPValue::Float64(FloatOrUnknown::UNKNOWN),
+            SPValueType::Int64 => SPValue::Int64(IntOrUnknown::UNKNOWN),
+            SPValueType::String => SPValue::String(StringOrUnknown::UNKNOWN),
+            SPValueType::Time => SPValue::Time(TimeOrUnknown::UNKNOWN),
+            SPValueType::Array => SPValue::Array(ArrayOrUnknown::UNKNOWN),
+            SPValueType::Map => SPValue::Map(MapOrUnknown::UNKNOWN),
+            SPValueType::Transform => SPValue::Transform(TransformOrUnknown::UNKNOWN),
+        }
+    }
 }
 
 /// Renders the type name, in the spelling [`SPValueType::from_str`] accepts.
@@ -413,6 +427,21 @@ impl SPValue {
         }
     }
 
+    /// Returns `true` for the `UNKNOWN` value of any type.
+    pub fn is_unknown(&self) -> bool {
+        matches!(
+            self,
+            SPValue::Bool(BoolOrUnknown::UNKNOWN)
+                | SPValue::Float64(FloatOrUnknown::UNKNOWN)
+                | SPValue::Int64(IntOrUnknown::UNKNOWN)
+                | SPValue::String(StringOrUnknown::UNKNOWN)
+                | SPValue::Time(TimeOrUnknown::UNKNOWN)
+                | SPValue::Array(ArrayOrUnknown::UNKNOWN)
+                | SPValue::Map(MapOrUnknown::UNKNOWN)
+                | SPValue::Transform(TransformOrUnknown::UNKNOWN)
+        )
+    }
+
     /// Renders the value as a string; `"UNKNOWN"` for an unknown value.
     ///
     /// Equivalent to the `Display` implementation. A `Time` renders as the
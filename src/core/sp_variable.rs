@@ -2,13 +2,16 @@
 //!
 //! An [`SPVariable`] is a name plus an [`SPValueType`]; pairing one with a
 //! value of that type gives an [`SPAssignment`], and a set of assignments is a
-//! [`State`]. [`SPVariableFormal`] adds an explicit domain, used where the
-//! planner needs to enumerate a variable's possible values.
+//! [`State`]. A variable may be restricted to an [`SPDomain`], which state
+//! updates, the parser, the linter and the planners all respect.
+//! [`SPVariableFormal`] adds an explicit domain, used where the planner needs
+//! to enumerate a variable's possible values.
 
+use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
 use crate::*;
-use std::fmt;
+use std::{fmt, ops::RangeInclusive};
 
 /// A named unit of data with a declared [`SPValueType`].
 ///
@@ -30,6 +33,131 @@ pub struct SPVariable {
     pub name: String,
     /// The type of value the variable may hold.
     pub value_type: SPValueType,
+    /// How far apart two values of this variable may be and still count as
+    /// the same in an approximate comparison, `var:x ~= 0.25`. `None` means
+    /// such comparisons are exact unless they give their own tolerance. Only
+    /// meaningful for [`SPValueType::Float64`] variables.
+    #[serde(default)]
+    pub tolerance: Option<OrderedFloat<f64>>,
+    /// The values the variable may take, if it is restricted to a finite
+    /// set. `None` means any value of its type.
+    #[serde(default)]
+    pub domain: Option<SPDomain>,
+    /// What the variable means and who writes it. Informational, except
+    /// where [`SPVariableMetadata`] says otherwise.
+    #[serde(default)]
+    pub metadata: SPVariableMetadata,
+    /// How long, in milliseconds, a value stays valid without being written
+    /// again. When it lapses the runtime resets the variable to its typed
+    /// `UNKNOWN`; see [`ttl_runner`]. `None` means values never go stale.
+    #[serde(default)]
+    pub ttl_ms: Option<u64>,
+}
+
+/// Documentation for a variable, carried with it into the exported model; see
+/// [`Model::variables`].
+///
+/// ```
+/// use micro_sp::*;
+///
+/// let pos = v!("gantry_pos")
+///     .described("Where the gantry is")
+///     .with_unit("mm")
+///     .owned_by("gantry_driver")
+///     .with_kind(VariableKind::Measured);
+/// assert_eq!(pos.metadata.unit.as_deref(), Some("mm"));
+/// assert!(pos.is_measured());
+/// ```
+#[derive(Debug, PartialEq, Clone, Hash, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub struct SPVariableMetadata {
+    /// What the variable means, for people reading the model.
+    pub description: Option<String>,
+    /// The physical unit of its value, such as `mm` or `rad`.
+    pub unit: Option<String>,
+    /// The process or service that writes it. A runner of another `sp_id`
+    /// that writes it is warned about.
+    pub owner: Option<String>,
+    /// Where its value comes from.
+    pub kind: Option<VariableKind>,
+}
+
+/// Where a variable's value comes from; see [`SPVariableMetadata::kind`].
+#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum VariableKind {
+    /// Read from the world by a sensor or driver. The planner does not let an
+    /// operation predict it unless the operation is a sensing one; see
+    /// [`Operation::sensing`].
+    Measured,
+    /// A command the model sends to a driver or service.
+    Commanded,
+    /// Computed from other variables, such as a filtered position.
+    Estimated,
+    /// Bookkeeping of the runners themselves.
+    RunnerInternal,
+}
+
+/// A finite set of values a variable may take; see [`SPVariable::domain`].
+///
+/// `UNKNOWN` is always in the domain: a variable may always be unknown.
+///
+/// ```
+/// use micro_sp::*;
+///
+/// let pos = v!("pos", ["a", "b", "c"]);
+/// assert!(pos.admits(&"b".to_spvalue()));
+/// assert!(!pos.admits(&"d".to_spvalue()));
+///
+/// let slot = iv!("slot", 1..=8);
+/// assert!(slot.admits(&8.to_spvalue()));
+/// assert!(!slot.admits(&9.to_spvalue()));
+/// ```
+#[derive(Debug, PartialEq, Clone, Hash, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SPDomain {
+    /// Exactly these values, such as `pos ∈ {a, b, c}`.
+    Values(Vec<SPValue>),
+    /// The `i64` values from the first bound to the second, both included.
+    Range(i64, i64),
+}
+
+impl SPDomain {
+    /// Whether `value` is in the domain. `UNKNOWN` always is.
+    pub fn contains(&self, value: &SPValue) -> bool {
+        if value.is_unknown() {
+            return true;
+        }
+        match self {
+            SPDomain::Values(values) => values.contains(value),
+            SPDomain::Range(low, high) => match value {
+                SPValue::Int64(IntOrUnknown::Int64(i)) => (low..=high).contains(&i),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl From<Vec<SPValue>> for SPDomain {
+    fn from(values: Vec<SPValue>) -> SPDomain {
+        SPDomain::Values(values)
+    }
+}
+
+impl From<RangeInclusive<i64>> for SPDomain {
+    fn from(range: RangeInclusive<i64>) -> SPDomain {
+        SPDomain::Range(*range.start(), *range.end())
+    }
+}
+
+/// Renders as set or range notation: `{a, b, c}` or `1..=8`.
+impl fmt::Display for SPDomain {
+    fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SPDomain::Values(values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(fmtr, "{{{}}}", values.join(", "))
+            }
+            SPDomain::Range(low, high) => write!(fmtr, "{}..={}", low, high),
+        }
+    }
 }
 
 /// An [`SPVariable`] with an explicit domain of allowed values.
@@ -49,9 +177,89 @@ impl SPVariable {
         SPVariable {
             name: name.to_owned(),
             value_type,
+            tolerance: None,
+            domain: None,
+            metadata: SPVariableMetadata::default(),
+            ttl_ms: None,
         }
     }
 
+    /// The same variable with a description.
+    pub fn described(mut self, description: &str) -> SPVariable {
+        self.metadata.description = Some(description.to_string());
+        self
+    }
+
+    /// The same variable with a physical unit.
+    pub fn with_unit(mut self, unit: &str) -> SPVariable {
+        self.metadata.unit = Some(unit.to_string());
+        self
+    }
+
+    /// The same variable owned by `owner`, the process or service that writes
+    /// it.
+    pub fn owned_by(mut self, owner: &str) -> SPVariable {
+        self.metadata.owner = Some(owner.to_string());
+        self
+    }
+
+    /// The same variable of the given kind.
+    pub fn with_kind(mut self, kind: VariableKind) -> SPVariable {
+        self.metadata.kind = Some(kind);
+        self
+    }
+
+    /// Whether the variable is declared [`VariableKind::Measured`].
+    pub fn is_measured(&self) -> bool {
+        self.metadata.kind == Some(VariableKind::Measured)
+    }
+
+    /// The same variable restricted to a domain: a list of values or an
+    /// inclusive `i64` range. The `v!`, `iv!` and `fv!` macros take one as a
+    /// second argument.
+    pub fn with_domain(mut self, domain: impl Into<SPDomain>) -> SPVariable {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Whether the variable may hold `value`: always when it has no domain,
+    /// otherwise when the domain contains it.
+    pub fn admits(&self, value: &SPValue) -> bool {
+        self.domain.as_ref().is_none_or(|domain| domain.contains(value))
+    }
+
+    /// The same variable with a default tolerance for approximate
+    /// comparisons; see [`Predicate::APPROX`].
+    ///
+    /// ```
+    /// use micro_sp::*;
+    ///
+    /// let x = fv!("x").with_tolerance(0.01);
+    /// let state = State::from_vec(&vec![(x.clone(), 0.254.to_spvalue())]);
+    /// let near = pred_parser::pred("var:x ~= 0.25", &state).unwrap();
+    /// assert!(near.eval(&state, "docs"));
+    /// ```
+    pub fn with_tolerance(mut self, tolerance: f64) -> SPVariable {
+        self.tolerance = Some(OrderedFloat(tolerance.abs()));
+        self
+    }
+
+    /// The same variable with a time-to-live: a value not rewritten within
+    /// `ttl` is reset to `UNKNOWN`, so guards like `var:tool != UNKNOWN`
+    /// stop holding on stale measurements.
+    ///
+    /// ```
+    /// use micro_sp::*;
+    /// use std::time::Duration;
+    ///
+    /// let tool = v!("detected_tool").with_ttl(Duration::from_secs(2));
+    /// assert_eq!(tool.ttl_ms, Some(2000));
+    /// ```
+    pub fn with_ttl(mut self, ttl: std::time::Duration) -> SPVariable {
+        self.ttl_ms = Some(ttl.as_millis() as u64);
+        self
+    }
+
     /// Creates a [`SPValueType::Bool`] variable. The `bv!` macro is shorter.
     pub fn new_boolean_var(name: &str) -> SPVariable {
         SPVariable::new(name, SPValueType::Bool)
@@ -208,4 +416,46 @@ mod tests {
         let var = SPVariable::new("my_variable", SPValueType::Bool);
         assert_eq!(format!("{}", var), "my_variable");
     }
+
+    #[test]
+    fn domains_admit_their_values_and_unknown() {
+        let pos = v!("pos", ["a", "b"]);
+        assert!(pos.admits(&"a".to_spvalue()));
+        assert!(!pos.admits(&"c".to_spvalue()));
+        assert!(pos.admits(&SPValue::String(StringOrUnknown::UNKNOWN)));
+        assert!(v!("free").admits(&"c".to_spvalue()));
+
+        let slot = iv!("slot", 1..=3);
+        assert_eq!(slot.domain, Some(SPDomain::Range(1, 3)));
+        assert!(slot.admits(&1.to_spvalue()) && slot.admits(&3.to_spvalue()));
+        assert!(!slot.admits(&0.to_spvalue()));
+        assert!(!slot.admits(&"1".to_spvalue()));
+
+        let speed = fv!("speed", [0.5, 1.0]);
+        assert!(speed.admits(&1.0.to_spvalue()));
+        assert!(!speed.admits(&0.7.to_spvalue()));
+        assert_eq!(speed.domain.unwrap().to_string(), "{0.5, 1}");
+        assert_eq!(slot.domain.unwrap().to_string(), "1..=3");
+    }
+
+    #[test]
+    fn metadata_is_optional_when_deserializing() {
+        let pos = v!("pos")
+            .described("Where the gantry is")
+            .with_unit("mm")
+            .owned_by("gantry_driver")
+            .with_kind(VariableKind::Measured);
+        assert!(pos.is_measured());
+        assert!(!v!("pos").is_measured());
+
+        let json = serde_json::to_string(&pos).unwrap();
+        assert_eq!(serde_json::from_str::<SPVariable>(&json).unwrap(), pos);
+
+        // A variable serialized before metadata existed still reads back.
+        let mut bare: serde_json::Value = serde_json::to_value(v!("pos")).unwrap();
+        for field in ["tolerance", "domain", "metadata", "ttl_ms"] {
+            bare.as_object_mut().unwrap().remove(field);
+        }
+        assert_eq!(serde_json::from_value::<SPVariable>(bare).unwrap(), v!("pos"));
+    }
 }
\ No newline at end of file
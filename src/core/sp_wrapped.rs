@@ -4,12 +4,13 @@
 //! comparison, and each right-hand side of an assignment, is either a literal
 //! [`SPValue`] or a reference to an [`SPVariable`] that is looked up in the
 //! [`State`] at evaluation time. The `Array` and `Map` variants build composite
-//! values out of other operands.
+//! values out of other operands, and `Expression` computes one with
+//! arithmetic.
 
 use crate::*;
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{fmt, time::SystemTime};
 
 /// An operand in a predicate or action: a literal, a variable, or a composite
 /// of those.
@@ -37,6 +38,203 @@ pub enum SPWrapped {
     Array(Vec<SPWrapped>),
     /// A map built from other operands; both keys and values are evaluated.
     Map(Vec<(SPWrapped, SPWrapped)>),
+    /// Arithmetic over other operands, such as `var:count * 2 + 1`.
+    Expression(Box<SPExpression>),
+    /// A part of a structured variable, such as `robot_pose.translation.z`;
+    /// see [`PathSegment`].
+    Path(SPVariable, Vec<PathSegment>),
+}
+
+/// An arithmetic expression over numeric operands, see
+/// [`SPWrapped::Expression`].
+///
+/// Both operands of a binary operator must have the same type, `i64` or
+/// `f64`, and the result has that type too; there is no implicit conversion
+/// between the two. An `UNKNOWN` operand, a division by zero or an `i64`
+/// overflow makes the result `UNKNOWN`. Integer division truncates. The
+/// non-numeric operands are those of `len`, an array or a map, and of `age`, a
+/// time. Durations are `i64` milliseconds.
+///
+/// ```
+/// use micro_sp::*;
+///
+/// let count = SPVariable::new("count", SPValueType::Int64);
+/// let state = State::from_vec(&vec![(count.clone(), 7.to_spvalue())]);
+///
+/// let doubled = SPExpression::Add(
+///     SPExpression::Mul(count.wrap(), 2.wrap()).wrap(),
+///     1.wrap(),
+/// );
+/// assert_eq!(doubled.wrap().evaluate(&state, "docs"), 15.to_spvalue());
+/// ```
+#[derive(Debug, PartialEq, PartialOrd, Ord, Eq, Clone, Hash, Serialize, Deserialize)]
+pub enum SPExpression {
+    /// `a + b`.
+    Add(SPWrapped, SPWrapped),
+    /// `a - b`.
+    Sub(SPWrapped, SPWrapped),
+    /// `a * b`.
+    Mul(SPWrapped, SPWrapped),
+    /// `a / b`.
+    Div(SPWrapped, SPWrapped),
+    /// `min(a, b)`.
+    Min(SPWrapped, SPWrapped),
+    /// `max(a, b)`.
+    Max(SPWrapped, SPWrapped),
+    /// `abs(a)`.
+    Abs(SPWrapped),
+    /// `len(a)`: the number of elements of an array or entries of a map,
+    /// as an `i64`.
+    Len(SPWrapped),
+    /// `now()`: the current time, read when the expression is evaluated.
+    Now,
+    /// `age(t)`: the milliseconds from the time `t` until now, as an `i64`;
+    /// negative for a time in the future and `UNKNOWN` for an `UNKNOWN` time.
+    /// Compare it with a duration literal, `age(var:last_seen) > dur:5s`.
+    Age(SPWrapped),
+}
+
+impl SPExpression {
+    /// The operands, in order.
+    pub fn operands(&self) -> Vec<&SPWrapped> {
+        match self {
+            SPExpression::Add(a, b)
+            | SPExpression::Sub(a, b)
+            | SPExpression::Mul(a, b)
+            | SPExpression::Div(a, b)
+            | SPExpression::Min(a, b)
+            | SPExpression::Max(a, b) => vec![a, b],
+            SPExpression::Abs(a) | SPExpression::Len(a) | SPExpression::Age(a) => vec![a],
+            SPExpression::Now => vec![],
+        }
+    }
+
+    /// The type of the result: `i64` for `len` and `age`, a time for `now`,
+    /// otherwise that of the first operand, if that is known.
+    pub fn static_type(&self) -> Option<SPValueType> {
+        match self {
+            SPExpression::Len(_) | SPExpression::Age(_) => Some(SPValueType::Int64),
+            SPExpression::Now => Some(SPValueType::Time),
+            _ => self.operands()[0].static_type(),
+        }
+    }
+
+    /// Wrap this expression as an [`SPWrapped::Expression`] operand.
+    pub fn wrap(self) -> SPWrapped {
+        SPWrapped::Expression(Box::new(self))
+    }
+
+    /// Computes the expression's value in `state`.
+    ///
+    /// Operands of the wrong type are a model error that the parser rejects;
+    /// for expressions built by hand they are logged and give `UNKNOWN`.
+    pub fn evaluate(&self, state: &State, log_target: &str) -> SPValue {
+        let values: Vec<SPValue> = self
+            .operands()
+            .iter()
+            .map(|operand| operand.evaluate(state, log_target))
+            .collect();
+        let result_type = self.static_type().unwrap_or_else(|| values[0].has_type());
+        let computed = match (self, values.as_slice()) {
+            (SPExpression::Len(_), [SPValue::Array(ArrayOrUnknown::Array(items))]) => {
+                Some((items.len() as i64).to_spvalue())
+            }
+            (SPExpression::Len(_), [SPValue::Map(MapOrUnknown::Map(pairs))]) => {
+                Some((pairs.len() as i64).to_spvalue())
+            }
+            (SPExpression::Len(_), [SPValue::Array(_) | SPValue::Map(_)]) => None,
+            (SPExpression::Now, []) => Some(SystemTime::now().to_spvalue()),
+            (SPExpression::Age(_), [SPValue::Time(TimeOrUnknown::Time(t))]) => {
+                Some(match SystemTime::now().duration_since(*t) {
+                    Ok(age) => age.as_millis() as i64,
+                    Err(ahead) => -(ahead.duration().as_millis() as i64),
+                }
+                .to_spvalue())
+            }
+            (SPExpression::Age(_), [SPValue::Time(_)]) => None,
+            (
+                SPExpression::Abs(_),
+                [SPValue::Int64(IntOrUnknown::Int64(a))],
+            ) => a.checked_abs().map(|a| a.to_spvalue()),
+            (
+                SPExpression::Abs(_),
+                [SPValue::Float64(FloatOrUnknown::Float64(a))],
+            ) => Some(a.abs().to_spvalue()),
+            (
+                _,
+                [
+                    SPValue::Int64(IntOrUnknown::Int64(a)),
+                    SPValue::Int64(IntOrUnknown::Int64(b)),
+                ],
+            ) => match self {
+                SPExpression::Add(..) => a.checked_add(*b),
+                SPExpression::Sub(..) => a.checked_sub(*b),
+                SPExpression::Mul(..) => a.checked_mul(*b),
+                SPExpression::Div(..) => a.checked_div(*b),
+                SPExpression::Min(..) => Some(*a.min(b)),
+                SPExpression::Max(..) => Some(*a.max(b)),
+                SPExpression::Abs(_)
+                | SPExpression::Len(_)
+                | SPExpression::Now
+                | SPExpression::Age(_) => None,
+            }
+            .map(|i| i.to_spvalue()),
+            (
+                _,
+                [
+                    SPValue::Float64(FloatOrUnknown::Float64(a)),
+                    SPValue::Float64(FloatOrUnknown::Float64(b)),
+                ],
+            ) => match self {
+                SPExpression::Add(..) => Some(a.0 + b.0),
+                SPExpression::Sub(..) => Some(a.0 - b.0),
+                SPExpression::Mul(..) => Some(a.0 * b.0),
+                SPExpression::Div(..) if b.0 != 0.0 => Some(a.0 / b.0),
+                SPExpression::Div(..) => None,
+                SPExpression::Min(..) => Some(a.0.min(b.0)),
+                SPExpression::Max(..) => Some(a.0.max(b.0)),
+                SPExpression::Abs(_)
+                | SPExpression::Len(_)
+                | SPExpression::Now
+                | SPExpression::Age(_) => None,
+            }
+            .map(|f| f.to_spvalue()),
+            _ => {
+                let types: Vec<SPValueType> = values.iter().map(|v| v.has_type()).collect();
+                let numeric = types
+                    .iter()
+                    .all(|t| matches!(t, SPValueType::Int64 | SPValueType::Float64));
+                if matches!(self, SPExpression::Len(_) | SPExpression::Age(_))
+                    || !numeric
+                    || types.iter().any(|t| *t != result_type)
+                {
+                    log::error!(target: log_target,
+                        "Cannot evaluate '{}' over {:?} operands.", self, types);
+                }
+                None
+            }
+        };
+        computed.unwrap_or_else(|| result_type.unknown())
+    }
+}
+
+/// Renders the expression as the DSL writes it, e.g. `(count * 2)` or
+/// `abs(x)`.
+impl fmt::Display for SPExpression {
+    fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SPExpression::Add(a, b) => write!(fmtr, "({} + {})", a, b),
+            SPExpression::Sub(a, b) => write!(fmtr, "({} - {})", a, b),
+            SPExpression::Mul(a, b) => write!(fmtr, "({} * {})", a, b),
+            SPExpression::Div(a, b) => write!(fmtr, "({} / {})", a, b),
+            SPExpression::Min(a, b) => write!(fmtr, "min({}, {})", a, b),
+            SPExpression::Max(a, b) => write!(fmtr, "max({}, {})", a, b),
+            SPExpression::Abs(a) => write!(fmtr, "abs({})", a),
+            SPExpression::Len(a) => write!(fmtr, "len({})", a),
+            SPExpression::Now => write!(fmtr, "now()"),
+            SPExpression::Age(a) => write!(fmtr, "age({})", a),
+        }
+    }
 }
 
 impl SPWrapped {
@@ -72,6 +270,14 @@ impl SPWrapped {
                     .collect();
                 SPValue::Map(MapOrUnknown::Map(evaluated_pairs))
             }
+
+            SPWrapped::Expression(expression) => expression.evaluate(state, log_target),
+
+            SPWrapped::Path(var, path) => state
+                .get_value(&var.name, log_target)
+                .unwrap_or_else(|| panic!("Variable '{}' not in state.", var.name))
+                .get_path(path)
+                .unwrap_or_else(|| self.static_type().unwrap_or(SPValueType::String).unknown()),
         }
     }
 
@@ -93,6 +299,40 @@ impl SPWrapped {
                     vars
                 })
                 .collect(),
+            SPWrapped::Path(v, _) => vec![v.clone()],
+            SPWrapped::Expression(expression) => expression
+                .operands()
+                .iter()
+                .flat_map(|x| x.get_variables())
+                .collect(),
+        }
+    }
+
+    /// Whether evaluating this operand reads the clock, through `now()` or
+    /// `age(..)`.
+    pub fn reads_clock(&self) -> bool {
+        match self {
+            SPWrapped::SPVariable(_) | SPWrapped::SPValue(_) | SPWrapped::Path(..) => false,
+            SPWrapped::Array(arr) => arr.iter().any(|x| x.reads_clock()),
+            SPWrapped::Map(map) => map.iter().any(|(k, v)| k.reads_clock() || v.reads_clock()),
+            SPWrapped::Expression(expression) => {
+                matches!(**expression, SPExpression::Now | SPExpression::Age(_))
+                    || expression.operands().iter().any(|x| x.reads_clock())
+            }
+        }
+    }
+
+    /// The type this operand evaluates to, known without a state: a
+    /// variable's declared type or a literal's own type. `None` for a part of
+    /// a map or an array, whose type depends on what the state holds.
+    pub fn static_type(&self) -> Option<SPValueType> {
+        match self {
+            SPWrapped::SPVariable(v) => Some(v.has_type()),
+            SPWrapped::SPValue(val) => Some(val.has_type()),
+            SPWrapped::Array(_) => Some(SPValueType::Array),
+            SPWrapped::Map(_) => Some(SPValueType::Map),
+            SPWrapped::Expression(expression) => expression.static_type(),
+            SPWrapped::Path(v, path) => path_type(v.has_type(), path).ok().flatten(),
         }
     }
 }
@@ -282,7 +522,20 @@ impl fmt::Display for SPWrapped {
                 },
             },
             SPWrapped::SPVariable(var) => write!(fmtr, "{}", var.name.to_owned()),
-            _ => write!(fmtr, "TODO!"),
+            SPWrapped::Expression(expression) => write!(fmtr, "{}", expression),
+            SPWrapped::Path(var, path) => {
+                write!(fmtr, "{}", var.name)?;
+                path.iter().try_for_each(|segment| write!(fmtr, "{}", segment))
+            }
+            SPWrapped::Array(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(fmtr, "[{}]", items.join(", "))
+            }
+            SPWrapped::Map(pairs) => {
+                let pairs: Vec<String> =
+                    pairs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(fmtr, "{{{}}}", pairs.join(", "))
+            }
         }
     }
 }
@@ -506,16 +759,19 @@ mod composite_tests {
         );
     }
 
-    /// A composite has no `Display` form of its own - it renders as the
-    /// placeholder. Worth pinning because these strings end up in the "please
-    /// satisfy the runner guard" message a user reads.
+    /// A composite renders as its operands in brackets. Worth pinning because
+    /// these strings end up in the "please satisfy the runner guard" message a
+    /// user reads, and `var:x in [a, b]` is a common guard.
     #[test]
-    fn a_composite_renders_as_a_placeholder() {
+    fn a_composite_renders_its_operands() {
         let state = state();
-        assert_eq!(SPWrapped::Array(vec![var("a", &state)]).to_string(), "TODO!");
+        assert_eq!(
+            SPWrapped::Array(vec![var("a", &state), 2.wrap()]).to_string(),
+            "[a, 2]"
+        );
         assert_eq!(
             SPWrapped::Map(vec![(var("k", &state), var("a", &state))]).to_string(),
-            "TODO!"
+            "{k: a}"
         );
     }
 
@@ -529,3 +785,91 @@ mod composite_tests {
         let _ = missing.evaluate(&state, TARGET);
     }
 }
+
+#[cfg(test)]
+mod expression_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (iv!("count"), 7.to_spvalue()),
+            (fv!("x"), 1.5.to_spvalue()),
+            (fv!("y"), 4.0.to_spvalue()),
+            (iv!("missing"), SPValue::Int64(IntOrUnknown::UNKNOWN)),
+        ])
+    }
+
+    #[test]
+    fn integer_and_float_arithmetic() {
+        let s = state();
+        let cases = [
+            (SPExpression::Sub(iv!("count").wrap(), 10.wrap()), (-3).to_spvalue()),
+            (SPExpression::Div(iv!("count").wrap(), 2.wrap()), 3.to_spvalue()),
+            (SPExpression::Abs(SPExpression::Sub(1.wrap(), 5.wrap()).wrap()), 4.to_spvalue()),
+            (SPExpression::Min(iv!("count").wrap(), 3.wrap()), 3.to_spvalue()),
+            (SPExpression::Max(fv!("x").wrap(), fv!("y").wrap()), 4.0.to_spvalue()),
+            (SPExpression::Mul(fv!("x").wrap(), fv!("y").wrap()), 6.0.to_spvalue()),
+            (SPExpression::Sub(fv!("x").wrap(), fv!("y").wrap()), (-2.5).to_spvalue()),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(expression.wrap().evaluate(&s, TARGET), expected);
+        }
+    }
+
+    #[test]
+    fn undefined_results_are_unknown() {
+        let s = state();
+        let unknown_int = SPValue::Int64(IntOrUnknown::UNKNOWN);
+        let cases = [
+            (SPExpression::Div(iv!("count").wrap(), 0.wrap()), unknown_int.clone()),
+            (SPExpression::Add(i64::MAX.wrap(), 1.wrap()), unknown_int.clone()),
+            (SPExpression::Add(iv!("missing").wrap(), 1.wrap()), unknown_int.clone()),
+            (SPExpression::Add(iv!("count").wrap(), 1.0.wrap()), unknown_int),
+            (
+                SPExpression::Div(fv!("x").wrap(), 0.0.wrap()),
+                SPValue::Float64(FloatOrUnknown::UNKNOWN),
+            ),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(expression.wrap().evaluate(&s, TARGET), expected);
+        }
+    }
+
+    #[test]
+    fn expressions_expose_their_variables() {
+        let expression = SPExpression::Max(
+            SPExpression::Mul(iv!("count").wrap(), 2.wrap()).wrap(),
+            iv!("missing").wrap(),
+        )
+        .wrap();
+        assert_eq!(expression.get_variables(), vec![iv!("count"), iv!("missing")]);
+        assert_eq!(expression.static_type(), Some(SPValueType::Int64));
+        assert_eq!(expression.to_string(), "max((count * 2), missing)");
+    }
+
+    #[test]
+    fn now_and_age_measure_wall_clock_milliseconds() {
+        use std::time::{Duration, SystemTime};
+        let s = State::from_vec(&vec![
+            (tv!("seen"), (SystemTime::now() - Duration::from_secs(2)).to_spvalue()),
+            (tv!("due"), (SystemTime::now() + Duration::from_secs(60)).to_spvalue()),
+            (tv!("never"), SPValue::Time(TimeOrUnknown::UNKNOWN)),
+        ]);
+        let age = |var: SPVariable| SPExpression::Age(var.wrap()).wrap().evaluate(&s, TARGET);
+        let SPValue::Int64(IntOrUnknown::Int64(seen)) = age(tv!("seen")) else {
+            panic!("age of a known time is known");
+        };
+        assert!((2_000..3_000).contains(&seen));
+        assert!(age(tv!("due")) < 0.to_spvalue());
+        assert_eq!(age(tv!("never")), SPValue::Int64(IntOrUnknown::UNKNOWN));
+
+        let SPValue::Time(TimeOrUnknown::Time(now)) = SPExpression::Now.wrap().evaluate(&s, TARGET)
+        else {
+            panic!("now is a known time");
+        };
+        assert!(now.elapsed().unwrap_or_default() < Duration::from_secs(1));
+        assert_eq!(SPExpression::Age(tv!("seen").wrap()).to_string(), "age(seen)");
+    }
+}
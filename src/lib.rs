@@ -64,7 +64,7 @@
 //! let connection_manager = Arc::new(ConnectionManager::new().await);
 //!
 //! // Seed Redis with the model's variables, then hand it to the runners.
-//! main_runner(&"sp".to_string(), model, 3, &connection_manager).await;
+//! main_runner(&"sp".to_string(), model, 3, OperationHandlers::default(), &connection_manager).await;
 //! # }
 //! ```
 //!
@@ -112,6 +112,7 @@ pub const NANOID_ALPHABET: [char; 62] = [
 
 pub mod core;
 pub use crate::core::sp_assignment::*;
+pub use crate::core::sp_path::*;
 pub use crate::core::sp_state::*;
 pub use crate::core::sp_value::*;
 pub use crate::core::sp_variable::*;
@@ -119,28 +120,44 @@ pub use crate::core::sp_wrapped::*;
 
 pub mod modelling;
 pub use crate::modelling::action::*;
+pub use crate::modelling::alarm::*;
+pub use crate::modelling::lint::*;
 pub use crate::modelling::model::*;
 pub use crate::modelling::operation::*;
+pub use crate::modelling::operation_result::*;
+pub use crate::modelling::operator_task::*;
 pub use crate::modelling::parser::*;
 pub use crate::modelling::predicate::*;
+pub use crate::modelling::retry_policy::*;
+pub use crate::modelling::service_spec::*;
 pub use crate::modelling::sops::*;
 pub use crate::modelling::transition::*;
+pub use crate::modelling::watchdog::*;
 
 pub mod planning;
 pub use crate::planning::operation::*;
 pub use crate::planning::transition::*;
 
 pub mod running;
+pub use crate::running::alarm_runner::*;
 pub use crate::running::auto_runner::*;
+pub use crate::running::compensation::*;
+pub use crate::running::dashboard::*;
 pub use crate::running::main_runner::*;
+pub use crate::running::operation_handlers::*;
+pub use crate::running::operation_statistics::*;
 pub use crate::running::plan_runner::*;
 pub use crate::running::planner_ticker::*;
 pub use crate::running::runner_keys::*;
 pub use crate::running::tick::*;
 pub use crate::running::runner_states::*;
+pub use crate::running::service_server::*;
 pub use crate::running::sop_runner::*;
+pub use crate::running::step_mode::*;
 pub use crate::running::state_init::*;
 pub use crate::running::time_runner::*;
+pub use crate::running::ttl_runner::*;
+pub use crate::running::watchdog_runner::*;
 
 pub mod management;
 pub use crate::management::connection::*;
@@ -2,11 +2,13 @@
 //!
 //! Each macro is sugar for `SPVariable::new(name, SPValueType::X)`, one per
 //! value type, so a model declaration reads as a list of names rather than as a
-//! wall of repeated constructor calls.
+//! wall of repeated constructor calls. `v!`, `iv!` and `fv!` also take a
+//! domain as a second argument; see [`SPDomain`](crate::SPDomain).
 
 /// Declares a `String`-typed variable.
 ///
-/// `v!("pos")` is `SPVariable::new("pos", SPValueType::String)`.
+/// `v!("pos")` is `SPVariable::new("pos", SPValueType::String)`, and
+/// `v!("pos", ["a", "b"])` restricts it to the values `a` and `b`.
 ///
 /// ```
 /// use micro_sp::*;
@@ -14,12 +16,21 @@
 /// let pos = v!("pos");
 /// assert_eq!(pos.name, "pos");
 /// assert_eq!(pos.has_type(), SPValueType::String);
+///
+/// let pos = v!("pos", ["a", "b"]);
+/// assert_eq!(pos.domain, Some(SPDomain::Values(vec!["a".to_spvalue(), "b".to_spvalue()])));
 /// ```
 #[macro_export]
 macro_rules! v {
     ($a:expr) => {
         SPVariable::new($a.clone(), SPValueType::String)
     };
+    ($a:expr, [$($value:expr),* $(,)?]) => {
+        SPVariable::new($a.clone(), SPValueType::String).with_domain(vec![$($value.to_spvalue()),*])
+    };
+    ($a:expr, $domain:expr) => {
+        SPVariable::new($a.clone(), SPValueType::String).with_domain($domain)
+    };
 }
 
 /// Declares a `Bool`-typed variable.
@@ -34,22 +45,36 @@ macro_rules! bv {
 
 /// Declares an `Int64`-typed variable.
 ///
-/// `iv!("retries")` is `SPVariable::new("retries", SPValueType::Int64)`.
+/// `iv!("retries")` is `SPVariable::new("retries", SPValueType::Int64)`;
+/// `iv!("retries", 0..=3)` or `iv!("retries", [0, 1, 3])` adds a domain.
 #[macro_export]
 macro_rules! iv {
     ($a:expr) => {
         SPVariable::new($a.clone(), SPValueType::Int64)
     };
+    ($a:expr, [$($value:expr),* $(,)?]) => {
+        SPVariable::new($a.clone(), SPValueType::Int64).with_domain(vec![$($value.to_spvalue()),*])
+    };
+    ($a:expr, $domain:expr) => {
+        SPVariable::new($a.clone(), SPValueType::Int64).with_domain($domain)
+    };
 }
 
 /// Declares a `Float64`-typed variable.
 ///
-/// `fv!("weight")` is `SPVariable::new("weight", SPValueType::Float64)`.
+/// `fv!("weight")` is `SPVariable::new("weight", SPValueType::Float64)`;
+/// `fv!("speed", [0.5, 1.0])` adds a domain.
 #[macro_export]
 macro_rules! fv {
     ($a:expr) => {
         SPVariable::new($a.clone(), SPValueType::Float64)
     };
+    ($a:expr, [$($value:expr),* $(,)?]) => {
+        SPVariable::new($a.clone(), SPValueType::Float64).with_domain(vec![$($value.to_spvalue()),*])
+    };
+    ($a:expr, $domain:expr) => {
+        SPVariable::new($a.clone(), SPValueType::Float64).with_domain($domain)
+    };
 }
 
 /// Declares an `Array`-typed variable.
@@ -9,6 +9,7 @@ use crate::SPConnection;
 
 mod apply;
 mod build_state;
+mod expire_stale;
 mod get_full_state;
 mod get_sp_value;
 mod get_state_for_keys;
@@ -141,6 +142,50 @@ impl StateManager {
         build_state::build_state(keys, values)
     }
 
+    /// Reset every variable in `variables` whose `ttl_ms` has lapsed since it
+    /// was last written to its typed `UNKNOWN`, and return their names.
+    ///
+    /// One reconciliation pass: a pipelined `GET` and `PTTL` per variable with
+    /// a TTL, then one pipelined write. A value written since the previous
+    /// pass is armed with a key expiry, which the next `SET` clears again, so
+    /// the TTL counts from the first pass after the latest write. A key that
+    /// is missing altogether is restored as `UNKNOWN`. Like every other
+    /// read-modify-write here this is not atomic: a write racing the pass can
+    /// be overwritten by the `UNKNOWN` and has to be repeated. Failures are
+    /// logged and reset nothing.
+    pub async fn expire_stale(
+        con: &mut SPConnection,
+        variables: &[SPVariable],
+        log_target: &str,
+    ) -> Vec<String> {
+        expire_stale::expire_stale(con, variables, log_target).await
+    }
+
+    /// The results `operation` reported, oldest first, from its
+    /// `{operation}_result_history`; see [`ResultDeclaration`]. Empty when it
+    /// has none or they cannot be read.
+    pub async fn get_operation_results(
+        con: &mut SPConnection,
+        operation: &str,
+    ) -> Vec<OperationResult> {
+        let history = format!("{}_result_history", operation);
+        match get_sp_value::get_sp_value(con, &history).await {
+            Some(value) => OperationResult::history_from_spvalue(&value),
+            None => vec![],
+        }
+    }
+
+    /// The runtime statistics of `operation`, by its name in the model, from
+    /// `{operation}_stats`; see [`OperationStatistics`]. `None` before its
+    /// first run has ended or when they cannot be read.
+    pub async fn get_operation_statistics(
+        con: &mut SPConnection,
+        operation: &str,
+    ) -> Option<OperationStatistics> {
+        let value = get_sp_value::get_sp_value(con, &statistics_key(operation)).await?;
+        OperationStatistics::from_spvalue(&value)
+    }
+
     /// `FLUSHDB` - erase the entire Redis database.
     ///
     /// State variables *and* transform keys share one keyspace, so this takes
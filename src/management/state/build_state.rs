@@ -136,4 +136,32 @@ mod tests_for_build_state {
 
         assert!(result_state.state.is_empty());
     }
+
+    /// A state read back from Redis has lost the domains the model declared.
+    /// Actions still hold to the domain their own variable carries, and a goal
+    /// is checked once the declarations are put back.
+    #[test]
+    fn domains_hold_for_a_state_read_back_from_redis() {
+        let pos = v!("pos", ["a", "b"]);
+        let declared = State::from_vec(&vec![
+            (pos.clone(), "a".to_spvalue()),
+            (v!("target"), "z".to_spvalue()),
+        ]);
+        let keys: Vec<String> = vec!["pos".to_string(), "target".to_string()];
+        let values = keys
+            .iter()
+            .map(|key| serde_json::to_string(&declared.get_value(key, "test").unwrap()).ok())
+            .collect();
+        let mut read = build_state(keys, values);
+        assert_eq!(read.get_assignment("pos", "test").var.domain, None);
+
+        let action = pred_parser::action("var:pos <- var:target", &declared).unwrap();
+        let after = action.assign(&read, "test");
+        assert_eq!(after.get_value("pos", "test"), Some("a".to_spvalue()));
+
+        let goal = pred_parser::pred("var:pos == z", &read).unwrap();
+        assert!(goal.domain_violations(&read).is_empty());
+        read.declare_mut(&[pos]);
+        assert_eq!(goal.domain_violations(&read).len(), 1);
+    }
 }
\ No newline at end of file
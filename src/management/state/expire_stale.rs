@@ -0,0 +1,151 @@
+use crate::*;
+use redis::Value;
+
+/// How much longer than its TTL a variable's key may live in Redis.
+///
+/// The expiry set on a key is only a marker that this pass has seen the
+/// latest write: `SET` and `MSET` clear it, so a key without one has been
+/// written since. The pass resets the value itself once the TTL lapses; Redis
+/// only deletes the key if nothing has reconciled it for this long, because a
+/// missing key makes every reader that expects it panic.
+pub(super) const EXPIRY_BACKSTOP_MS: u64 = 3_600_000;
+
+/// What a pass does with one variable, given its value and `PTTL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Freshness {
+    /// `UNKNOWN` already: nothing can go stale.
+    Idle,
+    /// Written since the last pass: start counting from now.
+    Arm,
+    /// Armed and still within its TTL.
+    Fresh,
+    /// Past its TTL, or gone from Redis: write `UNKNOWN`.
+    Reset,
+}
+
+pub(super) fn freshness(value: Option<&SPValue>, pttl_ms: i64, ttl_ms: u64) -> Freshness {
+    match value {
+        None => Freshness::Reset,
+        Some(value) if value.is_unknown() => Freshness::Idle,
+        Some(_) if pttl_ms < 0 => Freshness::Arm,
+        Some(_) => {
+            let armed_for_ms = (ttl_ms + EXPIRY_BACKSTOP_MS) as i64 - pttl_ms;
+            if armed_for_ms >= ttl_ms as i64 {
+                Freshness::Reset
+            } else {
+                Freshness::Fresh
+            }
+        }
+    }
+}
+
+pub(super) async fn expire_stale(
+    con: &mut SPConnection,
+    variables: &[SPVariable],
+    log_target: &str,
+) -> Vec<String> {
+    let expiring: Vec<(&SPVariable, u64)> = variables
+        .iter()
+        .filter_map(|var| var.ttl_ms.map(|ttl_ms| (var, ttl_ms)))
+        .collect();
+    if expiring.is_empty() {
+        return vec![];
+    }
+
+    let mut read = redis::pipe();
+    for (var, _) in &expiring {
+        read.get(&var.name).pttl(&var.name);
+    }
+    let replies: Vec<Value> = match read.query_async(con).await {
+        Ok(replies) => replies,
+        Err(e) => {
+            log::error!(target: log_target, "Failed to read variable expiries from Redis: {e}");
+            return vec![];
+        }
+    };
+
+    let mut write = redis::pipe();
+    let mut reset = vec![];
+    for ((var, ttl_ms), reply) in expiring.iter().zip(replies.chunks(2)) {
+        let value: Option<SPValue> = redis::from_redis_value::<Option<String>>(&reply[0])
+            .ok()
+            .flatten()
+            .and_then(|raw| serde_json::from_str(&raw).ok());
+        let pttl_ms = redis::from_redis_value::<i64>(&reply[1]).unwrap_or(-2);
+
+        match freshness(value.as_ref(), pttl_ms, *ttl_ms) {
+            Freshness::Idle | Freshness::Fresh => {}
+            Freshness::Arm => {
+                write.pexpire(&var.name, (ttl_ms + EXPIRY_BACKSTOP_MS) as i64).ignore();
+            }
+            Freshness::Reset => match serde_json::to_string(&var.value_type.unknown()) {
+                Ok(unknown) => {
+                    write.set(&var.name, unknown).ignore();
+                    reset.push(var.name.clone());
+                }
+                Err(e) => log::error!(target: log_target, "Failed to serialize UNKNOWN for '{}': {e}", var.name),
+            },
+        }
+    }
+
+    if let Err(e) = write.query_async::<()>(con).await {
+        log::error!(target: log_target, "Failed to write variable expiries to Redis: {e}");
+        return vec![];
+    }
+    reset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use testcontainers::{ImageExt, core::ContainerPort, runners::AsyncRunner};
+    use testcontainers_modules::redis::Redis;
+
+    const TTL: u64 = 1000;
+
+    #[test]
+    fn a_write_is_armed_and_then_goes_stale() {
+        let seen = 1.to_spvalue();
+        assert_eq!(freshness(Some(&seen), -1, TTL), Freshness::Arm);
+        let just_armed = (TTL + EXPIRY_BACKSTOP_MS) as i64;
+        assert_eq!(freshness(Some(&seen), just_armed, TTL), Freshness::Fresh);
+        assert_eq!(freshness(Some(&seen), just_armed - 999, TTL), Freshness::Fresh);
+        assert_eq!(freshness(Some(&seen), just_armed - 1000, TTL), Freshness::Reset);
+    }
+
+    #[test]
+    fn unknown_values_never_go_stale_and_missing_ones_are_restored() {
+        let unknown = SPValue::Int64(IntOrUnknown::UNKNOWN);
+        assert_eq!(freshness(Some(&unknown), -1, TTL), Freshness::Idle);
+        assert_eq!(freshness(None, -2, TTL), Freshness::Reset);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_expire_stale_resets_only_lapsed_variables() {
+        let _container = Redis::default()
+            .with_mapped_port(6379, ContainerPort::Tcp(6379))
+            .start()
+            .await
+            .unwrap();
+        let mut con = ConnectionManager::new().await.get_connection().await;
+        StateManager::flush_state(&mut con).await;
+
+        let tool = v!("tool").with_ttl(std::time::Duration::from_millis(100));
+        let pos = fv!("pos");
+        StateManager::set_sp_value(&mut con, "tool", &"gripper".to_spvalue()).await;
+        StateManager::set_sp_value(&mut con, "pos", &1.0.to_spvalue()).await;
+        let variables = vec![tool, pos];
+
+        assert!(expire_stale(&mut con, &variables, "test").await.is_empty());
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        assert_eq!(expire_stale(&mut con, &variables, "test").await, vec!["tool".to_string()]);
+
+        assert_eq!(
+            StateManager::get_sp_value(&mut con, "tool").await,
+            Some(SPValue::String(StringOrUnknown::UNKNOWN))
+        );
+        assert_eq!(StateManager::get_sp_value(&mut con, "pos").await, Some(1.0.to_spvalue()));
+    }
+}
@@ -47,6 +47,10 @@ mod tests_for_get_state_for_keys {
         let var1 = SPVariable {
             name: "x".to_string(),
             value_type: SPValueType::Int64,
+            tolerance: None,
+            domain: None,
+            metadata: SPVariableMetadata::default(),
+            ttl_ms: None,
         };
         let val1 = SPValue::Int64(IntOrUnknown::Int64(1));
         let assignment1 = SPAssignment::new(var1.clone(), val1.clone());
@@ -54,6 +58,10 @@ mod tests_for_get_state_for_keys {
         let var2 = SPVariable {
             name: "y".to_string(),
             value_type: SPValueType::String,
+            tolerance: None,
+            domain: None,
+            metadata: SPVariableMetadata::default(),
+            ttl_ms: None,
         };
         let val2 = SPValue::String(StringOrUnknown::String("hello".to_string()));
         let assignment2 = SPAssignment::new(var2.clone(), val2.clone());
@@ -90,6 +98,10 @@ mod tests_for_get_state_for_keys {
         let var1 = SPVariable {
             name: "x".to_string(),
             value_type: SPValueType::Int64,
+            tolerance: None,
+            domain: None,
+            metadata: SPVariableMetadata::default(),
+            ttl_ms: None,
         };
         let val1 = SPValue::Int64(IntOrUnknown::Int64(100));
         let assignment1 = SPAssignment::new(var1.clone(), val1.clone());
@@ -156,6 +168,10 @@ mod tests_for_get_state_for_keys {
         let var_good = SPVariable {
             name: "good_key".to_string(),
             value_type: SPValueType::Bool,
+            tolerance: None,
+            domain: None,
+            metadata: SPVariableMetadata::default(),
+            ttl_ms: None,
         };
         let val_good = SPValue::Bool(BoolOrUnknown::Bool(true));
         let assignment_good = SPAssignment::new(var_good, val_good.clone());
@@ -2,7 +2,9 @@
 //!
 //! An action writes one state variable, either replacing its value or adding to
 //! / subtracting from it. The right-hand side may itself be a variable, in which
-//! case it is read from the state at the moment the action is applied.
+//! case it is read from the state at the moment the action is applied. Array
+//! and map variables can also be edited in place - pushed to, popped from,
+//! inserted into - so a queue of parts can live in the state itself.
 
 use serde::{Deserialize, Serialize};
 
@@ -18,6 +20,25 @@ pub enum ActionType {
     Increment,
     /// Subtract the right-hand side from the variable's current value.
     Decrement,
+    /// Append the right-hand side to an array.
+    Push,
+    /// Drop the first element of an array; an empty array stays empty.
+    PopFront,
+    /// Drop the first element of an array equal to the right-hand side, if
+    /// there is one.
+    Remove,
+    /// Empty an array or a map.
+    Clear,
+    /// Set keys of a map: the right-hand side is a map whose entries replace
+    /// the values of existing keys in place, or are appended after the last
+    /// entry.
+    MapInsert,
+    /// Drop the entry of a map with the right-hand side as key, if there is
+    /// one.
+    MapRemove,
+    /// Replace only the part of the variable at this path, e.g.
+    /// `config.speed`; see [`SPValue::set_path`].
+    AssignAt(Vec<PathSegment>),
 }
 
 /// Actions update the assignments of the state variables.
@@ -88,6 +109,70 @@ impl Action {
         }
     }
 
+    /// An assignment to a part of `var`: the part at `path` takes the value of
+    /// `var_or_val`.
+    pub fn assign_at(var: SPVariable, path: Vec<PathSegment>, var_or_val: SPWrapped) -> Action {
+        Action {
+            var,
+            var_or_val,
+            action_type: ActionType::AssignAt(path),
+        }
+    }
+
+    /// Append `value` to the array `var`.
+    pub fn push(var: SPVariable, value: SPWrapped) -> Action {
+        Action {
+            var,
+            var_or_val: value,
+            action_type: ActionType::Push,
+        }
+    }
+
+    /// Drop the first element of the array `var`.
+    pub fn pop_front(var: SPVariable) -> Action {
+        Action {
+            var_or_val: var.wrap(),
+            var,
+            action_type: ActionType::PopFront,
+        }
+    }
+
+    /// Drop the first element of the array `var` that equals `value`.
+    pub fn remove(var: SPVariable, value: SPWrapped) -> Action {
+        Action {
+            var,
+            var_or_val: value,
+            action_type: ActionType::Remove,
+        }
+    }
+
+    /// Empty the array or map `var`.
+    pub fn clear(var: SPVariable) -> Action {
+        Action {
+            var_or_val: var.wrap(),
+            var,
+            action_type: ActionType::Clear,
+        }
+    }
+
+    /// Set `key` to `value` in the map `var`.
+    pub fn map_insert(var: SPVariable, key: SPWrapped, value: SPWrapped) -> Action {
+        Action {
+            var,
+            var_or_val: SPWrapped::Map(vec![(key, value)]),
+            action_type: ActionType::MapInsert,
+        }
+    }
+
+    /// Drop `key` from the map `var`.
+    pub fn map_remove(var: SPVariable, key: SPWrapped) -> Action {
+        Action {
+            var,
+            var_or_val: key,
+            action_type: ActionType::MapRemove,
+        }
+    }
+
     /// Apply this action to `state` in place.
     ///
     /// `log_target` is the `log::` target the state lookups report under.
@@ -96,12 +181,32 @@ impl Action {
     ///
     /// Panics if the variable is not in the state, if an increment/decrement
     /// targets a non-numeric variable, or if it mixes an integer with a float.
-    /// A type mismatch is a model error, not something to coerce silently.
+    /// Array and map edits likewise panic on a variable of another type. A
+    /// type mismatch is a model error, not something to coerce silently.
+    ///
+    /// Editing an `UNKNOWN` array or map leaves it `UNKNOWN` - its contents
+    /// are still not known - and logs a warning; only clearing it makes it
+    /// known. Assigning to a path that is not there, such as an index past the
+    /// end of an array, logs an error and leaves the variable unchanged, as
+    /// does a value outside the variable's [`SPVariable::domain`].
     pub fn assign_mut(&self, state: &mut State, log_target: &str) {
-        match self.action_type {
+        match &self.action_type {
             ActionType::Assign => {
                 let value_to_assign = self.var_or_val.evaluate(state, log_target);
-                state.update_mut(&self.var.name, value_to_assign);
+                self.write(state, value_to_assign, log_target);
+            }
+
+            ActionType::AssignAt(path) => {
+                let mut current_val = state
+                    .get_value(&self.var.name, log_target)
+                    .unwrap_or_else(|| panic!("Variable '{}' not in state.", self.var.name));
+                let value_to_assign = self.var_or_val.evaluate(state, log_target);
+                if current_val.set_path(path, value_to_assign) {
+                    self.write(state, current_val, log_target);
+                } else {
+                    log::error!(target: log_target,
+                        "'{}' has no part to assign to, left unchanged.", self);
+                }
             }
 
             ActionType::Increment => {
@@ -148,7 +253,7 @@ impl Action {
                     }
                 };
 
-                state.update_mut(&self.var.name, new_val);
+                self.write(state, new_val, log_target);
             }
 
             ActionType::Decrement => {
@@ -195,8 +300,95 @@ impl Action {
                     }
                 };
 
-                state.update_mut(&self.var.name, new_val);
+                self.write(state, new_val, log_target);
+            }
+
+            ActionType::Push
+            | ActionType::PopFront
+            | ActionType::Remove
+            | ActionType::Clear
+            | ActionType::MapInsert
+            | ActionType::MapRemove => {
+                let current_val = state
+                    .get_value(&self.var.name, log_target)
+                    .unwrap_or_else(|| panic!("Variable '{}' not in state.", self.var.name));
+                let operand = self.var_or_val.evaluate(state, log_target);
+                let new_val = self.edit_collection(current_val, operand, log_target);
+                self.write(state, new_val, log_target);
+            }
+        }
+    }
+
+    /// Write `val` to the action's variable, or log why not: a value outside
+    /// the variable's domain is refused. The action's own [`SPVariable`]
+    /// carries the domain the model declared, which a state read back from
+    /// Redis does not.
+    fn write(&self, state: &mut State, val: SPValue, log_target: &str) {
+        if let Err(e) = state.update_in_domain_mut(&self.var, val) {
+            log::error!(target: log_target, "{} Skipped '{}'.", e, self);
+        }
+    }
+
+    /// The array or map `current` after this action's edit with `operand`.
+    fn edit_collection(&self, current: SPValue, operand: SPValue, log_target: &str) -> SPValue {
+        match (&self.action_type, current) {
+            (ActionType::Clear, SPValue::Array(_)) => SPValue::Array(ArrayOrUnknown::Array(vec![])),
+            (ActionType::Clear, SPValue::Map(_)) => SPValue::Map(MapOrUnknown::Map(vec![])),
+            (
+                ActionType::Push | ActionType::PopFront | ActionType::Remove,
+                SPValue::Array(ArrayOrUnknown::Array(mut items)),
+            ) => {
+                match self.action_type {
+                    ActionType::Push => items.push(operand),
+                    ActionType::PopFront if !items.is_empty() => {
+                        items.remove(0);
+                    }
+                    ActionType::Remove => {
+                        if let Some(i) = items.iter().position(|item| *item == operand) {
+                            items.remove(i);
+                        }
+                    }
+                    _ => (),
+                }
+                SPValue::Array(ArrayOrUnknown::Array(items))
+            }
+            (
+                ActionType::MapInsert | ActionType::MapRemove,
+                SPValue::Map(MapOrUnknown::Map(mut pairs)),
+            ) => {
+                match (&self.action_type, operand) {
+                    (ActionType::MapInsert, SPValue::Map(MapOrUnknown::Map(entries))) => {
+                        for (key, value) in entries {
+                            match pairs.iter_mut().find(|(k, _)| *k == key) {
+                                Some(pair) => pair.1 = value,
+                                None => pairs.push((key, value)),
+                            }
+                        }
+                    }
+                    (ActionType::MapRemove, key) => pairs.retain(|(k, _)| *k != key),
+                    (_, other) => panic!(
+                        "Cannot insert '{:?}' into map variable '{}', it is not a map of entries.",
+                        other, self.var.name
+                    ),
+                }
+                SPValue::Map(MapOrUnknown::Map(pairs))
+            }
+            (
+                ActionType::Push | ActionType::PopFront | ActionType::Remove,
+                unknown @ SPValue::Array(ArrayOrUnknown::UNKNOWN),
+            )
+            | (
+                ActionType::MapInsert | ActionType::MapRemove,
+                unknown @ SPValue::Map(MapOrUnknown::UNKNOWN),
+            ) => {
+                log::warn!(target: log_target,
+                    "Variable '{}' is UNKNOWN, '{}' leaves it UNKNOWN.", self.var.name, self);
+                unknown
             }
+            (_, other) => panic!(
+                "Variable '{}' holds '{:?}', which '{}' cannot edit.",
+                self.var.name, other, self
+            ),
         }
     }
 
@@ -211,18 +403,34 @@ impl Action {
 }
 
 impl fmt::Display for Action {
-    /// Renders as `var <= value`, `var += value` or `var -= value`.
+    /// Renders as `var <= value`, `var += value` or `var -= value`, and the
+    /// array and map edits as calls, e.g. `push(queue, part)`.
     fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.action_type {
+        match &self.action_type {
             ActionType::Assign => {
                 write!(fmtr, "{} <= {}", self.var, self.var_or_val)
             }
+            ActionType::AssignAt(path) => {
+                let path: String = path.iter().map(|segment| segment.to_string()).collect();
+                write!(fmtr, "{}{} <= {}", self.var, path, self.var_or_val)
+            }
             ActionType::Increment => {
                 write!(fmtr, "{} += {}", self.var, self.var_or_val)
             }
             ActionType::Decrement => {
                 write!(fmtr, "{} -= {}", self.var, self.var_or_val)
             }
+            ActionType::Push => write!(fmtr, "push({}, {})", self.var, self.var_or_val),
+            ActionType::PopFront => write!(fmtr, "pop_front({})", self.var),
+            ActionType::Remove => write!(fmtr, "remove({}, {})", self.var, self.var_or_val),
+            ActionType::Clear => write!(fmtr, "clear({})", self.var),
+            ActionType::MapInsert => match &self.var_or_val {
+                SPWrapped::Map(entries) if entries.len() == 1 => {
+                    write!(fmtr, "map_insert({}, {}, {})", self.var, entries[0].0, entries[0].1)
+                }
+                entries => write!(fmtr, "map_insert({}, {})", self.var, entries),
+            },
+            ActionType::MapRemove => write!(fmtr, "map_remove({}, {})", self.var, self.var_or_val),
         }
     }
 }
@@ -495,3 +703,117 @@ mod arithmetic_tests {
         assert_eq!(after.get_value("counter", TARGET), Some(11.to_spvalue()));
     }
 }
+
+#[cfg(test)]
+mod collection_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn parts(names: &[&str]) -> SPValue {
+        names.iter().map(|n| n.to_spvalue()).collect::<Vec<_>>().to_spvalue()
+    }
+
+    fn stock(entries: &[(&str, i64)]) -> SPValue {
+        SPValue::Map(MapOrUnknown::Map(
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_spvalue(), v.to_spvalue()))
+                .collect(),
+        ))
+    }
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (av!("queue"), parts(&["p1", "p2", "p1"])),
+            (mv!("stock"), stock(&[("bolt", 3), ("nut", 5)])),
+            (v!("next"), "p3".to_spvalue()),
+        ])
+    }
+
+    #[test]
+    fn array_edits() {
+        let s = state();
+        let cases = [
+            (Action::push(av!("queue"), v!("next").wrap()), parts(&["p1", "p2", "p1", "p3"])),
+            (Action::pop_front(av!("queue")), parts(&["p2", "p1"])),
+            (Action::remove(av!("queue"), "p1".wrap()), parts(&["p2", "p1"])),
+            (Action::remove(av!("queue"), "p9".wrap()), parts(&["p1", "p2", "p1"])),
+            (Action::clear(av!("queue")), parts(&[])),
+        ];
+        for (action, expected) in cases {
+            let label = action.to_string();
+            assert_eq!(action.assign(&s, TARGET).get_value("queue", TARGET), Some(expected), "{label}");
+        }
+
+        let empty = Action::clear(av!("queue")).assign(&s, TARGET);
+        let still_empty = Action::pop_front(av!("queue")).assign(&empty, TARGET);
+        assert_eq!(still_empty.get_value("queue", TARGET), Some(parts(&[])));
+    }
+
+    #[test]
+    fn map_edits_keep_entry_order() {
+        let s = state();
+        let cases = [
+            (Action::map_insert(mv!("stock"), "bolt".wrap(), 7.wrap()), stock(&[("bolt", 7), ("nut", 5)])),
+            (
+                Action::map_insert(mv!("stock"), "washer".wrap(), 1.wrap()),
+                stock(&[("bolt", 3), ("nut", 5), ("washer", 1)]),
+            ),
+            (Action::map_remove(mv!("stock"), "bolt".wrap()), stock(&[("nut", 5)])),
+            (Action::map_remove(mv!("stock"), "gear".wrap()), stock(&[("bolt", 3), ("nut", 5)])),
+            (Action::clear(mv!("stock")), stock(&[])),
+        ];
+        for (action, expected) in cases {
+            let label = action.to_string();
+            assert_eq!(action.assign(&s, TARGET).get_value("stock", TARGET), Some(expected), "{label}");
+        }
+    }
+
+    #[test]
+    fn editing_an_unknown_collection_keeps_it_unknown() {
+        let s = State::from_vec(&vec![(av!("queue"), SPValue::Array(ArrayOrUnknown::UNKNOWN))]);
+        let pushed = Action::push(av!("queue"), "p1".wrap()).assign(&s, TARGET);
+        assert_eq!(pushed, s);
+        let cleared = Action::clear(av!("queue")).assign(&s, TARGET);
+        assert_eq!(cleared.get_value("queue", TARGET), Some(parts(&[])));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot edit")]
+    fn editing_a_non_collection_panics() {
+        let s = state();
+        let _ = Action::push(v!("next"), "p1".wrap()).assign(&s, TARGET);
+    }
+
+    /// The planner simulates a transition with `take_planning`, so a queue
+    /// can be planned over like any other variable.
+    #[test]
+    fn the_planner_can_work_through_a_queue() {
+        let queue = av!("queue");
+        let state = State::from_vec(&vec![(queue.clone(), parts(&["p1", "p2"]))]);
+        let transitions = vec![Transition::parse(
+            "process_next",
+            "len(var:queue) > 0",
+            "true",
+            vec!["pop_front(var:queue)"],
+            Vec::<&str>::new(),
+            &state,
+        )];
+        let goal = pred_parser::pred("len(var:queue) == 0", &state).unwrap();
+        let result = bfs_transition_planner(state, goal, transitions, 10, TARGET);
+        assert!(result.found);
+        assert_eq!(result.plan, vec!["process_next", "process_next"]);
+    }
+
+    #[test]
+    fn assign_at_leaves_the_variable_alone_when_the_path_is_missing() {
+        let s = state();
+        let first = Action::assign_at(av!("queue"), vec![PathSegment::Index(0)], "p9".wrap());
+        let past_end = Action::assign_at(av!("queue"), vec![PathSegment::Index(5)], "p9".wrap());
+        assert_eq!(first.to_string(), "queue[0] <= p9");
+        let before = s.get_value("queue", TARGET);
+        assert_eq!(past_end.assign(&s, TARGET).get_value("queue", TARGET), before);
+        assert_ne!(first.assign(&s, TARGET).get_value("queue", TARGET), before);
+    }
+}
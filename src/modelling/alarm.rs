@@ -0,0 +1,212 @@
+//! [`Alarm`]s: conditions an operator has to be told about.
+//!
+//! An alarm is raised while its predicate holds and reported with a message
+//! built from the state. The alarm runner keeps the currently active ones in
+//! `{sp_id}_active_alarms` and every raise, acknowledgement and clear in
+//! `{sp_id}_alarm_history`; the operator acknowledges one with the dashboard
+//! command `ack:<name>`. A [`AlarmClearing::Acknowledge`] alarm stays active
+//! until it has been both acknowledged and its condition has gone.
+//!
+//! ```
+//! use micro_sp::*;
+//!
+//! let state = State::from_vec(&vec![(fv!("gripper_force"), 41.5.to_spvalue())]);
+//! let alarm = Alarm::new(
+//!     "gripper_overload",
+//!     pred_parser::pred("var:gripper_force > 40.0", &state).unwrap(),
+//!     AlarmSeverity::High,
+//!     "Gripper force {var:gripper_force} N is over the limit",
+//! )
+//! .requiring_acknowledgement()
+//! .blocking_goals();
+//!
+//! assert!(alarm.is_raised(&state, "docs"));
+//! assert_eq!(alarm.render_message(&state), "Gripper force 41.5 N is over the limit");
+//! ```
+
+use std::{fmt, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// How urgent an [`Alarm`] is.
+#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AlarmSeverity {
+    /// Worth knowing about; `"low"`.
+    Low,
+    /// Needs attention soon; `"medium"`.
+    Medium,
+    /// Needs attention now; `"high"`.
+    High,
+}
+
+impl AlarmSeverity {
+    /// Parses `"low"`, `"medium"` or `"high"`; anything else is `High`, so a
+    /// mistyped severity errs on the loud side.
+    pub fn parse(x: &str) -> AlarmSeverity {
+        match x {
+            "low" => AlarmSeverity::Low,
+            "medium" => AlarmSeverity::Medium,
+            _ => AlarmSeverity::High,
+        }
+    }
+}
+
+impl fmt::Display for AlarmSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlarmSeverity::Low => write!(f, "low"),
+            AlarmSeverity::Medium => write!(f, "medium"),
+            AlarmSeverity::High => write!(f, "high"),
+        }
+    }
+}
+
+/// When an active [`Alarm`] goes away.
+#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq, Serialize, Deserialize)]
+pub enum AlarmClearing {
+    /// As soon as its predicate no longer holds.
+    Auto,
+    /// Once its predicate no longer holds and an operator has acknowledged it.
+    Acknowledge,
+}
+
+/// One alarm of a [`Model`]; see the [module documentation](self).
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
+pub struct Alarm {
+    /// The name it is reported and acknowledged by.
+    pub name: String,
+    /// The alarm is raised while this holds.
+    pub predicate: Predicate,
+    /// How urgent it is.
+    pub severity: AlarmSeverity,
+    /// The message, with every `{var:name}` replaced by that variable's
+    /// current value when the alarm is raised.
+    pub message: String,
+    /// When an active alarm goes away.
+    pub clearing: AlarmClearing,
+    /// Whether raising it turns `{sp_id}_step_mode` on, which holds every
+    /// planned and SOP operation before it starts, from `initial` or from
+    /// `disabled`, until the operator confirms it or turns step mode off
+    /// again. Automatic operations are not held.
+    pub pauses: bool,
+    /// Whether `goal_runner` leaves new goals in `{sp_id}_incoming_goals`
+    /// while it is active.
+    pub blocks_goals: bool,
+}
+
+impl Alarm {
+    /// An alarm that clears by itself and has no effect beyond being reported.
+    pub fn new(name: &str, predicate: Predicate, severity: AlarmSeverity, message: &str) -> Alarm {
+        Alarm {
+            name: name.to_string(),
+            predicate,
+            severity,
+            message: message.to_string(),
+            clearing: AlarmClearing::Auto,
+            pauses: false,
+            blocks_goals: false,
+        }
+    }
+
+    /// The same alarm, staying active until an operator acknowledges it.
+    pub fn requiring_acknowledgement(mut self) -> Alarm {
+        self.clearing = AlarmClearing::Acknowledge;
+        self
+    }
+
+    /// The same alarm, pausing the runtime when raised.
+    pub fn pausing(mut self) -> Alarm {
+        self.pauses = true;
+        self
+    }
+
+    /// The same alarm, keeping new goals out while it is active.
+    pub fn blocking_goals(mut self) -> Alarm {
+        self.blocks_goals = true;
+        self
+    }
+
+    /// Whether the alarm's predicate holds in `state`.
+    pub fn is_raised(&self, state: &State, log_target: &str) -> bool {
+        self.predicate.eval(state, log_target)
+    }
+
+    /// [`Alarm::message`] with its `{var:name}` placeholders filled in from
+    /// `state`. A placeholder naming a variable not in the state is left as
+    /// it is.
+    pub fn render_message(&self, state: &State) -> String {
+        let mut rendered = String::new();
+        let mut copied = 0;
+        for (placeholder, name) in placeholders(&self.message) {
+            if let Some(assignment) = state.state.get(name) {
+                rendered.push_str(&self.message[copied..placeholder.start]);
+                rendered.push_str(&assignment.val.to_string());
+                copied = placeholder.end;
+            }
+        }
+        rendered.push_str(&self.message[copied..]);
+        rendered
+    }
+
+    /// The variables the alarm reads: those of its predicate and its message.
+    pub fn variable_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .predicate
+            .get_predicate_vars()
+            .into_iter()
+            .map(|var| var.name)
+            .collect();
+        keys.extend(placeholders(&self.message).map(|(_, name)| name.to_string()));
+        keys
+    }
+}
+
+/// Every `{var:name}` in `message`, with where it is.
+fn placeholders(message: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    let mut from = 0;
+    std::iter::from_fn(move || {
+        let start = from + message[from..].find("{var:")?;
+        let end = start + message[start..].find('}')? + 1;
+        from = end;
+        Some((start..end, &message[start + "{var:".len()..end - 1]))
+    })
+}
+
+#[cfg(test)]
+mod alarm_tests {
+    use crate::*;
+
+    fn alarm(message: &str) -> Alarm {
+        Alarm::new("door_open", Predicate::TRUE, AlarmSeverity::Medium, message)
+    }
+
+    #[test]
+    fn messages_are_filled_in_from_the_state() {
+        let state = State::from_vec(&vec![
+            (v!("door"), "open".to_spvalue()),
+            (iv!("cell"), 3.to_spvalue()),
+        ]);
+        assert_eq!(
+            alarm("Door {var:door} in cell {var:cell}").render_message(&state),
+            "Door open in cell 3"
+        );
+        assert_eq!(
+            alarm("{var:missing} stays, {var:door").render_message(&state),
+            "{var:missing} stays, {var:door"
+        );
+        assert_eq!(
+            alarm("Door {var:door} in cell {var:cell}").variable_keys(),
+            vec!["door", "cell"]
+        );
+    }
+
+    #[test]
+    fn severities_round_trip_and_default_loud() {
+        for severity in [AlarmSeverity::Low, AlarmSeverity::Medium, AlarmSeverity::High] {
+            assert_eq!(AlarmSeverity::parse(&severity.to_string()), severity);
+        }
+        assert_eq!(AlarmSeverity::parse("hgih"), AlarmSeverity::High);
+    }
+}
@@ -0,0 +1,574 @@
+//! [`Model::lint`]: static checks for mistakes that only show up at runtime.
+//!
+//! Everything here works on the model alone - no state, no Redis - so it can
+//! run in a unit test on every model change:
+//!
+//! ```
+//! use micro_sp::*;
+//!
+//! let model = Model::new("demo", vec![], vec![], vec![], vec![], vec![]);
+//! let findings = model.lint();
+//! assert!(findings.is_empty(), "{}", findings.iter().map(|f| f.to_string()).collect::<Vec<_>>().join("\n"));
+//! ```
+//!
+//! The checks only see what the model itself reads and writes. Variables that
+//! drivers, emulators or operators write from outside show up as read but never
+//! written, and commands that only a driver reads as written but never read;
+//! filter those by [`LintKind`] and subject if they are expected.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::*;
+
+/// What a [`LintFinding`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LintKind {
+    /// A precondition guard requires a value no action ever writes to a
+    /// variable the model does write, so only the initial state can satisfy it.
+    UnreachablePrecondition,
+    /// A variable is read by a guard or an action but no action writes it.
+    ReadNeverWritten,
+    /// A variable is written by an action but nothing in the model reads it.
+    WrittenNeverRead,
+    /// Two operations end up with the same name once `op_` is prefixed.
+    DuplicateOperationName,
+    /// A SOP runs an operation that is not one of the model's operations.
+    SopOperationMissing,
+    /// Two automatic transitions can enable each other back and forth forever.
+    AutoTransitionPingPong,
+    /// A guard requires, or an action assigns, a value outside the variable's
+    /// declared domain.
+    OutOfDomain,
+    /// A variable is mentioned with declarations that disagree, such as two
+    /// different units or owners.
+    ConflictingDeclaration,
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintKind::UnreachablePrecondition => write!(f, "unreachable_precondition"),
+            LintKind::ReadNeverWritten => write!(f, "read_never_written"),
+            LintKind::WrittenNeverRead => write!(f, "written_never_read"),
+            LintKind::DuplicateOperationName => write!(f, "duplicate_operation_name"),
+            LintKind::SopOperationMissing => write!(f, "sop_operation_missing"),
+            LintKind::AutoTransitionPingPong => write!(f, "auto_transition_ping_pong"),
+            LintKind::OutOfDomain => write!(f, "out_of_domain"),
+            LintKind::ConflictingDeclaration => write!(f, "conflicting_declaration"),
+        }
+    }
+}
+
+/// One problem [`Model::lint`] found.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LintFinding {
+    /// What kind of problem it is.
+    pub kind: LintKind,
+    /// The operation, transition, SOP or variable it is about.
+    pub subject: String,
+    /// A sentence saying what is wrong.
+    pub message: String,
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} '{}': {}", self.kind, self.subject, self.message)
+    }
+}
+
+/// The literal values actions assign to one variable. `None` once some action
+/// writes it in a way that could produce anything: from another variable, or
+/// by incrementing or decrementing.
+type WrittenValues = Option<BTreeSet<SPValue>>;
+
+impl Model {
+    /// Run every check and return the findings, sorted by kind and subject.
+    ///
+    /// See the [module documentation](crate::modelling::lint) for what the
+    /// checks can and cannot see.
+    pub fn lint(&self) -> Vec<LintFinding> {
+        let transitions = self.all_transitions();
+        let written = written_values(&transitions);
+        let read = read_variables(&transitions);
+
+        let mut findings = vec![];
+        findings.extend(self.unreachable_preconditions(&written));
+        findings.extend(read_never_written(&read, &written));
+        findings.extend(written_never_read(&read, &written));
+        findings.extend(self.duplicate_operation_names());
+        findings.extend(self.sop_operations_missing());
+        findings.extend(self.auto_transition_ping_pong());
+        findings.extend(out_of_domain(&transitions));
+        findings.extend(self.conflicting_declarations());
+        findings.sort();
+        findings
+    }
+
+    pub(crate) fn all_operations(&self) -> Vec<Operation> {
+        let mut operations: Vec<Operation> = self
+            .operations
+            .iter()
+            .chain(self.auto_operations.iter())
+            .chain(self.mutexed_auto_operations.iter())
+            .cloned()
+            .collect();
+        for sop in &self.sops {
+            operations.extend(get_all_operations_from_sop(&sop.sop));
+        }
+        operations
+    }
+
+    pub(crate) fn all_transitions(&self) -> Vec<Transition> {
+        let mut transitions = self.auto_transitions.clone();
+        let operations = self.all_operations();
+        let compensations = operations
+            .iter()
+            .filter_map(|operation| operation.compensation.as_deref().cloned());
+        let operations: Vec<Operation> = compensations.chain(operations.iter().cloned()).collect();
+        for operation in operations {
+            transitions.extend(operation.preconditions);
+            transitions.extend(operation.postconditions);
+            transitions.extend(operation.failure_transitions);
+            transitions.extend(operation.timeout_transitions);
+            transitions.extend(operation.bypass_transitions);
+            transitions.extend(operation.cancel_transitions);
+            if let Some(policy) = operation.retry_policy {
+                transitions.extend(policy.recovery_transitions);
+            }
+        }
+        transitions
+    }
+
+    fn unreachable_preconditions(
+        &self,
+        written: &BTreeMap<String, WrittenValues>,
+    ) -> Vec<LintFinding> {
+        let mut findings = vec![];
+        for operation in self.all_operations() {
+            for precondition in &operation.preconditions {
+                for (variable, value) in required_values(&precondition.guard) {
+                    // Only variables the model writes: anything else is an
+                    // input from outside, which the checks cannot reason about.
+                    let Some(Some(values)) = written.get(&variable.name) else {
+                        continue;
+                    };
+                    if !values.contains(&value) {
+                        findings.push(LintFinding {
+                            kind: LintKind::UnreachablePrecondition,
+                            subject: format!("{}.{}", operation.name, precondition.name),
+                            message: format!(
+                                "requires '{}' = {}, which no action writes; only the initial state can satisfy it.",
+                                variable.name, value
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        findings
+    }
+
+    fn duplicate_operation_names(&self) -> Vec<LintFinding> {
+        let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+        for operation in self
+            .operations
+            .iter()
+            .chain(self.auto_operations.iter())
+            .chain(self.mutexed_auto_operations.iter())
+        {
+            *seen.entry(operation.name.clone()).or_default() += 1;
+        }
+        seen.into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(name, count)| LintFinding {
+                kind: LintKind::DuplicateOperationName,
+                subject: name,
+                message: format!("{} operations share this name.", count),
+            })
+            .collect()
+    }
+
+    fn sop_operations_missing(&self) -> Vec<LintFinding> {
+        let known: Vec<&String> = self
+            .operations
+            .iter()
+            .chain(self.auto_operations.iter())
+            .chain(self.mutexed_auto_operations.iter())
+            .map(|operation| &operation.name)
+            .collect();
+        let mut findings = vec![];
+        for sop in &self.sops {
+            for operation in get_all_operations_from_sop(&sop.sop) {
+                if !known
+                    .iter()
+                    .any(|name| operation_answers_to(name, &operation.name))
+                {
+                    findings.push(LintFinding {
+                        kind: LintKind::SopOperationMissing,
+                        subject: format!("{}.{}", sop.id, operation.name),
+                        message: "is not one of the model's operations.".to_string(),
+                    });
+                }
+            }
+        }
+        findings
+    }
+
+    fn conflicting_declarations(&self) -> Vec<LintFinding> {
+        self.variable_declarations()
+            .into_iter()
+            .filter(|(_, conflicts)| !conflicts.is_empty())
+            .map(|(variable, conflicts)| LintFinding {
+                kind: LintKind::ConflictingDeclaration,
+                subject: variable.name,
+                message: format!(
+                    "is mentioned with different {}; the first mention's is used.",
+                    conflicts.join(", ")
+                ),
+            })
+            .collect()
+    }
+
+    fn auto_transition_ping_pong(&self) -> Vec<LintFinding> {
+        let mut findings = vec![];
+        for (i, first) in self.auto_transitions.iter().enumerate() {
+            for second in self.auto_transitions.iter().skip(i + 1) {
+                if let Some(variable) = enables_back_and_forth(first, second) {
+                    findings.push(LintFinding {
+                        kind: LintKind::AutoTransitionPingPong,
+                        subject: format!("{}/{}", first.name, second.name),
+                        message: format!(
+                            "each sets '{}' to the value the other one waits for.",
+                            variable
+                        ),
+                    });
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// Every action of every transition, planning and runner alike.
+fn all_actions(transition: &Transition) -> impl Iterator<Item = &Action> {
+    transition.actions.iter().chain(transition.runner_actions.iter())
+}
+
+fn written_values(transitions: &[Transition]) -> BTreeMap<String, WrittenValues> {
+    let mut written: BTreeMap<String, WrittenValues> = BTreeMap::new();
+    for action in transitions.iter().flat_map(all_actions) {
+        let entry = written
+            .entry(action.var.name.clone())
+            .or_insert_with(|| Some(BTreeSet::new()));
+        match (&action.action_type, &action.var_or_val, entry.as_mut()) {
+            (ActionType::Assign, SPWrapped::SPValue(value), Some(values)) => {
+                values.insert(value.clone());
+            }
+            (_, _, Some(_)) => *entry = None,
+            (_, _, None) => (),
+        }
+    }
+    written
+}
+
+fn read_variables(transitions: &[Transition]) -> BTreeSet<String> {
+    let mut read = BTreeSet::new();
+    for transition in transitions {
+        read.extend(transition.guard.get_predicate_var_keys());
+        read.extend(transition.runner_guard.get_predicate_var_keys());
+        for action in all_actions(transition) {
+            read.extend(action.var_or_val.get_variables().into_iter().map(|v| v.name));
+            if action.action_type != ActionType::Assign {
+                read.insert(action.var.name.clone());
+            }
+        }
+    }
+    read
+}
+
+fn read_never_written(
+    read: &BTreeSet<String>,
+    written: &BTreeMap<String, WrittenValues>,
+) -> Vec<LintFinding> {
+    read.iter()
+        .filter(|name| !written.contains_key(*name))
+        .map(|name| LintFinding {
+            kind: LintKind::ReadNeverWritten,
+            subject: name.clone(),
+            message: "is read but no action writes it.".to_string(),
+        })
+        .collect()
+}
+
+fn written_never_read(
+    read: &BTreeSet<String>,
+    written: &BTreeMap<String, WrittenValues>,
+) -> Vec<LintFinding> {
+    written
+        .keys()
+        .filter(|name| !read.contains(*name))
+        .map(|name| LintFinding {
+            kind: LintKind::WrittenNeverRead,
+            subject: name.clone(),
+            message: "is written but nothing in the model reads it.".to_string(),
+        })
+        .collect()
+}
+
+/// Guards that can never hold because they require a value outside a
+/// variable's domain, and literal assignments of such values.
+fn out_of_domain(transitions: &[Transition]) -> Vec<LintFinding> {
+    let no_state = State::new();
+    let mut findings = vec![];
+    for transition in transitions {
+        let mut violations = transition.guard.domain_violations(&no_state);
+        violations.extend(transition.runner_guard.domain_violations(&no_state));
+        for (variable, value) in violations {
+            findings.push(LintFinding {
+                kind: LintKind::OutOfDomain,
+                subject: transition.name.clone(),
+                message: format!(
+                    "requires '{}' = {}, outside its domain {}; the guard can never hold.",
+                    variable.name,
+                    value,
+                    variable.domain.map_or(String::new(), |d| d.to_string())
+                ),
+            });
+        }
+        for action in all_actions(transition) {
+            let (ActionType::Assign, SPWrapped::SPValue(value), Some(domain)) =
+                (&action.action_type, &action.var_or_val, &action.var.domain)
+            else {
+                continue;
+            };
+            if !domain.contains(value) {
+                findings.push(LintFinding {
+                    kind: LintKind::OutOfDomain,
+                    subject: transition.name.clone(),
+                    message: format!(
+                        "assigns '{}' = {}, outside its domain {}.",
+                        action.var.name, value, domain
+                    ),
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// The `variable == value` comparisons `predicate` needs to hold: those at the
+/// top level or under nested `AND`s. Anything under an `OR` or a `NOT` is an
+/// alternative rather than a requirement, and is left out.
+fn required_values(predicate: &Predicate) -> Vec<(SPVariable, SPValue)> {
+    match predicate {
+        Predicate::AND(children) => children.iter().flat_map(required_values).collect(),
+        Predicate::EQ(SPWrapped::SPVariable(variable), SPWrapped::SPValue(value))
+        | Predicate::EQ(SPWrapped::SPValue(value), SPWrapped::SPVariable(variable)) => {
+            vec![(variable.clone(), value.clone())]
+        }
+        _ => vec![],
+    }
+}
+
+/// A variable that `first` sets to what `second` requires, and `second` sets
+/// to a different value that `first` requires.
+fn enables_back_and_forth(first: &Transition, second: &Transition) -> Option<String> {
+    let requires = |transition: &Transition| {
+        let mut required = required_values(&transition.guard);
+        required.extend(required_values(&transition.runner_guard));
+        required
+    };
+    let assigns = |transition: &Transition, variable: &SPVariable, value: &SPValue| {
+        all_actions(transition).any(|action| {
+            action.action_type == ActionType::Assign
+                && action.var.name == variable.name
+                && action.var_or_val == SPWrapped::SPValue(value.clone())
+        })
+    };
+    let (first_requires, second_requires) = (requires(first), requires(second));
+    for (variable, first_value) in &first_requires {
+        for (other, second_value) in &second_requires {
+            if variable.name == other.name
+                && first_value != second_value
+                && assigns(first, variable, second_value)
+                && assigns(second, variable, first_value)
+            {
+                return Some(variable.name.clone());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod lint_tests {
+    use crate::*;
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (SPVariable::new("pos", SPValueType::String), "a".to_spvalue()),
+            (SPVariable::new("light", SPValueType::Bool), false.to_spvalue()),
+            (SPVariable::new("sensor", SPValueType::Bool), false.to_spvalue()),
+            (SPVariable::new("log", SPValueType::String), "".to_spvalue()),
+            (SPVariable::new("counter", SPValueType::Int64), 0.to_spvalue()),
+        ])
+    }
+
+    fn transition(name: &str, guard: &str, actions: Vec<&str>) -> Transition {
+        Transition::parse(name, guard, "true", actions, Vec::<&str>::new(), &state())
+    }
+
+    fn operation(name: &str, guard: &str, actions: Vec<&str>) -> Operation {
+        Operation {
+            name: name.to_string(),
+            preconditions: vec![transition(&format!("start_{name}"), guard, vec![])],
+            postconditions: vec![transition(&format!("complete_{name}"), "true", actions)],
+            ..Default::default()
+        }
+    }
+
+    fn kinds(model: &Model, kind: LintKind) -> Vec<String> {
+        model
+            .lint()
+            .into_iter()
+            .filter(|finding| finding.kind == kind)
+            .map(|finding| finding.subject)
+            .collect()
+    }
+
+    #[test]
+    fn a_clean_model_has_no_findings() {
+        let model = Model::new(
+            "m",
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![
+                operation("to_b", "var:pos == a", vec!["var:pos <- b"]),
+                operation("to_a", "var:pos == b", vec!["var:pos <- a"]),
+            ],
+        );
+        assert_eq!(model.lint(), vec![]);
+    }
+
+    #[test]
+    fn a_precondition_nobody_can_satisfy_is_reported() {
+        let model = Model::new(
+            "m",
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![
+                operation("to_b", "var:pos == a", vec!["var:pos <- b"]),
+                operation("from_c", "var:pos == c", vec!["var:pos <- a"]),
+            ],
+        );
+        assert_eq!(
+            kinds(&model, LintKind::UnreachablePrecondition),
+            vec!["op_from_c.start_from_c"]
+        );
+    }
+
+    #[test]
+    fn an_increment_can_write_anything() {
+        let model = Model::new(
+            "m",
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![
+                operation("count", "var:counter == 3", vec!["var:counter += 1"]),
+            ],
+        );
+        assert_eq!(kinds(&model, LintKind::UnreachablePrecondition), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reads_and_writes_are_matched_up() {
+        let model = Model::new(
+            "m",
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![operation("react", "var:sensor == true", vec!["var:log <- done"])],
+        );
+        assert_eq!(kinds(&model, LintKind::ReadNeverWritten), vec!["sensor"]);
+        assert_eq!(kinds(&model, LintKind::WrittenNeverRead), vec!["log"]);
+    }
+
+    #[test]
+    fn duplicate_names_and_missing_sop_operations_are_reported() {
+        let to_b = operation("to_b", "var:pos == a", vec!["var:pos <- b"]);
+        let stray = operation("to_a", "var:pos == b", vec!["var:pos <- a"]);
+        let model = Model::new(
+            "m",
+            vec![],
+            vec![to_b.clone()],
+            vec![],
+            vec![SOPStruct {
+                id: "sop".to_string(),
+                sop: SOP::Sequence(vec![
+                    SOP::Operation(Box::new(to_b.clone())),
+                    SOP::Operation(Box::new(stray)),
+                ]),
+            }],
+            vec![to_b],
+        );
+        assert_eq!(kinds(&model, LintKind::DuplicateOperationName), vec!["op_to_b"]);
+        assert_eq!(kinds(&model, LintKind::SopOperationMissing), vec!["sop.to_a"]);
+    }
+
+    #[test]
+    fn auto_transitions_that_toggle_each_other_are_reported() {
+        let on = transition("on", "var:light == false", vec!["var:light <- true"]);
+        let off = transition("off", "var:light == true", vec!["var:light <- false"]);
+        let model = Model::new("m", vec![on, off], vec![], vec![], vec![], vec![]);
+        assert_eq!(kinds(&model, LintKind::AutoTransitionPingPong), vec!["on/off"]);
+    }
+
+    #[test]
+    fn values_outside_a_domain_are_reported() {
+        let pos = v!("pos", ["a", "b"]);
+        let to_c = t_plan!("to_c", eq!(pos.wrap(), "b".wrap()), vec!(a!(pos.clone(), "c".wrap())));
+        let at_d = t_plan!("at_d", eq!(pos.wrap(), "d".wrap()), vec!(a!(pos.clone(), "a".wrap())));
+        let model = Model::new("m", vec![to_c, at_d], vec![], vec![], vec![], vec![]);
+        let findings: Vec<String> = model
+            .lint()
+            .into_iter()
+            .filter(|finding| finding.kind == LintKind::OutOfDomain)
+            .map(|finding| finding.to_string())
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                "out_of_domain 'at_d': requires 'pos' = d, outside its domain {a, b}; the guard can never hold.",
+                "out_of_domain 'to_c': assigns 'pos' = c, outside its domain {a, b}.",
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicting_declarations_are_reported() {
+        let gantry = fv!("x").with_unit("mm").owned_by("gantry");
+        let robot = fv!("x").with_unit("m").owned_by("gantry").with_kind(VariableKind::Measured);
+        let park = t_plan!("park", eq!(gantry.wrap(), 0.0.wrap()), vec!(a!(robot.clone(), 1.0.wrap())));
+        let model = Model::new("m", vec![park], vec![], vec![], vec![], vec![]);
+        let findings: Vec<String> = model
+            .lint()
+            .into_iter()
+            .filter(|finding| finding.kind == LintKind::ConflictingDeclaration)
+            .map(|finding| finding.to_string())
+            .collect();
+        assert_eq!(
+            findings,
+            vec!["conflicting_declaration 'x': is mentioned with different unit; the first mention's is used."]
+        );
+    }
+}
@@ -7,9 +7,16 @@
 //! runtime - so a model can be built and evaluated in a plain unit test.
 
 pub mod action;
+pub mod alarm;
 pub mod sops;
 pub mod operation;
+pub mod operation_result;
+pub mod operator_task;
 pub mod model;
+pub mod lint;
 pub mod parser;
 pub mod predicate;
+pub mod retry_policy;
+pub mod service_spec;
 pub mod transition;
+pub mod watchdog;
@@ -6,6 +6,7 @@
 
 use crate::*;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// A model contains behavior that defines what a system is capable of doing.
 ///
@@ -49,6 +50,19 @@ pub struct Model {
     pub sops: Vec<SOPStruct>,
     /// The operations the planner may sequence into a plan.
     pub operations: Vec<Operation>,
+    /// Every variable the model's guards and actions mention, once each and
+    /// sorted by name, with the domain and [`SPVariableMetadata`] it was
+    /// declared with. Filled in by [`Model::new`]; this is the part of an
+    /// exported model that documents its interface.
+    #[serde(default)]
+    pub variables: Vec<SPVariable>,
+    /// Supervision of the services the operations depend on; see
+    /// [`Model::with_watchdogs`].
+    #[serde(default)]
+    pub watchdogs: Vec<Watchdog>,
+    /// Conditions the operator is told about; see [`Model::with_alarms`].
+    #[serde(default)]
+    pub alarms: Vec<Alarm>,
 }
 
 impl Model {
@@ -80,7 +94,7 @@ impl Model {
         sops: Vec<SOPStruct>,
         operations: Vec<Operation>,
     ) -> Model {
-        Model {
+        let mut model = Model {
             name: name.to_string(),
             auto_transitions,
             auto_operations: auto_operations
@@ -92,12 +106,19 @@ impl Model {
                     failure_retries: o.failure_retries,
                     timeout_retries: o.timeout_retries,
                     can_be_bypassed: o.can_be_bypassed,
+                    sensing: o.sensing,
                     preconditions: o.preconditions.clone(),
                     postconditions: o.postconditions.clone(),
                     failure_transitions: o.failure_transitions.clone(),
                     timeout_transitions: o.timeout_transitions.clone(),
                     bypass_transitions: o.bypass_transitions.clone(),
                     cancel_transitions: o.cancel_transitions.clone(),
+                    compensation: prefixed_compensation(o),
+                    retry_policy: o.retry_policy.clone(),
+                    results: o.results.clone().map(|results| ResultDeclaration {
+                        operation: format!("op_{}", o.name),
+                        ..results
+                    }),
                     state: o.state.clone(),
                 })
                 .collect(),
@@ -110,12 +131,19 @@ impl Model {
                     failure_retries: o.failure_retries,
                     timeout_retries: o.timeout_retries,
                     can_be_bypassed: o.can_be_bypassed,
+                    sensing: o.sensing,
                     preconditions: o.preconditions.clone(),
                     postconditions: o.postconditions.clone(),
                     failure_transitions: o.failure_transitions.clone(),
                     timeout_transitions: o.timeout_transitions.clone(),
                     bypass_transitions: o.bypass_transitions.clone(),
                     cancel_transitions: o.cancel_transitions.clone(),
+                    compensation: prefixed_compensation(o),
+                    retry_policy: o.retry_policy.clone(),
+                    results: o.results.clone().map(|results| ResultDeclaration {
+                        operation: format!("op_{}", o.name),
+                        ..results
+                    }),
                     state: o.state.clone(),
                 })
                 .collect(),
@@ -129,16 +157,300 @@ impl Model {
                     failure_retries: o.failure_retries,
                     timeout_retries: o.timeout_retries,
                     can_be_bypassed: o.can_be_bypassed,
+                    sensing: o.sensing,
                     preconditions: o.preconditions.clone(),
                     postconditions: o.postconditions.clone(),
                     failure_transitions: o.failure_transitions.clone(),
                     timeout_transitions: o.timeout_transitions.clone(),
                     bypass_transitions: o.bypass_transitions.clone(),
                     cancel_transitions: o.cancel_transitions.clone(),
+                    compensation: prefixed_compensation(o),
+                    retry_policy: o.retry_policy.clone(),
+                    results: o.results.clone().map(|results| ResultDeclaration {
+                        operation: format!("op_{}", o.name),
+                        ..results
+                    }),
                     state: o.state.clone(),
                 })
                 .collect(),
+            variables: vec![],
+            watchdogs: vec![],
+            alarms: vec![],
+        };
+        model.variables = model.mentioned_variables();
+        model
+    }
+
+    /// The same model supervised by `watchdogs`.
+    ///
+    /// Every operation a watchdog [supervises](Watchdog::supervises) - planned,
+    /// automatic, mutexed or in a SOP - needs its `{name}_watchdog_alive` to
+    /// start and fails when it drops; see [`Watchdog`]. Each watchdog's
+    /// [`Watchdog::state_variables`] are seeded by
+    /// [`generate_operation_state_variables`].
+    pub fn with_watchdogs(mut self, watchdogs: Vec<Watchdog>) -> Model {
+        let supervise = |operation: Operation| -> Operation {
+            watchdogs.iter().fold(operation, |operation, watchdog| {
+                if watchdog.supervises(&operation) {
+                    watchdog.supervise(operation)
+                } else {
+                    operation
+                }
+            })
+        };
+        let supervise_all =
+            |operations: Vec<Operation>| -> Vec<Operation> { operations.into_iter().map(supervise).collect() };
+        self.operations = supervise_all(self.operations);
+        self.auto_operations = supervise_all(self.auto_operations);
+        self.mutexed_auto_operations = supervise_all(self.mutexed_auto_operations);
+        self.sops = self
+            .sops
+            .into_iter()
+            .map(|sop| SOPStruct { sop: supervise_sop(sop.sop, &supervise), ..sop })
+            .collect();
+        self.watchdogs.extend(watchdogs);
+        self.variables = self.mentioned_variables();
+        self
+    }
+
+    /// The same model with `alarms`, which the alarm runner evaluates every
+    /// tick; see [`Alarm`].
+    pub fn with_alarms(mut self, alarms: Vec<Alarm>) -> Model {
+        self.alarms.extend(alarms);
+        self
+    }
+
+    /// The variables in `written` that [`Model::variables`] says are owned by
+    /// someone other than `writer`, the `sp_id` of the runner writing them.
+    pub fn foreign_writes(&self, writer: &str, written: &State) -> Vec<&SPVariable> {
+        let mut foreign: Vec<&SPVariable> = written
+            .state
+            .keys()
+            .filter_map(|name| {
+                let index = self.variables.binary_search_by(|v| v.name.as_str().cmp(name));
+                index.ok().map(|index| &self.variables[index])
+            })
+            .filter(|var| var.metadata.owner.as_ref().is_some_and(|owner| owner != writer))
+            .collect();
+        foreign.sort();
+        foreign
+    }
+
+    /// Warn under `log_target` about each of [`Model::foreign_writes`]. The
+    /// runners call this before every write.
+    pub fn warn_foreign_writes(&self, writer: &str, written: &State, log_target: &str) {
+        for var in self.foreign_writes(writer, written) {
+            log::warn!(target: log_target,
+                "Writing '{}', which is owned by '{}'.",
+                var.name, var.metadata.owner.as_deref().unwrap_or_default());
+        }
+    }
+
+    /// The variables for [`Model::variables`]: each of
+    /// [`Model::variable_declarations`] without its conflicts.
+    fn mentioned_variables(&self) -> Vec<SPVariable> {
+        self.variable_declarations()
+            .into_iter()
+            .map(|(variable, _)| variable)
+            .collect()
+    }
+
+    /// Every variable the model's guards and actions mention, sorted by name.
+    /// A variable mentioned with different declarations, say once parsed from
+    /// the model's state and once built by hand, is merged field by field:
+    /// each field takes the first mention that sets it. The names of the
+    /// fields two mentions set differently come along with it; the first
+    /// mention's value is kept, and [`Model::lint`] reports the rest.
+    pub(crate) fn variable_declarations(&self) -> Vec<(SPVariable, Vec<&'static str>)> {
+        let mut mentions: Vec<SPVariable> = vec![];
+        for transition in self.all_transitions() {
+            mentions.extend(transition.guard.get_predicate_vars());
+            mentions.extend(transition.runner_guard.get_predicate_vars());
+            for action in transition.actions.iter().chain(transition.runner_actions.iter()) {
+                mentions.push(action.var.clone());
+                mentions.extend(action.var_or_val.get_variables());
+            }
+        }
+        let mut declarations: BTreeMap<String, (SPVariable, Vec<&'static str>)> = BTreeMap::new();
+        for mention in mentions {
+            match declarations.get_mut(&mention.name) {
+                Some((declared, conflicts)) => merge_declaration(declared, &mention, conflicts),
+                None => {
+                    declarations.insert(mention.name.clone(), (mention, vec![]));
+                }
+            }
         }
+        declarations.into_values().collect()
+    }
+}
+
+/// Fill the fields `declared` leaves unset from `mention`, and add the name of
+/// every field the two set differently to `conflicts`.
+fn merge_declaration(
+    declared: &mut SPVariable,
+    mention: &SPVariable,
+    conflicts: &mut Vec<&'static str>,
+) {
+    fn merge<T: Clone + PartialEq>(
+        field: &'static str,
+        declared: &mut Option<T>,
+        mention: &Option<T>,
+        conflicts: &mut Vec<&'static str>,
+    ) {
+        match (declared.as_ref(), mention) {
+            (None, _) => *declared = mention.clone(),
+            (Some(kept), Some(other)) if kept != other && !conflicts.contains(&field) => {
+                conflicts.push(field)
+            }
+            _ => (),
+        }
+    }
+    if declared.value_type != mention.value_type && !conflicts.contains(&"value_type") {
+        conflicts.push("value_type");
     }
+    merge("tolerance", &mut declared.tolerance, &mention.tolerance, conflicts);
+    merge("domain", &mut declared.domain, &mention.domain, conflicts);
+    merge("ttl_ms", &mut declared.ttl_ms, &mention.ttl_ms, conflicts);
+    let metadata = &mut declared.metadata;
+    merge("description", &mut metadata.description, &mention.metadata.description, conflicts);
+    merge("unit", &mut metadata.unit, &mention.metadata.unit, conflicts);
+    merge("owner", &mut metadata.owner, &mention.metadata.owner, conflicts);
+    merge("kind", &mut metadata.kind, &mention.metadata.kind, conflicts);
+}
+
+/// `operation`'s compensation, named with the same `op_` prefix.
+fn prefixed_compensation(operation: &Operation) -> Option<Box<Operation>> {
+    operation.compensation.as_ref().map(|compensation| {
+        Box::new(Operation {
+            name: format!("op_{}", compensation.name),
+            ..(**compensation).clone()
+        })
+    })
+}
+
+/// `sop` with `supervise` applied to every operation in it.
+fn supervise_sop(sop: SOP, supervise: &impl Fn(Operation) -> Operation) -> SOP {
+    let supervise_children =
+        |sops: Vec<SOP>| -> Vec<SOP> { sops.into_iter().map(|child| supervise_sop(child, supervise)).collect() };
+    match sop {
+        SOP::Operation(operation) => SOP::Operation(Box::new(supervise(*operation))),
+        SOP::Sequence(sops) => SOP::Sequence(supervise_children(sops)),
+        SOP::Parallel(sops) => SOP::Parallel(supervise_children(sops)),
+        SOP::Alternative(sops) => SOP::Alternative(supervise_children(sops)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (
+                fv!("gantry_x").with_unit("mm").owned_by("gantry").with_kind(VariableKind::Measured),
+                0.0.to_spvalue(),
+            ),
+            (v!("gantry_command").owned_by("cell"), "idle".to_spvalue()),
+            (bv!("done"), false.to_spvalue()),
+        ])
+    }
+
+    fn model() -> Model {
+        let state = state();
+        let move_gantry = Operation {
+            name: "move".to_string(),
+            preconditions: vec![Transition::parse(
+                "start_move",
+                "var:done == false",
+                "true",
+                vec!["var:gantry_command <- move"],
+                Vec::<&str>::new(),
+                &state,
+            )],
+            postconditions: vec![Transition::parse(
+                "complete_move",
+                "var:gantry_x > 100.0",
+                "true",
+                vec!["var:done <- true"],
+                Vec::<&str>::new(),
+                &state,
+            )],
+            ..Default::default()
+        };
+        Model::new("cell", vec![], vec![], vec![], vec![], vec![move_gantry])
+    }
+
+    #[test]
+    fn the_model_lists_its_variables_with_their_metadata() {
+        let model = model();
+        let names: Vec<&str> = model.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["done", "gantry_command", "gantry_x"]);
+        assert_eq!(model.variables[2].metadata.unit.as_deref(), Some("mm"));
+
+        let exported = serde_json::to_string(&model).unwrap();
+        assert!(exported.contains(r#""unit":"mm""#));
+        let imported: Model = serde_json::from_str(&exported).unwrap();
+        assert_eq!(imported, model);
+    }
+
+    #[test]
+    fn writes_to_variables_owned_elsewhere_are_found() {
+        let model = model();
+        let written = State::from_vec(&vec![
+            (v!("gantry_command"), "move".to_spvalue()),
+            (fv!("gantry_x"), 5.0.to_spvalue()),
+            (bv!("done"), true.to_spvalue()),
+            (v!("op_move"), "executing".to_spvalue()),
+        ]);
+        let foreign: Vec<&str> = model
+            .foreign_writes("cell", &written)
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        assert_eq!(foreign, vec!["gantry_x"]);
+        assert_eq!(model.foreign_writes("gantry", &written).len(), 1);
+    }
+
+    #[test]
+    fn compensations_are_named_and_read_like_operations() {
+        let state = state();
+        let park = Operation {
+            name: "park".to_string(),
+            preconditions: vec![Transition::parse(
+                "start_park",
+                "true",
+                "true",
+                vec!["var:gantry_command <- park"],
+                Vec::<&str>::new(),
+                &state,
+            )],
+            ..Default::default()
+        };
+        let mut move_gantry = model().operations[0].clone().compensated_by(park);
+        move_gantry.name = "move".to_string();
+        let model = Model::new("cell", vec![], vec![], vec![], vec![], vec![move_gantry]);
 
+        let compensations = compensation_operations(&model);
+        assert_eq!(compensations.len(), 1);
+        assert_eq!(compensations[0].name, "op_park");
+        assert!(model_variable_keys(&model).contains(&"gantry_command".to_string()));
+    }
+
+    #[test]
+    fn declarations_of_one_variable_are_merged_field_by_field() {
+        let described = fv!("gantry_x").described("Gantry position");
+        let measured = fv!("gantry_x").with_unit("mm").with_kind(VariableKind::Measured);
+        let tracked = t_plan!(
+            "track",
+            eq!(described.wrap(), 0.0.wrap()),
+            vec!(a!(measured.clone(), 1.0.wrap()))
+        );
+        let model = Model::new("cell", vec![tracked], vec![], vec![], vec![], vec![]);
+        assert_eq!(model.variables.len(), 1);
+        let metadata = &model.variables[0].metadata;
+        assert_eq!(metadata.description.as_deref(), Some("Gantry position"));
+        assert_eq!(metadata.unit.as_deref(), Some("mm"));
+        assert_eq!(metadata.kind, Some(VariableKind::Measured));
+        assert!(model.lint().iter().all(|f| f.kind != LintKind::ConflictingDeclaration));
+    }
 }
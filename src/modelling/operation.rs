@@ -165,6 +165,12 @@ pub struct Operation {
     /// Whether a failed or timed-out operation may be bypassed and the plan
     /// carried on regardless.
     pub can_be_bypassed: bool,
+    /// Whether the operation senses the world, so that its planning actions
+    /// may predict [`VariableKind::Measured`] variables. The planning actions
+    /// of any other operation leave measured variables alone. Set it with
+    /// [`Operation::as_sensing`].
+    #[serde(default)]
+    pub sensing: bool,
     /// Guards that start the operation; the first one that holds is taken.
     pub preconditions: Vec<Transition>,
     /// Guards that complete the operation; the first one that holds is taken.
@@ -178,8 +184,24 @@ pub struct Operation {
     /// Guards checked before timing out, with the same all-or-nothing rule as
     /// [`Operation::bypass_transitions`].
     pub timeout_transitions: Vec<Transition>,
-    /// Extra assignments to make when the operation is cancelled.
+    /// Extra assignments to make when the operation is cancelled: the first
+    /// whose guard holds is taken by [`Operation::cancel`], for any operation,
+    /// not only [operator tasks](crate::OperatorTask).
     pub cancel_transitions: Vec<Transition>,
+    /// The operation that undoes this one, run by the plan and SOP runners
+    /// for each completed step when a plan or SOP is cancelled or fails. Set
+    /// it with [`Operation::compensated_by`]; see
+    /// [`compensation`](crate::running::compensation).
+    #[serde(default)]
+    pub compensation: Option<Box<Operation>>,
+    /// How retries are taken; without one each is taken on the next tick.
+    /// Set it with [`Operation::with_retry_policy`].
+    #[serde(default)]
+    pub retry_policy: Option<RetryPolicy>,
+    /// The values the operation reports when it completes. Set them with
+    /// [`Operation::with_results`].
+    #[serde(default)]
+    pub results: Option<ResultDeclaration>,
 }
 
 impl Default for Operation {
@@ -192,12 +214,16 @@ impl Default for Operation {
             failure_retries: 0,
             timeout_retries: 0,
             can_be_bypassed: false,
+            sensing: false,
             preconditions: Vec::new(),
             postconditions: Vec::new(),
             failure_transitions: Vec::new(),
             timeout_transitions: Vec::new(),
             bypass_transitions: Vec::new(),
             cancel_transitions: Vec::new(),
+            compensation: None,
+            retry_policy: None,
+            results: None,
         }
     }
 }
@@ -306,11 +332,15 @@ impl Operation {
                 None => 0,
             },
             can_be_bypassed,
+            sensing: false,
             preconditions,
             postconditions,
             failure_transitions,
             bypass_transitions,
             cancel_transitions,
+            compensation: None,
+            retry_policy: None,
+            results: None,
         }
     }
 
@@ -331,13 +361,51 @@ impl Operation {
 
     /// Execute the planing actions of both the pre and post conditions.
     /// Inex 0 taken as to indicate that the firstly defined transition should be taken when planning.
+    /// Unless the operation is [`Operation::sensing`], actions on measured
+    /// variables are skipped: only sensing tells the planner what they are.
     pub fn take_planning(&self, state: &State, log_target: &str) -> State {
         let mut new_state = state.clone();
-        self.preconditions[0].take_planning_mut(&mut new_state, &log_target);
-        self.postconditions[0].take_planning_mut(&mut new_state, &log_target);
+        let actions = self.preconditions[0]
+            .actions
+            .iter()
+            .chain(self.postconditions[0].actions.iter());
+        for action in actions {
+            if self.sensing || !action.var.is_measured() {
+                action.assign_mut(&mut new_state, &log_target);
+            }
+        }
         new_state
     }
 
+    /// The same operation marked as sensing; see [`Operation::sensing`].
+    pub fn as_sensing(mut self) -> Operation {
+        self.sensing = true;
+        self
+    }
+
+    /// The same operation, undone by `compensation`; see
+    /// [`Operation::compensation`].
+    pub fn compensated_by(mut self, compensation: Operation) -> Operation {
+        self.compensation = Some(Box::new(compensation));
+        self
+    }
+
+    /// The same operation, retrying as `policy` says; see [`RetryPolicy`].
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Operation {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// The same operation, reporting the values of `variables` when it
+    /// completes; see [`ResultDeclaration`].
+    pub fn with_results(mut self, variables: Vec<SPVariable>) -> Operation {
+        self.results = Some(ResultDeclaration {
+            operation: self.name.clone(),
+            variables,
+        });
+        self
+    }
+
     /// Whether the operation can start right now.
     ///
     /// True when it is `initial` or `disabled` and some precondition's full
@@ -452,28 +520,42 @@ impl Operation {
         false
     }
 
-    /// Check if we can stop the execution and cancel the operations
+    /// Check if the dashboard asks to cancel this operation: `stop` for every
+    /// operation, or `cancel:<name>` for this one (see [`DashboardCommand`]),
+    /// while it is [cancellable](Operation::is_cancellable).
     pub fn can_be_cancelled(&self, sp_id: &str, state: &State, log_target: &str) -> bool {
-        if let Some(value) = state.get_value(&self.name, &log_target) {
-            if value_is(&value, OperationState::Initial)
-                || value_is(&value, OperationState::Executing)
-                || value_is(&value, OperationState::Disabled)
-                || value_is(&value, OperationState::Failed)
-                || value_is(&value, OperationState::Timedout)
-            {
-                if let Some(dashboard_command) =
-                    state.get_value(&format!("{}_dashboard_command", sp_id), &log_target)
-                {
-                    if let SPValue::String(StringOrUnknown::String(db)) = dashboard_command {
-                        match db.as_str() {
-                            "stop" => return true,
-                            _ => (),
-                        }
-                    }
-                }
+        if !self.is_cancellable(state, log_target) {
+            return false;
+        }
+        match read_dashboard_command(sp_id, state, log_target) {
+            DashboardCommand::Stop => true,
+            DashboardCommand::Cancel(requested) => operation_answers_to(&self.name, &requested),
+            _ => false,
+        }
+    }
+
+    /// Whether the operation is in a state cancelling means something in:
+    /// planned, running, or stuck somewhere it can still be recovered from.
+    pub fn is_cancellable(&self, state: &State, log_target: &str) -> bool {
+        match state.get_value(&self.name, log_target) {
+            Some(value) => {
+                value_is(&value, OperationState::Initial)
+                    || value_is(&value, OperationState::Executing)
+                    || value_is(&value, OperationState::Disabled)
+                    || value_is(&value, OperationState::Failed)
+                    || value_is(&value, OperationState::Timedout)
             }
+            None => false,
         }
-        false
+    }
+
+    /// Whether the operation has yet to reach an end state: cancellable, or
+    /// `fatal` but not yet terminated.
+    pub fn is_unfinished(&self, state: &State, log_target: &str) -> bool {
+        self.is_cancellable(state, log_target)
+            || state
+                .get_value(&self.name, log_target)
+                .is_some_and(|value| value_is(&value, OperationState::Fatal))
     }
 
     /// Start executing the operation. Check for eval_running() first.
@@ -488,9 +570,11 @@ impl Operation {
         }
     }
 
-    /// Move the operation to `cancelled`.
+    /// Move the operation to `cancelled`, taking the first of its
+    /// [`Operation::cancel_transitions`] whose guard holds.
     ///
-    /// Unguarded: it cancels from any state, including terminal ones. Check
+    /// Unguarded: it cancels from any state, including terminal ones, and
+    /// whether or not a cancel transition can be taken. Check
     /// [`Operation::can_be_cancelled`] first.
     pub fn cancel(&self, state: &State, log_target: &str) -> State {
         let assignment = state.get_assignment(&self.name, &log_target);
@@ -498,7 +582,30 @@ impl Operation {
             assignment.var,
             OperationState::Cancelled.to_spvalue().wrap(),
         );
-        action.assign(&state, &log_target)
+        let mut new_state = action.assign(&state, &log_target);
+        if let Some(cancel_transition) = self
+            .cancel_transitions
+            .iter()
+            .find(|transition| transition.eval(state, log_target))
+        {
+            cancel_transition.take_mut(&mut new_state, log_target);
+        }
+        new_state
+    }
+
+    /// Move an unfinished operation to `bypassed` on the operator's say-so.
+    ///
+    /// Unlike [`Operation::bypass`] it ignores `can_be_bypassed` and takes no
+    /// bypass transition: the operator has decided the work is done or not
+    /// needed. From a finished state the state is returned unchanged.
+    pub fn skip(&self, state: &State, log_target: &str) -> State {
+        if self.is_unfinished(state, log_target) {
+            let assignment = state.get_assignment(&self.name, log_target);
+            let action = Action::new(assignment.var, OperationState::Bypassed.to_spvalue().wrap());
+            action.assign(state, log_target)
+        } else {
+            state.clone()
+        }
     }
 
     /// Start executing the operation. Check for eval_running() first.
@@ -753,6 +860,8 @@ impl Operation {
                     .iter()
                     .flat_map(|t| t.get_all_var_keys()),
             )
+            .chain(self.retry_policy.iter().flat_map(|policy| policy.get_all_var_keys()))
+            .chain(self.results.iter().flat_map(|results| results.get_all_var_keys()))
             .collect();
 
         all_keys.sort_unstable();
@@ -901,6 +1010,27 @@ mod can_be_cancelled_tests {
         }
     }
 
+    #[test]
+    fn cancel_by_name_reaches_only_that_operation() {
+        for command in ["cancel:op_x", "cancel:x"] {
+            assert!(operation().can_be_cancelled(SP_ID, &state_with("executing", command), TARGET));
+        }
+        assert!(!operation().can_be_cancelled(SP_ID, &state_with("executing", "cancel:y"), TARGET));
+        assert!(!operation().can_be_cancelled(SP_ID, &state_with("completed", "cancel:x"), TARGET));
+    }
+
+    #[test]
+    fn skip_bypasses_unfinished_operations_only() {
+        for operation_state in ["initial", "executing", "disabled", "failed", "timedout", "fatal"] {
+            let state = operation().skip(&state_with(operation_state, "none"), TARGET);
+            assert_eq!(state.get_string_or_default_to_unknown("op_x", TARGET), "bypassed");
+        }
+        for operation_state in ["completed", "cancelled", "terminated_completed"] {
+            let before = state_with(operation_state, "none");
+            assert_eq!(operation().skip(&before, TARGET), before);
+        }
+    }
+
     #[test]
     fn without_a_stop_command_nothing_is_cancellable() {
         for operation_state in ["initial", "executing", "disabled", "failed", "timedout"] {
@@ -1145,6 +1275,37 @@ mod guard_tests {
         }
     }
 
+    /// A cancelled operation takes the first of its cancel transitions whose
+    /// guard holds, whether or not it is an operator task, and none when no
+    /// guard does.
+    #[test]
+    fn cancel_takes_the_first_cancel_transition_that_holds() {
+        let world = world();
+        let on_cancel = |name: &str, guard: &str, action: &str| {
+            Transition::parse(name, guard, "true", vec![action], Vec::<&str>::new(), &world)
+        };
+        let mut operation = operation(&world);
+        operation.cancel_transitions = vec![
+            on_cancel("cancel_late", "var:late == true", "var:broken <- true"),
+            on_cancel("cancel", "true", "var:done <- true"),
+        ];
+
+        let cancelled = operation.cancel(&in_state(&world, "executing"), TARGET);
+        assert_eq!(op_state(&cancelled), "cancelled");
+        assert_eq!(cancelled.get_value("broken", TARGET), Some(false.to_spvalue()));
+        assert_eq!(cancelled.get_value("done", TARGET), Some(true.to_spvalue()));
+
+        let late = in_state(&world.update("late", true.to_spvalue()), "executing");
+        let cancelled = operation.cancel(&late, TARGET);
+        assert_eq!(cancelled.get_value("broken", TARGET), Some(true.to_spvalue()));
+        assert_eq!(cancelled.get_value("done", TARGET), Some(false.to_spvalue()));
+
+        operation.cancel_transitions.clear();
+        let cancelled = operation.cancel(&in_state(&world, "executing"), TARGET);
+        assert_eq!(op_state(&cancelled), "cancelled");
+        assert_eq!(cancelled.get_value("done", TARGET), Some(false.to_spvalue()));
+    }
+
     /// `initialize` is the other unguarded one - it is the recovery path for an
     /// operation in an unrecognised state, so it has to work from anywhere.
     #[test]
@@ -1253,3 +1414,54 @@ mod guard_tests {
         assert_eq!(op_state(&plain.bypass(&timedout, TARGET)), "bypassed");
     }
 }
+
+#[cfg(test)]
+mod sensing_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (v!("op_look"), "initial".to_spvalue()),
+            (fv!("part_x").with_kind(VariableKind::Measured), 0.0.to_spvalue()),
+            (bv!("looked"), false.to_spvalue()),
+        ])
+    }
+
+    fn look(state: &State) -> Operation {
+        Operation {
+            name: "op_look".to_string(),
+            preconditions: vec![Transition::parse(
+                "start_look",
+                "var:looked == false",
+                "true",
+                vec!["var:looked <- true"],
+                Vec::<&str>::new(),
+                state,
+            )],
+            postconditions: vec![Transition::parse(
+                "complete_look",
+                "true",
+                "true",
+                vec!["var:part_x <- 0.4"],
+                Vec::<&str>::new(),
+                state,
+            )],
+            ..Default::default()
+        }
+    }
+
+    /// Only a sensing operation may tell the planner what a measured
+    /// variable will be; its other planning actions apply either way.
+    #[test]
+    fn only_sensing_operations_predict_measured_variables() {
+        let state = state();
+        let plain = look(&state).take_planning(&state, TARGET);
+        assert_eq!(plain.get_value("part_x", TARGET), Some(0.0.to_spvalue()));
+        assert_eq!(plain.get_value("looked", TARGET), Some(true.to_spvalue()));
+
+        let sensed = look(&state).as_sensing().take_planning(&state, TARGET);
+        assert_eq!(sensed.get_value("part_x", TARGET), Some(0.4.to_spvalue()));
+    }
+}
@@ -0,0 +1,251 @@
+//! Operation results: typed values an operation reports when it completes.
+//!
+//! An operation declares its result variables with
+//! [`Operation::with_results`] - say the weight a scale measured or the ID of
+//! the part a camera found. When it completes, the runner driving it captures
+//! their values into two variables named after the operation:
+//!
+//! * `{op}_result` - a map from each result variable's name to its value at
+//!   completion. Later guards read it through a path, as in
+//!   `var:op_weigh_result.weight > 2.0`.
+//! * `{op}_result_history` - the latest [`RESULT_HISTORY_LENGTH`] results,
+//!   oldest first, each an [`OperationResult`] with when the operation
+//!   completed and how long it executed. Clients read it with
+//!   [`StateManager::get_operation_results`].
+//!
+//! `{op}` is the operation's name in the model, so results of plan steps and
+//! SOP operations, which run under uniquified names, still land in one place.
+//! [`generate_operation_state_variables`] seeds both, from
+//! [`ResultDeclaration::state_variables`], for every operation with results.
+//!
+//! ```
+//! use micro_sp::*;
+//! use std::time::SystemTime;
+//!
+//! let weigh = Operation {
+//!     name: "op_weigh".to_string(),
+//!     ..Default::default()
+//! }
+//! .with_results(vec![fv!("weight")]);
+//! let results = weigh.results.as_ref().unwrap();
+//!
+//! let mut state = results.state_variables();
+//! state.extend_mut(State::from_vec(&vec![(fv!("weight"), 2.5.to_spvalue())]), true);
+//! let state = results.record(&state, SystemTime::now(), 1200, "docs");
+//!
+//! let latest = &OperationResult::history_from_spvalue(
+//!     &state.get_value("op_weigh_result_history", "docs").unwrap(),
+//! )[0];
+//! assert_eq!(latest.values, vec![("weight".to_string(), 2.5.to_spvalue())]);
+//! assert_eq!(latest.duration_ms, 1200);
+//! ```
+
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// How many results `{op}_result_history` keeps.
+pub const RESULT_HISTORY_LENGTH: usize = 50;
+
+/// The result variables of one operation; see the [module documentation](self).
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
+pub struct ResultDeclaration {
+    /// The operation's name in the model, which names the result variables.
+    pub operation: String,
+    /// The variables whose values make up the result.
+    pub variables: Vec<SPVariable>,
+}
+
+/// One completion of an operation, as kept in `{op}_result_history`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OperationResult {
+    /// Each result variable's name and value at completion. A variable that
+    /// was not in the state is left out.
+    pub values: Vec<(String, SPValue)>,
+    /// When the operation completed.
+    pub completed_at: SystemTime,
+    /// How long it had been executing, in milliseconds.
+    pub duration_ms: i64,
+}
+
+impl ResultDeclaration {
+    /// `{operation}_result`.
+    pub fn result(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_result", self.operation), SPValueType::Map)
+    }
+
+    /// `{operation}_result_history`.
+    pub fn history(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_result_history", self.operation), SPValueType::Array)
+    }
+
+    /// The initial state: no result yet and an empty history.
+    pub fn state_variables(&self) -> State {
+        State::from_vec(&vec![
+            (self.result(), SPValue::Map(MapOrUnknown::UNKNOWN)),
+            (self.history(), Vec::<SPValue>::new().to_spvalue()),
+        ])
+    }
+
+    /// `state` with the current values of the result variables published as
+    /// the result of a completion at `completed_at` after `duration_ms`.
+    pub fn record(
+        &self,
+        state: &State,
+        completed_at: SystemTime,
+        duration_ms: i64,
+        log_target: &str,
+    ) -> State {
+        let result = OperationResult {
+            values: self
+                .variables
+                .iter()
+                .filter_map(|var| {
+                    state.state.get(&var.name).map(|a| (var.name.clone(), a.val.clone()))
+                })
+                .collect(),
+            completed_at,
+            duration_ms,
+        };
+        let mut history = match state.contains(&self.history().name) {
+            true => state.get_array_or_default_to_empty(&self.history().name, log_target),
+            false => vec![],
+        };
+        history.push(result.to_spvalue());
+        let overflow = history.len().saturating_sub(RESULT_HISTORY_LENGTH);
+        history.drain(..overflow);
+
+        let mut new_state = state.clone();
+        new_state.extend_mut(
+            State::from_vec(&vec![
+                (self.result(), values_to_spvalue(&result.values)),
+                (self.history(), SPValue::Array(ArrayOrUnknown::Array(history))),
+            ]),
+            true,
+        );
+        new_state
+    }
+
+    /// The result variables and the two variables they are published in.
+    pub fn get_all_var_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.variables.iter().map(|var| var.name.clone()).collect();
+        keys.push(self.result().name);
+        keys.push(self.history().name);
+        keys
+    }
+}
+
+fn values_to_spvalue(values: &[(String, SPValue)]) -> SPValue {
+    SPValue::Map(MapOrUnknown::Map(
+        values
+            .iter()
+            .map(|(name, value)| (name.to_spvalue(), value.clone()))
+            .collect(),
+    ))
+}
+
+impl OperationResult {
+    /// The result as a history entry: a map of `values`, `completed_at` and
+    /// `duration_ms`.
+    pub fn to_spvalue(&self) -> SPValue {
+        SPValue::Map(MapOrUnknown::Map(vec![
+            ("values".to_spvalue(), values_to_spvalue(&self.values)),
+            ("completed_at".to_spvalue(), self.completed_at.to_spvalue()),
+            ("duration_ms".to_spvalue(), self.duration_ms.to_spvalue()),
+        ]))
+    }
+
+    /// Read back a history entry written by [`OperationResult::to_spvalue`].
+    pub fn from_spvalue(value: &SPValue) -> Option<OperationResult> {
+        let field = |name: &str| value.get_path(&[PathSegment::Field(name.to_string())]);
+        let values = match field("values")? {
+            SPValue::Map(MapOrUnknown::Map(entries)) => entries
+                .into_iter()
+                .filter_map(|(name, value)| match name {
+                    SPValue::String(StringOrUnknown::String(name)) => Some((name, value)),
+                    _ => None,
+                })
+                .collect(),
+            _ => return None,
+        };
+        let completed_at = match field("completed_at")? {
+            SPValue::Time(TimeOrUnknown::Time(time)) => time,
+            _ => return None,
+        };
+        let duration_ms = match field("duration_ms")? {
+            SPValue::Int64(IntOrUnknown::Int64(ms)) => ms,
+            _ => return None,
+        };
+        Some(OperationResult {
+            values,
+            completed_at,
+            duration_ms,
+        })
+    }
+
+    /// Every entry of a `{op}_result_history` value that reads back, oldest
+    /// first.
+    pub fn history_from_spvalue(value: &SPValue) -> Vec<OperationResult> {
+        match value {
+            SPValue::Array(ArrayOrUnknown::Array(entries)) => {
+                entries.iter().filter_map(OperationResult::from_spvalue).collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod operation_result_tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn declaration() -> ResultDeclaration {
+        ResultDeclaration {
+            operation: "op_scan".to_string(),
+            variables: vec![v!("part_id"), fv!("confidence")],
+        }
+    }
+
+    fn scanned(part_id: &str) -> State {
+        let mut state = declaration().state_variables();
+        state.extend_mut(
+            State::from_vec(&vec![
+                (v!("part_id"), part_id.to_spvalue()),
+                (fv!("confidence"), 0.9.to_spvalue()),
+            ]),
+            true,
+        );
+        state
+    }
+
+    #[test]
+    fn the_result_is_readable_by_a_guard() {
+        let state = declaration().record(&scanned("p17"), UNIX_EPOCH, 40, TARGET);
+        let guard = pred_parser::pred("var:op_scan_result.part_id == p17", &state).unwrap();
+        assert!(guard.eval(&state, TARGET));
+    }
+
+    #[test]
+    fn the_history_keeps_the_latest_results_oldest_first() {
+        let mut state = scanned("p0");
+        for n in 0..RESULT_HISTORY_LENGTH + 3 {
+            state = state.update("part_id", format!("p{n}").to_spvalue());
+            let at = UNIX_EPOCH + Duration::from_secs(n as u64);
+            state = declaration().record(&state, at, n as i64, TARGET);
+        }
+
+        let history = OperationResult::history_from_spvalue(
+            &state.get_value("op_scan_result_history", TARGET).unwrap(),
+        );
+        assert_eq!(history.len(), RESULT_HISTORY_LENGTH);
+        assert_eq!(history[0].values[0], ("part_id".to_string(), "p3".to_spvalue()));
+        assert_eq!(history[0].completed_at, UNIX_EPOCH + Duration::from_secs(3));
+        assert_eq!(history.last().unwrap().duration_ms, RESULT_HISTORY_LENGTH as i64 + 2);
+    }
+}
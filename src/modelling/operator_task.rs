@@ -0,0 +1,404 @@
+//! [`OperatorTask`]s: operations carried out by a person rather than a machine.
+//!
+//! Some steps - "load fixture", "check the weld" - are done by hand. An operator
+//! task is an ordinary [`Operation`] whose transitions are wired to two keys
+//! instead of to equipment:
+//!
+//! * `{name}_operator_request` - a map with the `prompt`, the names of the
+//!   `inputs` the operator has to fill in, and the `timeout_ms`. Published when
+//!   the operation starts, withdrawn (set to `UNKNOWN`) when it ends.
+//! * `{name}_operator_response` - where the operator's answer goes: `accept`,
+//!   `reject` or `failure` (see [`OperatorResponse`]). Cleared on start, so an
+//!   answer from an earlier run never counts.
+//!
+//! `accept` takes a postcondition, once every input has a value; `reject` and
+//! `failure` take a failure transition, so the usual retries, bypass and fatal
+//! handling apply. Going unanswered past the timeout is an ordinary timeout.
+//!
+//! ```
+//! use micro_sp::*;
+//!
+//! let task = OperatorTask::new(
+//!     "load_fixture",
+//!     "Load fixture A into station 2.",
+//!     vec![SPVariable::new("fixture_id", SPValueType::String)],
+//!     Some(120_000),
+//! );
+//! let operation = task.to_operation(vec![], vec![], vec![]);
+//!
+//! let mut state = task.state_variables();
+//! state.add_mut(
+//!     SPAssignment::new(SPVariable::new("fixture_id", SPValueType::String), "A".to_spvalue()),
+//!     "docs",
+//! );
+//! state.add_mut(
+//!     SPAssignment::new(SPVariable::new("load_fixture", SPValueType::String), "initial".to_spvalue()),
+//!     "docs",
+//! );
+//!
+//! let state = operation.start(&state, "docs");
+//! assert_eq!(state.get_value(&task.request_key(), "docs"), Some(task.request()));
+//! assert!(!operation.can_be_completed(&state, "docs")); // the input was cleared
+//!
+//! let state = state
+//!     .update("fixture_id", "A".to_spvalue())
+//!     .update(&task.response_key(), OperatorResponse::Accept.to_spvalue());
+//! assert!(operation.can_be_completed(&state, "docs"));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::*;
+
+/// The operator's answer, read from `{name}_operator_response`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OperatorResponse {
+    /// The task is done; serialises to `"accept"`.
+    Accept,
+    /// The operator declined to do the task; `"reject"`.
+    Reject,
+    /// The operator tried and it did not work; `"failure"`.
+    Failure,
+    /// No answer yet, or nothing recognisable; `"UNKNOWN"`. Also the [`Default`].
+    #[default]
+    UNKNOWN,
+}
+
+impl OperatorResponse {
+    /// Parses the string form written to `{name}_operator_response`.
+    pub fn parse(x: &str) -> OperatorResponse {
+        match x {
+            "accept" => OperatorResponse::Accept,
+            "reject" => OperatorResponse::Reject,
+            "failure" => OperatorResponse::Failure,
+            _ => OperatorResponse::UNKNOWN,
+        }
+    }
+
+    /// The response as the [`SPValue`] stored in the shared state, matching
+    /// [`Display`](std::fmt::Display).
+    pub fn to_spvalue(self) -> SPValue {
+        self.to_string().to_spvalue()
+    }
+}
+
+impl fmt::Display for OperatorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OperatorResponse::Accept => write!(f, "accept"),
+            OperatorResponse::Reject => write!(f, "reject"),
+            OperatorResponse::Failure => write!(f, "failure"),
+            OperatorResponse::UNKNOWN => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+/// A step done by a person: what to ask, what to collect, and how long to wait.
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
+pub struct OperatorTask {
+    /// Name of the operation, and the prefix of its request and response keys.
+    pub name: String,
+    /// What the operator is asked to do.
+    pub prompt: String,
+    /// Variables the operator has to fill in before `accept` counts. They are
+    /// model variables and have to be declared like any other.
+    pub inputs: Vec<SPVariable>,
+    /// How long to wait for an answer, in milliseconds. `None` means
+    /// [`MAX_ALLOWED_OPERATION_DURATION_MS`], as for [`Operation::new`].
+    pub timeout_ms: Option<i64>,
+}
+
+impl OperatorTask {
+    /// Define an operator task.
+    pub fn new(
+        name: &str,
+        prompt: &str,
+        inputs: Vec<SPVariable>,
+        timeout_ms: Option<i64>,
+    ) -> OperatorTask {
+        OperatorTask {
+            name: name.to_string(),
+            prompt: prompt.to_string(),
+            inputs,
+            timeout_ms,
+        }
+    }
+
+    /// `{name}_operator_request`.
+    pub fn request_key(&self) -> String {
+        format!("{}_operator_request", self.name)
+    }
+
+    /// `{name}_operator_response`.
+    pub fn response_key(&self) -> String {
+        format!("{}_operator_response", self.name)
+    }
+
+    /// The request published on start: a map of `prompt`, `inputs` (the
+    /// variable names) and `timeout_ms`.
+    pub fn request(&self) -> SPValue {
+        SPValue::Map(MapOrUnknown::Map(vec![
+            ("prompt".to_spvalue(), self.prompt.to_spvalue()),
+            (
+                "inputs".to_spvalue(),
+                self.inputs
+                    .iter()
+                    .map(|input| input.name.to_spvalue())
+                    .collect::<Vec<SPValue>>()
+                    .to_spvalue(),
+            ),
+            (
+                "timeout_ms".to_spvalue(),
+                self.timeout_ms
+                    .unwrap_or(MAX_ALLOWED_OPERATION_DURATION_MS)
+                    .to_spvalue(),
+            ),
+        ]))
+    }
+
+    /// The request and response variables, both `UNKNOWN`, for the initial
+    /// state.
+    pub fn state_variables(&self) -> State {
+        State::from_vec(&vec![
+            (
+                SPVariable::new(&self.request_key(), SPValueType::Map),
+                SPValue::Map(MapOrUnknown::UNKNOWN),
+            ),
+            (
+                SPVariable::new(&self.response_key(), SPValueType::String),
+                SPValue::String(StringOrUnknown::UNKNOWN),
+            ),
+        ])
+    }
+
+    /// The [`Operation`] that carries the task out.
+    ///
+    /// `preconditions` say when the task may be asked for; each also publishes
+    /// the request and clears the response and the inputs. `postconditions`
+    /// and `failure_transitions` carry the task's effects and only fire on the
+    /// matching answer; either may be empty, in which case a plain transition
+    /// is used. An empty `preconditions` starts unconditionally.
+    pub fn to_operation(
+        &self,
+        preconditions: Vec<Transition>,
+        postconditions: Vec<Transition>,
+        failure_transitions: Vec<Transition>,
+    ) -> Operation {
+        let request = SPVariable::new(&self.request_key(), SPValueType::Map);
+        let response = SPVariable::new(&self.response_key(), SPValueType::String);
+        let answered = |answer: OperatorResponse| {
+            Predicate::EQ(
+                SPWrapped::SPVariable(response.clone()),
+                SPWrapped::SPValue(answer.to_spvalue()),
+            )
+        };
+        let withdraw = Action::new(
+            request.clone(),
+            SPWrapped::SPValue(SPValue::Map(MapOrUnknown::UNKNOWN)),
+        );
+
+        let mut publish = vec![
+            Action::new(request.clone(), SPWrapped::SPValue(self.request())),
+            Action::new(
+                response.clone(),
+                SPWrapped::SPValue(SPValue::String(StringOrUnknown::UNKNOWN)),
+            ),
+        ];
+        publish.extend(self.inputs.iter().map(|input| {
+            Action::new(input.clone(), SPWrapped::SPValue(input.value_type.unknown()))
+        }));
+
+        let mut accepted = vec![answered(OperatorResponse::Accept)];
+        accepted.extend(self.inputs.iter().map(|input| {
+            Predicate::NEQ(
+                SPWrapped::SPVariable(input.clone()),
+                SPWrapped::SPValue(input.value_type.unknown()),
+            )
+        }));
+        let accepted = Predicate::AND(accepted);
+        let refused = Predicate::OR(vec![
+            answered(OperatorResponse::Reject),
+            answered(OperatorResponse::Failure),
+        ]);
+
+        let plain = |name: &str| {
+            vec![Transition::new(
+                &format!("{}_{}", self.name, name),
+                Predicate::TRUE,
+                Predicate::TRUE,
+                vec![],
+                vec![],
+            )]
+        };
+        let or_plain = |transitions: Vec<Transition>, name: &str| match transitions.is_empty() {
+            true => plain(name),
+            false => transitions,
+        };
+        let gated = |transitions: Vec<Transition>, gate: &Predicate, extra: &[Action]| {
+            transitions
+                .into_iter()
+                .map(|mut transition| {
+                    transition.runner_guard =
+                        Predicate::AND(vec![transition.runner_guard, gate.clone()]);
+                    transition.runner_actions.extend(extra.iter().cloned());
+                    transition
+                })
+                .collect::<Vec<Transition>>()
+        };
+
+        Operation::new(
+            &self.name,
+            self.timeout_ms,
+            None,
+            None,
+            None,
+            false,
+            gated(or_plain(preconditions, "request"), &Predicate::TRUE, &publish),
+            gated(
+                or_plain(postconditions, "accepted"),
+                &accepted,
+                std::slice::from_ref(&withdraw),
+            ),
+            gated(
+                or_plain(failure_transitions, "refused"),
+                &refused,
+                std::slice::from_ref(&withdraw),
+            ),
+            gated(
+                plain("unanswered"),
+                &Predicate::TRUE,
+                std::slice::from_ref(&withdraw),
+            ),
+            vec![],
+            gated(plain("withdrawn"), &Predicate::TRUE, &[withdraw]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod operator_task_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn task() -> OperatorTask {
+        OperatorTask::new(
+            "load",
+            "Load the fixture.",
+            vec![SPVariable::new("count", SPValueType::Int64)],
+            Some(5_000),
+        )
+    }
+
+    fn state() -> State {
+        let mut state = task().state_variables();
+        for (var, value) in [
+            (SPVariable::new("load", SPValueType::String), "initial".to_spvalue()),
+            (SPVariable::new("count", SPValueType::Int64), 3.to_spvalue()),
+            (SPVariable::new("loaded", SPValueType::Bool), false.to_spvalue()),
+        ] {
+            state.add_mut(SPAssignment::new(var, value), TARGET);
+        }
+        state
+    }
+
+    fn answer(state: &State, response: OperatorResponse) -> State {
+        state.update(&task().response_key(), response.to_spvalue())
+    }
+
+    #[test]
+    fn every_response_survives_a_round_trip() {
+        for response in [
+            OperatorResponse::Accept,
+            OperatorResponse::Reject,
+            OperatorResponse::Failure,
+        ] {
+            assert_eq!(OperatorResponse::parse(&response.to_string()), response);
+        }
+        assert_eq!(OperatorResponse::parse("yes"), OperatorResponse::UNKNOWN);
+    }
+
+    #[test]
+    fn starting_publishes_the_request_and_clears_stale_answers() {
+        let operation = task().to_operation(vec![], vec![], vec![]);
+        let state = answer(&state(), OperatorResponse::Accept);
+
+        let state = operation.start(&state, TARGET);
+
+        assert_eq!(state.get_value("load", TARGET), Some("executing".to_spvalue()));
+        assert_eq!(state.get_value(&task().request_key(), TARGET), Some(task().request()));
+        assert_eq!(
+            state.get_value(&task().response_key(), TARGET),
+            Some(SPValue::String(StringOrUnknown::UNKNOWN))
+        );
+        assert_eq!(
+            state.get_value("count", TARGET),
+            Some(SPValue::Int64(IntOrUnknown::UNKNOWN))
+        );
+        assert!(!operation.can_be_completed(&state, TARGET));
+        assert!(!operation.can_be_failed(&state, TARGET));
+    }
+
+    #[test]
+    fn accept_needs_every_input_and_takes_the_postcondition() {
+        let effect = Transition::new(
+            "done",
+            Predicate::TRUE,
+            Predicate::TRUE,
+            vec![Action::new(
+                SPVariable::new("loaded", SPValueType::Bool),
+                true.to_spvalue().wrap(),
+            )],
+            vec![],
+        );
+        let operation = task().to_operation(vec![], vec![effect], vec![]);
+        let state = operation.start(&state(), TARGET);
+
+        let accepted = answer(&state, OperatorResponse::Accept);
+        assert!(!operation.can_be_completed(&accepted, TARGET), "'count' is unset");
+
+        let accepted = accepted.update("count", 4.to_spvalue());
+        let state = operation.complete(&accepted, TARGET);
+        assert_eq!(state.get_value("load", TARGET), Some("completed".to_spvalue()));
+        assert_eq!(state.get_value("loaded", TARGET), Some(true.to_spvalue()));
+        assert_eq!(
+            state.get_value(&task().request_key(), TARGET),
+            Some(SPValue::Map(MapOrUnknown::UNKNOWN))
+        );
+    }
+
+    #[test]
+    fn reject_and_failure_take_a_failure_transition() {
+        let operation = task().to_operation(vec![], vec![], vec![]);
+        let state = operation.start(&state(), TARGET);
+
+        for response in [OperatorResponse::Reject, OperatorResponse::Failure] {
+            let answered = answer(&state, response);
+            assert!(operation.can_be_failed(&answered, TARGET));
+            let failed = operation.fail(&answered, TARGET);
+            assert_eq!(failed.get_value("load", TARGET), Some("failed".to_spvalue()));
+        }
+    }
+
+    #[test]
+    fn the_timeout_is_the_operations_executing_deadline() {
+        let operation = task().to_operation(vec![], vec![], vec![]);
+        assert_eq!(operation.timeout_executing_ms, Some(5_000));
+        assert_eq!(operation.timeout_transitions.len(), 1);
+    }
+
+    #[test]
+    fn cancelling_withdraws_the_request() {
+        let operation = task().to_operation(vec![], vec![], vec![]);
+        let state = operation.start(&state(), TARGET);
+        assert_eq!(state.get_value(&task().request_key(), TARGET), Some(task().request()));
+
+        let state = operation.cancel(&state, TARGET);
+        assert_eq!(state.get_value("load", TARGET), Some("cancelled".to_spvalue()));
+        assert_eq!(
+            state.get_value(&task().request_key(), TARGET),
+            Some(SPValue::Map(MapOrUnknown::UNKNOWN))
+        );
+    }
+}
@@ -4,7 +4,23 @@
 //! [`Predicate`], and `"var:pos <- b"` into an [`Action`]. This is what
 //! [`Transition::parse`] uses, and it is how models are normally written.
 //! Variables are looked up in the [`State`] passed in, so they must already
-//! exist there.
+//! exist there. Both sides of a comparison and the right-hand side of an
+//! assignment may be arithmetic, as in `"var:x - var:y > 0.5"`; see
+//! [`SPExpression`]. Timeouts and staleness are written with `now()` and
+//! `age`, as in `"var:last_seen <- now()"` and
+//! `"age(var:last_seen) > dur:500ms"`, or `"var:last_seen <- now"` and
+//! `"age(var:last_seen) > 500ms"` where the other side gives them away. A
+//! `var:` reference may reach into a map, an array or a transform with a path,
+//! as in `"var:pose.translation.z > 0.3"`, `"var:queue[0] == a"` or
+//! `"var:config.speed <- 2.5"`; see [`PathSegment`].
+//!
+//! [`try_parse_pred`] and [`try_parse_action`] wrap the grammar for model
+//! authors: instead of peg's bare "expected ..." they return
+//! [`ParseDiagnostic`]s that carry the span and offending token, and suggest
+//! the closest variable names for a misspelt `var:` reference.
+
+use ordered_float::OrderedFloat;
+use std::{fmt, ops::Range};
 
 use crate::*;
 
@@ -35,21 +51,45 @@ pub grammar pred_parser() for str {
     rule _() =  quiet!{[' ' | '\t']*}
 
     /// A state variable reference, written `var:name`. The name is looked up in
-    /// `state`, and looking up a name that is not there panics.
+    /// `state`; a name that is not there is a parse error, not a panic.
     pub rule variable(state: &State) -> SPVariable =
-        "var:" _ n:$(['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '/']+) !(['a'..='z' | 'A'..='Z' | '0'..='9' | '_']) {
-        state.get_assignment(n, "parser").var
+        "var:" _ n:$(['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '/']+) !(['a'..='z' | 'A'..='Z' | '0'..='9' | '_']) {?
+        match state.state.get(n) {
+            Some(assignment) => Ok(assignment.var.clone()),
+            None => Err("a variable that is in the state"),
+        }
     }
 
+    /// A token made only of digits (optionally signed, optionally with a
+    /// fraction). Such a token is a number or an error, never a bare word, so
+    /// an integer that overflows `i64` is reported instead of becoming a string.
+    rule number() = "-"? ['0'..='9']+ ("." ['0'..='9']+)? !(['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-'])
+
+    /// One step of a path into a structured value: `.field` into a map or a
+    /// transform, or `[index]` into an array.
+    pub rule path_segment() -> PathSegment
+        = "." n:$(['a'..='z' | 'A'..='Z' | '_'] ['a'..='z' | 'A'..='Z' | '0'..='9' | '_']*) {
+            PathSegment::Field(n.to_string())
+        }
+        / "[" _ i:$(['0'..='9']+) _ "]" {? i.parse().map(PathSegment::Index).or(Err("an index that fits in usize")) }
+
     /// One element inside an array literal; the same syntax as [`value`].
     pub rule array_element(state: &State) -> SPWrapped =
     v:value(state) { v }
 
     /// Anything that can stand on either side of a comparison or assignment:
-    /// a `var:` reference, a literal (bare word, quoted string, number, bool,
-    /// `ip:[...]`, array) or a typed unknown such as `UNKNOWN_int`.
+    /// a `var:` reference, possibly followed by a path such as
+    /// `var:pose.translation.z` or `var:queue[0]`, a literal (bare word, quoted
+    /// string, number, bool, `ip:[...]`, array) or a typed unknown such as
+    /// `UNKNOWN_int`.
     pub rule value(state: &State) -> SPWrapped
-        = _ var:variable(&state) _ { SPWrapped::SPVariable(var) }
+        = _ var:variable(&state) path:path_segment()* _ {
+            if path.is_empty() {
+                SPWrapped::SPVariable(var)
+            } else {
+                SPWrapped::Path(var, path)
+            }
+        }
         / _ "UNKNOWN_bool" _ { SPWrapped::SPValue(SPValue::Bool(BoolOrUnknown::UNKNOWN)) }
         / _ "UNKNOWN_int" _ { SPWrapped::SPValue(SPValue::Int64(IntOrUnknown::UNKNOWN)) }
         / _ "UNKNOWN_float" _ { SPWrapped::SPValue(SPValue::Float64(FloatOrUnknown::UNKNOWN)) }
@@ -69,26 +109,92 @@ pub grammar pred_parser() for str {
         / _ "ip:" _ "[" _ content:$([^']']*) _ "]" _ {
             SPWrapped::SPValue(content.to_spvalue())
         }
-        / _ n:$("-"? ['0'..='9']+ "." ['0'..='9']+) !(['a'..='z' | 'A'..='Z' | '0'..='9' | '_']) _ { // Float
-            let f: f64 = n.parse().expect("Failed to parse float");
-            SPWrapped::SPValue(f.to_spvalue())
+        / _ n:$("-"? ['0'..='9']+ "." ['0'..='9']+) !(['a'..='z' | 'A'..='Z' | '0'..='9' | '_']) _ {? // Float
+            n.parse::<f64>()
+                .map(|f| SPWrapped::SPValue(f.to_spvalue()))
+                .or(Err("a float"))
         }
-        / _ n:$("-"? ['0'..='9']+) !(['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.']) _ { // Integer
-            let i: i64 = n.parse().expect("Failed to parse integer");
-            SPWrapped::SPValue(i.to_spvalue())
+        / _ n:$("-"? ['0'..='9']+) !(['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.']) _ {? // Integer
+            n.parse::<i64>()
+                .map(|i| SPWrapped::SPValue(i.to_spvalue()))
+                .or(Err("an integer that fits in 64 bits"))
         }
-        / _ n:$(['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-']+) _ {
+        / _ !number() n:$(['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-']+) _ {
             SPWrapped::SPValue(n.to_spvalue())
         }
 
-    /// A single comparison between two values: `==`, `!=`, `<=`, `<`, `>=`, `>`.
+    /// A duration in `ms`, `s` or `min`, such as `250ms` or `1.5s`, as an
+    /// `i64` number of milliseconds. Written after `dur:` in an expression, or
+    /// bare where it is compared with an `age()`; anywhere else a bare `10s`
+    /// stays the string it always was.
+    pub rule duration() -> SPValue
+        = n:$(['0'..='9']+ ("." ['0'..='9']+)?) unit:$("ms" / "min" / "s") word_end() {?
+            let scale = match unit {
+                "ms" => 1.0,
+                "s" => 1_000.0,
+                _ => 60_000.0,
+            };
+            n.parse::<f64>()
+                .map(|n| ((n * scale).round() as i64).to_spvalue())
+                .or(Err("a duration"))
+        }
+
+    /// A value, or arithmetic over values: `+`, `-`, `*`, `/` with the usual
+    /// precedence, parentheses, and `abs(a)`, `min(a, b)`, `max(a, b)`. Time
+    /// has `now()`, the current time, `age(t)`, the milliseconds since `t`,
+    /// and `dur:` duration literals to compare ages with:
+    /// `age(var:last_seen) > dur:5s`. Where the other side of a comparison or
+    /// assignment is a time, a bare `now` is the clock too, and where it is an
+    /// `age()`, a bare `5s` is a duration: `var:last_seen <- now`,
+    /// `age(var:last_seen) > 5s`. Anywhere else bare words such as `now`,
+    /// `len` or `5s` are string values.
+    /// Write `/` with spaces around it - `var:a/b` is the variable `a/b` - and
+    /// do not write `+-` for adding a negative number: that is the tolerance of
+    /// a `~=` comparison.
+    pub rule expr(state: &State) -> SPWrapped = precedence!{
+        a:(@) _ "+" !"-" _ b:@ { SPExpression::Add(a, b).wrap() }
+        a:(@) _ "-" _ b:@ { SPExpression::Sub(a, b).wrap() }
+        --
+        a:(@) _ "*" _ b:@ { SPExpression::Mul(a, b).wrap() }
+        a:(@) _ "/" _ b:@ { SPExpression::Div(a, b).wrap() }
+        --
+        _ "abs" _ "(" _ a:expr(state) _ ")" _ { SPExpression::Abs(a).wrap() }
+        _ "len" _ "(" _ a:expr(state) _ ")" _ { SPExpression::Len(a).wrap() }
+        _ "min" _ "(" _ a:expr(state) _ "," _ b:expr(state) _ ")" _ { SPExpression::Min(a, b).wrap() }
+        _ "max" _ "(" _ a:expr(state) _ "," _ b:expr(state) _ ")" _ { SPExpression::Max(a, b).wrap() }
+        _ "age" _ "(" _ a:expr(state) _ ")" _ { SPExpression::Age(a).wrap() }
+        _ "now" _ "(" _ ")" _ { SPExpression::Now.wrap() }
+        _ "dur:" d:duration() _ { d.wrap() }
+        _ "(" _ e:expr(state) _ ")" _ { e }
+        v:value(state) { v }
+    }
+
+    /// The end of a keyword: it may not run on into a longer word.
+    rule word_end() = !['a'..='z' | 'A'..='Z' | '0'..='9' | '_']
+
+    /// The tolerance of an approximate comparison: a non-negative number.
+    rule tolerance() -> OrderedFloat<f64>
+        = n:$(['0'..='9']+ ("." ['0'..='9']+)?) _ {? n.parse().map(OrderedFloat).or(Err("a tolerance")) }
+
+    /// A single comparison between two expressions: `==`, `!=`, `<=`, `<`,
+    /// `>=`, `>`; `~=` for `f64` values that need only be close, as in
+    /// `var:x ~= 0.25 +- 0.01`, with the variable's declared
+    /// [`SPVariable::tolerance`] when `+- t` is left out; membership in an array, `x in a`, `x not in a` or
+    /// `a contains x`; or a map key lookup, `has_key(m, k)`.
     pub rule eq(state: &State) -> Predicate
-        = p1:value(&state) _ "==" _ p2:value(&state) { Predicate::EQ(p1,p2) }
-        / p1:value(&state) _ "!=" _ p2:value(&state) { Predicate::NEQ(p1,p2) }
-        / p1:value(&state) _ "<=" _ p2:value(&state) { Predicate::LTEQ(p1,p2) }
-        / p1:value(&state) _ "<" _ p2:value(&state) { Predicate::LT(p1,p2) }
-        / p1:value(&state) _ ">=" _ p2:value(&state) { Predicate::GTEQ(p1,p2) }
-        / p1:value(&state) _ ">" _ p2:value(&state) { Predicate::GT(p1,p2) }
+        = _ "has_key" _ "(" _ m:expr(&state) _ "," _ k:expr(&state) _ ")" _ { Predicate::HASKEY(m, k) }
+        / p1:expr(&state) _ "in" word_end() _ p2:expr(&state) { Predicate::IN(p1, p2) }
+        / p1:expr(&state) _ "not" [' ' | '\t']+ "in" word_end() _ p2:expr(&state) { Predicate::NOTIN(p1, p2) }
+        / p1:expr(&state) _ "contains" word_end() _ p2:expr(&state) { Predicate::IN(p2, p1) }
+        / p1:expr(&state) _ "~=" _ p2:expr(&state) t:(_ "+-" _ t:tolerance() { t })? {
+            Predicate::APPROX(p1, p2, t)
+        }
+        / p1:expr(&state) _ "==" _ p2:expr(&state) { let (p1, p2) = time_operands(p1, p2); Predicate::EQ(p1,p2) }
+        / p1:expr(&state) _ "!=" _ p2:expr(&state) { let (p1, p2) = time_operands(p1, p2); Predicate::NEQ(p1,p2) }
+        / p1:expr(&state) _ "<=" _ p2:expr(&state) { let (p1, p2) = time_operands(p1, p2); Predicate::LTEQ(p1,p2) }
+        / p1:expr(&state) _ "<" _ p2:expr(&state) { let (p1, p2) = time_operands(p1, p2); Predicate::LT(p1,p2) }
+        / p1:expr(&state) _ ">=" _ p2:expr(&state) { let (p1, p2) = time_operands(p1, p2); Predicate::GTEQ(p1,p2) }
+        / p1:expr(&state) _ ">" _ p2:expr(&state) { let (p1, p2) = time_operands(p1, p2); Predicate::GT(p1,p2) }
 
     /// A full predicate: comparisons combined with `&&`, `||`, `!`, `->` and
     /// parentheses, plus the constants `true`/`TRUE` and `false`/`FALSE`.
@@ -130,16 +236,676 @@ pub grammar pred_parser() for str {
         _ "false" _ { Predicate::FALSE }
     }
 
-    /// An assignment: `var:x <- value`, or `var:x += value` / `var:x -= value`
-    /// for numeric variables.
+    /// An assignment: `var:x <- expr`, or `var:x += expr` / `var:x -= expr`
+    /// for numeric variables. `var:x.field <- expr` or `var:x[0] <- expr`
+    /// replaces one part of a map, an array or a transform. Arrays and maps
+    /// are edited with calls:
+    /// `push(var:a, x)`, `pop_front(var:a)`, `remove(var:a, x)`, `clear(var:a)`,
+    /// `map_insert(var:m, k, v)` and `map_remove(var:m, k)`.
     pub rule action(state: &State) -> Action
-        = p1:variable(&state) _ "+=" _ p2:value(&state) { Action::inc(p1, p2) }
-        / p1:variable(&state) _ "-=" _ p2:value(&state) { Action::dec(p1, p2) }
-        / p1:variable(&state) _ "<-" _ p2:variable(&state) { Action::new(p1, p2.wrap()) }
-        / p1:variable(&state) _ "<-" _ p2:value(&state) { Action::new(p1, p2) }
+        = _ "push" _ "(" _ p1:variable(&state) _ "," _ p2:expr(&state) _ ")" _ { Action::push(p1, p2) }
+        / _ "pop_front" _ "(" _ p1:variable(&state) _ ")" _ { Action::pop_front(p1) }
+        / _ "remove" _ "(" _ p1:variable(&state) _ "," _ p2:expr(&state) _ ")" _ { Action::remove(p1, p2) }
+        / _ "clear" _ "(" _ p1:variable(&state) _ ")" _ { Action::clear(p1) }
+        / _ "map_insert" _ "(" _ p1:variable(&state) _ "," _ k:expr(&state) _ "," _ v:expr(&state) _ ")" _ {
+            Action::map_insert(p1, k, v)
+        }
+        / _ "map_remove" _ "(" _ p1:variable(&state) _ "," _ k:expr(&state) _ ")" _ { Action::map_remove(p1, k) }
+        / p1:variable(&state) _ "+=" _ p2:expr(&state) { Action::inc(p1, p2) }
+        / p1:variable(&state) _ "-=" _ p2:expr(&state) { Action::dec(p1, p2) }
+        / p1:variable(&state) path:path_segment()+ _ "<-" _ p2:expr(&state) { Action::assign_at(p1, path, p2) }
+        / p1:variable(&state) _ "<-" _ p2:expr(&state) {
+            let p2 = time_literal(&p2, &p1.clone().wrap()).unwrap_or(p2);
+            Action::new(p1, p2)
+        }
     }
 );
 
+/// `a` and `b`, with a bare `now` or duration on one side read as
+/// [`time_literal`] reads it against the other.
+fn time_operands(a: SPWrapped, b: SPWrapped) -> (SPWrapped, SPWrapped) {
+    match (time_literal(&a, &b), time_literal(&b, &a)) {
+        (Some(a), _) => (a, b),
+        (_, Some(b)) => (a, b),
+        _ => (a, b),
+    }
+}
+
+/// `literal` as the clock, if it is the string `now` and `other` is a time, or
+/// as a number of milliseconds, if it is a duration such as `5s` and `other`
+/// is an `age()`. Compared with anything else, both stay strings.
+fn time_literal(literal: &SPWrapped, other: &SPWrapped) -> Option<SPWrapped> {
+    let SPWrapped::SPValue(SPValue::String(StringOrUnknown::String(word))) = literal else {
+        return None;
+    };
+    match other {
+        _ if word == "now" && other.static_type() == Some(SPValueType::Time) => {
+            Some(SPExpression::Now.wrap())
+        }
+        SPWrapped::Expression(expression) if matches!(**expression, SPExpression::Age(_)) => {
+            pred_parser::duration(word).ok().map(|duration| duration.wrap())
+        }
+        _ => None,
+    }
+}
+
+/// One problem found while parsing a guard or action string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseDiagnostic {
+    /// Where the string came from, e.g. `guard of 'move_to_b'`. Empty when the
+    /// string was parsed on its own.
+    pub context: String,
+    /// The full string that was parsed.
+    pub source: String,
+    /// The byte range of the offending token in `source`.
+    pub span: Range<usize>,
+    /// The offending token itself; empty at the end of the input.
+    pub token: String,
+    /// What is wrong.
+    pub message: String,
+    /// Likely fixes, best first. May be empty.
+    pub suggestions: Vec<String>,
+}
+
+impl ParseDiagnostic {
+    /// The same diagnostic, attributed to `context`.
+    pub fn in_context(mut self, context: &str) -> ParseDiagnostic {
+        self.context = context.to_string();
+        self
+    }
+}
+
+impl fmt::Display for ParseDiagnostic {
+    /// One line naming the problem, then the source with the span underlined:
+    ///
+    /// ```text
+    /// guard of 'move_to_b', column 5: unknown variable 'pso' (did you mean 'pos'?)
+    ///     var:pso == a
+    ///         ^^^
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.source[..self.span.start].chars().count();
+        if !self.context.is_empty() {
+            write!(f, "{}, ", self.context)?;
+        }
+        write!(f, "column {}: {}", column + 1, self.message)?;
+        if !self.suggestions.is_empty() {
+            let suggestions: Vec<String> =
+                self.suggestions.iter().map(|s| format!("'{s}'")).collect();
+            write!(f, " (did you mean {}?)", suggestions.join(" or "))?;
+        }
+        let width = self.token.chars().count().max(1);
+        write!(
+            f,
+            "\n    {}\n    {}{}",
+            self.source,
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// Parse a predicate, reporting every problem as a [`ParseDiagnostic`].
+///
+/// All unknown `var:` names are reported at once, each with the closest names
+/// in `state` as suggestions. If every name is known, the first syntax error
+/// is reported instead - peg stops there, so there is only one. A predicate
+/// that parses is then type-checked: every comparison whose sides have
+/// different [`SPValueType`]s is reported, since it could never hold.
+///
+/// ```
+/// use micro_sp::*;
+///
+/// let state = State::from_vec(&vec![
+///     (SPVariable::new("pos", SPValueType::String), "a".to_spvalue()),
+/// ]);
+///
+/// let errors = try_parse_pred("var:pso == a", &state).unwrap_err();
+/// assert_eq!(errors[0].token, "pso");
+/// assert_eq!(errors[0].span, 4..7);
+/// assert_eq!(errors[0].suggestions, vec!["pos".to_string()]);
+/// ```
+pub fn try_parse_pred(input: &str, state: &State) -> Result<Predicate, Vec<ParseDiagnostic>> {
+    let unknown = unknown_variables(input, state);
+    if !unknown.is_empty() {
+        return Err(unknown);
+    }
+    let predicate =
+        pred_parser::pred(input, state).map_err(|e| vec![syntax_error(input, e, "==")])?;
+    let mistyped = predicate_type_errors(&predicate, input);
+    if mistyped.is_empty() {
+        Ok(predicate)
+    } else {
+        Err(mistyped)
+    }
+}
+
+/// Parse an action, reporting every problem as a [`ParseDiagnostic`].
+///
+/// The counterpart of [`try_parse_pred`] for `var:x <- value` and friends.
+/// The value must have the variable's type, and `+=`/`-=` only apply to
+/// `i64` and `f64` variables.
+pub fn try_parse_action(input: &str, state: &State) -> Result<Action, Vec<ParseDiagnostic>> {
+    let unknown = unknown_variables(input, state);
+    if !unknown.is_empty() {
+        return Err(unknown);
+    }
+    let action =
+        pred_parser::action(input, state).map_err(|e| vec![syntax_error(input, e, "<-")])?;
+    let mistyped = action_type_errors(&action, input);
+    if mistyped.is_empty() {
+        Ok(action)
+    } else {
+        Err(mistyped)
+    }
+}
+
+/// Gather many parse results, keeping every diagnostic rather than stopping at
+/// the first failure.
+///
+/// Meant for whole models: parse each transition with
+/// [`Transition::try_parse`], then collect them here to get either all the
+/// transitions or all the problems.
+pub fn collect_parsed<T>(
+    results: impl IntoIterator<Item = Result<T, Vec<ParseDiagnostic>>>,
+) -> Result<Vec<T>, Vec<ParseDiagnostic>> {
+    let mut parsed = vec![];
+    let mut diagnostics = vec![];
+    for result in results {
+        match result {
+            Ok(item) => parsed.push(item),
+            Err(errors) => diagnostics.extend(errors),
+        }
+    }
+    if diagnostics.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Every `var:name` reference in `input`, as the span of the name and the
+/// name itself.
+fn variable_references(input: &str) -> Vec<(Range<usize>, &str)> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '/';
+    let mut references = vec![];
+    let mut from = 0;
+    while let Some(found) = input[from..].find("var:") {
+        let prefix_end = from + found + "var:".len();
+        let start = prefix_end
+            + input[prefix_end..].len()
+            - input[prefix_end..].trim_start_matches([' ', '\t']).len();
+        let end = input[start..]
+            .find(|c: char| !is_name_char(c))
+            .map_or(input.len(), |i| start + i);
+        if end > start {
+            references.push((start..end, &input[start..end]));
+        }
+        from = end.max(prefix_end);
+    }
+    references
+}
+
+/// Every `var:name` in `input` whose name is not in `state`.
+fn unknown_variables(input: &str, state: &State) -> Vec<ParseDiagnostic> {
+    variable_references(input)
+        .into_iter()
+        .filter(|(_, name)| !state.contains(name))
+        .map(|(span, name)| ParseDiagnostic {
+            context: String::new(),
+            source: input.to_string(),
+            span,
+            token: name.to_string(),
+            message: format!("unknown variable '{name}'"),
+            suggestions: closest_names(name, state.state.keys()),
+        })
+        .collect()
+}
+
+/// Every comparison in `predicate` whose two sides have different types, and
+/// every membership test or key lookup in something that is not an array or
+/// a map.
+///
+/// Such a comparison is always false at runtime - `var:name == 5` on a string
+/// variable never holds - so it is a model error rather than a guard.
+fn predicate_type_errors(predicate: &Predicate, input: &str) -> Vec<ParseDiagnostic> {
+    match predicate {
+        Predicate::TRUE | Predicate::FALSE => vec![],
+        Predicate::NOT(p) => predicate_type_errors(p, input),
+        Predicate::AND(ps) | Predicate::OR(ps) => ps
+            .iter()
+            .flat_map(|p| predicate_type_errors(p, input))
+            .collect(),
+        Predicate::EQ(a, b) | Predicate::NEQ(a, b) => {
+            let errors = comparison_type_errors(a, b, input);
+            match errors.is_empty() {
+                true => domain_errors(a, b, input),
+                false => errors,
+            }
+        }
+        Predicate::LTEQ(a, b)
+        | Predicate::GTEQ(a, b)
+        | Predicate::LT(a, b)
+        | Predicate::GT(a, b) => comparison_type_errors(a, b, input),
+        Predicate::APPROX(a, b, _) => {
+            let errors = comparison_type_errors(a, b, input);
+            if errors.is_empty() && clashes(a.static_type(), Some(SPValueType::Float64)) {
+                return vec![type_error(
+                    input,
+                    &[a, b],
+                    format!("cannot compare {} approximately, only f64 values", describe(a)),
+                    vec![],
+                )];
+            }
+            errors
+        }
+        Predicate::IN(a, b) | Predicate::NOTIN(a, b) => {
+            let mut errors = expression_type_errors(a, input);
+            errors.extend(expression_type_errors(b, input));
+            if !errors.is_empty() {
+                return errors;
+            }
+            match b {
+                SPWrapped::Array(items) => items
+                    .iter()
+                    .flat_map(|item| comparison_type_errors(a, item, input))
+                    .collect(),
+                _ if clashes(b.static_type(), Some(SPValueType::Array)) => vec![type_error(
+                    input,
+                    &[b, a],
+                    format!("cannot look for {} in {}, only in an array", describe(a), describe(b)),
+                    vec![],
+                )],
+                _ => vec![],
+            }
+        }
+        Predicate::HASKEY(a, b) => {
+            let mut errors = expression_type_errors(a, input);
+            errors.extend(expression_type_errors(b, input));
+            if errors.is_empty() && clashes(a.static_type(), Some(SPValueType::Map)) {
+                errors.push(type_error(
+                    input,
+                    &[a, b],
+                    format!("cannot look up a key in {}, only in a map", describe(a)),
+                    vec![],
+                ));
+            }
+            errors
+        }
+    }
+}
+
+/// A literal that a variable with a [`SPVariable::domain`] is compared with or
+/// assigned, but that the domain does not contain, as in `var:pos == d` when
+/// `pos` is one of `{a, b, c}`.
+fn domain_errors(a: &SPWrapped, b: &SPWrapped, input: &str) -> Vec<ParseDiagnostic> {
+    let (var, value) = match (a, b) {
+        (SPWrapped::SPVariable(var), SPWrapped::SPValue(value))
+        | (SPWrapped::SPValue(value), SPWrapped::SPVariable(var)) => (var, value),
+        _ => return vec![],
+    };
+    let Some(domain) = var.domain.as_ref().filter(|domain| !domain.contains(value)) else {
+        return vec![];
+    };
+    let suggestions = match domain {
+        SPDomain::Values(values) => {
+            let names: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            closest_names(&value.to_string(), &names)
+        }
+        SPDomain::Range(..) => vec![],
+    };
+    vec![type_error(
+        input,
+        &[a, b],
+        format!(
+            "{} is outside the domain {} of {}",
+            describe(&value.clone().wrap()),
+            domain,
+            describe(&var.clone().wrap())
+        ),
+        suggestions,
+    )]
+}
+
+/// Whether two operand types are known and differ. A part of a map or an
+/// array has no known type, so it clashes with nothing.
+fn clashes(a: Option<SPValueType>, b: Option<SPValueType>) -> bool {
+    matches!((a, b), (Some(a), Some(b)) if a != b)
+}
+
+/// The type errors in comparing `a` with `b`: those within either side, or
+/// else the two sides having different types.
+fn comparison_type_errors(a: &SPWrapped, b: &SPWrapped, input: &str) -> Vec<ParseDiagnostic> {
+    let mut errors = expression_type_errors(a, input);
+    errors.extend(expression_type_errors(b, input));
+    if !errors.is_empty() || !clashes(a.static_type(), b.static_type()) {
+        return errors;
+    }
+    let suggestions = retyped_literal(b, a.static_type())
+        .or_else(|| retyped_literal(a, b.static_type()))
+        .into_iter()
+        .collect();
+    vec![type_error(
+        input,
+        &[a, b],
+        format!("cannot compare {} with {}", describe(a), describe(b)),
+        suggestions,
+    )]
+}
+
+/// The type error in `action`, if any.
+///
+/// Assignments need the value to have the variable's type. Increments and
+/// decrements also need a numeric variable; mixing `i64` and `f64` there
+/// would otherwise panic in [`Action::assign_mut`] on the first tick, as
+/// would an array edit on anything but an array, or a map edit on anything
+/// but a map.
+fn action_type_errors(action: &Action, input: &str) -> Vec<ParseDiagnostic> {
+    let target = action.var.has_type();
+    let value = &action.var_or_val;
+    let var = match &action.action_type {
+        ActionType::AssignAt(path) => SPWrapped::Path(action.var.clone(), path.clone()),
+        _ => action.var.clone().wrap(),
+    };
+    let mut mistyped = expression_type_errors(&var, input);
+    mistyped.extend(expression_type_errors(value, input));
+    if !mistyped.is_empty() {
+        return mistyped;
+    }
+    let verb = match action.action_type {
+        ActionType::Assign if !clashes(var.static_type(), value.static_type()) => {
+            return domain_errors(&var, value, input);
+        }
+        ActionType::AssignAt(_) if !clashes(var.static_type(), value.static_type()) => {
+            return vec![];
+        }
+        ActionType::Assign | ActionType::AssignAt(_) => {
+            return vec![type_error(
+                input,
+                &[&var, value],
+                format!("cannot assign {} to {}", describe(value), describe(&var)),
+                retyped_literal(value, var.static_type()).into_iter().collect(),
+            )];
+        }
+        ActionType::Increment => "increment",
+        ActionType::Decrement => "decrement",
+        ActionType::Push | ActionType::PopFront | ActionType::Remove
+            if target == SPValueType::Array =>
+        {
+            return vec![];
+        }
+        ActionType::Clear if matches!(target, SPValueType::Array | SPValueType::Map) => {
+            return vec![];
+        }
+        ActionType::MapInsert | ActionType::MapRemove if target == SPValueType::Map => {
+            return vec![];
+        }
+        _ => {
+            let expected = match action.action_type {
+                ActionType::Clear => "an array or a map",
+                ActionType::MapInsert | ActionType::MapRemove => "a map",
+                _ => "an array",
+            };
+            return vec![type_error(
+                input,
+                &[&var],
+                format!("cannot apply '{}' to {}, only to {}", action, describe(&var), expected),
+                vec![],
+            )];
+        }
+    };
+    if !matches!(target, SPValueType::Int64 | SPValueType::Float64) {
+        vec![type_error(
+            input,
+            &[&var],
+            format!("cannot {verb} {}, only i64 and f64 variables can be", describe(&var)),
+            vec![],
+        )]
+    } else if clashes(Some(target), value.static_type()) {
+        vec![type_error(
+            input,
+            &[&var, value],
+            format!("cannot {verb} {} by {}", describe(&var), describe(value)),
+            retyped_literal(value, Some(target)).into_iter().collect(),
+        )]
+    } else {
+        vec![]
+    }
+}
+
+/// Every arithmetic expression within `operand` over anything but two
+/// operands of the same numeric type, every `len` of something that is not
+/// an array or a map, and every path that no value of its variable's type
+/// has.
+fn expression_type_errors(operand: &SPWrapped, input: &str) -> Vec<ParseDiagnostic> {
+    let expression = match operand {
+        SPWrapped::Path(var, path) => {
+            return match path_type(var.has_type(), path) {
+                Ok(_) => vec![],
+                Err(reason) => vec![type_error(
+                    input,
+                    &[operand],
+                    format!("cannot read {} of {}: {reason}", operand, describe(&var.wrap())),
+                    vec![],
+                )],
+            };
+        }
+        SPWrapped::Expression(expression) => expression,
+        SPWrapped::Array(items) => {
+            return items
+                .iter()
+                .flat_map(|item| expression_type_errors(item, input))
+                .collect();
+        }
+        SPWrapped::Map(pairs) => {
+            return pairs
+                .iter()
+                .flat_map(|(k, v)| [k, v])
+                .flat_map(|item| expression_type_errors(item, input))
+                .collect();
+        }
+        _ => return vec![],
+    };
+    let operands = expression.operands();
+    let nested: Vec<ParseDiagnostic> = operands
+        .iter()
+        .flat_map(|operand| expression_type_errors(operand, input))
+        .collect();
+    if !nested.is_empty() {
+        return nested;
+    }
+    let Some(first) = operands.first().map(|operand| operand.static_type()) else {
+        return vec![];
+    };
+    if let SPExpression::Age(_) = **expression {
+        return match first {
+            None | Some(SPValueType::Time) => vec![],
+            _ => vec![type_error(
+                input,
+                &operands,
+                format!("cannot take the age of {}, only of a time", describe(operands[0])),
+                vec![],
+            )],
+        };
+    }
+    if let SPExpression::Len(_) = **expression {
+        return match first {
+            None | Some(SPValueType::Array | SPValueType::Map) => vec![],
+            _ => vec![type_error(
+                input,
+                &operands,
+                format!("cannot take the length of {}, only of an array or a map", describe(operands[0])),
+                vec![],
+            )],
+        };
+    }
+    if !matches!(first, None | Some(SPValueType::Int64 | SPValueType::Float64)) {
+        vec![type_error(
+            input,
+            &operands,
+            format!("cannot do arithmetic on {}", describe(operands[0])),
+            vec![],
+        )]
+    } else if let Some(other) = operands.iter().find(|o| clashes(o.static_type(), first)) {
+        vec![type_error(
+            input,
+            &operands,
+            format!(
+                "cannot do arithmetic on {} and {}",
+                describe(operands[0]),
+                describe(other)
+            ),
+            retyped_literal(other, first).into_iter().collect(),
+        )]
+    } else {
+        vec![]
+    }
+}
+
+/// A type error pointing at the first variable in `operands`, or at the
+/// whole input when they are all literals.
+fn type_error(
+    input: &str,
+    operands: &[&SPWrapped],
+    message: String,
+    suggestions: Vec<String>,
+) -> ParseDiagnostic {
+    let references = variable_references(input);
+    let span = operands
+        .iter()
+        .flat_map(|operand| operand.get_variables())
+        .find_map(|v| {
+            references
+                .iter()
+                .find(|(_, name)| *name == v.name)
+                .map(|(span, _)| span.clone())
+        })
+        .unwrap_or(0..input.len());
+    ParseDiagnostic {
+        context: String::new(),
+        source: input.to_string(),
+        token: input[span.clone()].to_string(),
+        span,
+        message,
+        suggestions,
+    }
+}
+
+/// How an operand reads in a type error: `i64 variable 'height'`,
+/// `string value tall`.
+fn describe(operand: &SPWrapped) -> String {
+    match operand {
+        SPWrapped::SPVariable(v) => format!("{} variable '{}'", v.has_type(), v.name),
+        SPWrapped::SPValue(val) => format!("{} value {}", val.has_type(), val),
+        SPWrapped::Array(_) => "an array".to_string(),
+        SPWrapped::Map(_) => "a map".to_string(),
+        SPWrapped::Expression(e) => match e.static_type() {
+            Some(t) => format!("{t} expression {e}"),
+            None => format!("expression {e}"),
+        },
+        SPWrapped::Path(..) => match operand.static_type() {
+            Some(t) => format!("{t} part '{operand}'"),
+            None => format!("part '{operand}'"),
+        },
+    }
+}
+
+/// `literal` rewritten as a `wanted` literal, where there is an obvious way
+/// to: `5` as `5.0`, `5.0` as `5`, a number or bool quoted as a string, or
+/// a word as the clock, `now()`.
+fn retyped_literal(literal: &SPWrapped, wanted: Option<SPValueType>) -> Option<String> {
+    match (literal, wanted?) {
+        (SPWrapped::SPValue(SPValue::Int64(IntOrUnknown::Int64(i))), SPValueType::Float64) => {
+            Some(format!("{i}.0"))
+        }
+        (SPWrapped::SPValue(SPValue::Float64(FloatOrUnknown::Float64(f))), SPValueType::Int64)
+            if f.fract() == 0.0 =>
+        {
+            Some(format!("{}", f.into_inner() as i64))
+        }
+        (
+            SPWrapped::SPValue(
+                val @ (SPValue::Int64(IntOrUnknown::Int64(_))
+                | SPValue::Float64(FloatOrUnknown::Float64(_))
+                | SPValue::Bool(BoolOrUnknown::Bool(_))),
+            ),
+            SPValueType::String,
+        ) => Some(format!("\"{val}\"")),
+        (SPWrapped::SPValue(SPValue::String(_)), SPValueType::Time) => Some("now()".to_string()),
+        _ => None,
+    }
+}
+
+/// Up to three of `names`, such as the variables in a state, within a small
+/// edit distance of `name`, closest first.
+fn closest_names<'a>(name: &str, names: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    let limit = (name.chars().count() / 3).max(2);
+    let mut candidates: Vec<(usize, &String)> = names
+        .into_iter()
+        .map(|key| (edit_distance(name, key), key))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(3)
+        .map(|(_, key)| key.clone())
+        .collect()
+}
+
+/// Levenshtein distance, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// A peg failure as a [`ParseDiagnostic`], with a hint for the usual slips.
+/// A stray `=` is read as whatever `equals` is in this kind of string: `==`
+/// in a predicate, `<-` in an action.
+fn syntax_error(
+    input: &str,
+    error: peg::error::ParseError<peg::str::LineCol>,
+    equals: &str,
+) -> ParseDiagnostic {
+    let start = error.location.offset.min(input.len());
+    let end = input[start..]
+        .find(char::is_whitespace)
+        .map_or(input.len(), |i| start + i);
+    let token = &input[start..end];
+    let message = if token.is_empty() {
+        format!("unexpected end of input, expected {}", error.expected)
+    } else {
+        format!("unexpected '{token}', expected {}", error.expected)
+    };
+    let suggestions = match token {
+        "=" => vec![equals.to_string()],
+        "<>" => vec!["!=".to_string()],
+        "and" | "AND" | "&" => vec!["&&".to_string()],
+        "or" | "OR" | "|" => vec!["||".to_string()],
+        "not" | "NOT" => vec!["!".to_string()],
+        "=<" => vec!["<=".to_string()],
+        "=>" => vec![">=".to_string()],
+        _ => vec![],
+    };
+    ParseDiagnostic {
+        context: String::new(),
+        source: input.to_string(),
+        span: start..end,
+        token: token.to_string(),
+        message,
+        suggestions,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{Predicate::*, *};
@@ -285,10 +1051,298 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn parse_variables_panic() {
+    fn parse_unknown_variable_is_an_error() {
+        let s = State::from_vec(&john_doe());
+        assert!(pred_parser::variable("var: wealth", &s).is_err());
+    }
+
+    #[test]
+    fn every_unknown_variable_is_reported_with_suggestions() {
+        let s = State::from_vec(&john_doe());
+        let errors = try_parse_pred("var:hieght > 180 && var:alvie == true || var:wealth > 9", &s)
+            .unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].token, "hieght");
+        assert_eq!(errors[0].span, 4..10);
+        assert_eq!(errors[0].suggestions, vec!["height".to_string()]);
+        assert_eq!(errors[1].suggestions, vec!["alive".to_string()]);
+        assert!(errors[2].suggestions.is_empty());
+        assert_eq!(
+            errors[0].to_string(),
+            "column 5: unknown variable 'hieght' (did you mean 'height'?)\n    \
+             var:hieght > 180 && var:alvie == true || var:wealth > 9\n        ^^^^^^"
+        );
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_offending_token() {
         let s = State::from_vec(&john_doe());
-        let _ = pred_parser::variable("var: wealth", &s);
+        let errors = try_parse_pred("var:height = 185", &s).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].token, "=");
+        assert_eq!(errors[0].span, 11..12);
+        assert_eq!(errors[0].suggestions, vec!["==".to_string()]);
+
+        let errors = try_parse_action("var:height <-", &s).unwrap_err();
+        assert!(errors[0].message.starts_with("unexpected end of input"));
+    }
+
+    #[test]
+    fn out_of_range_integers_are_errors_not_words() {
+        let s = State::from_vec(&john_doe());
+        assert!(pred_parser::value("99999999999999999999", &s).is_err());
+        assert!(try_parse_action("var:height <- 99999999999999999999", &s).is_err());
+        assert_eq!(
+            pred_parser::value("12ab", &s),
+            Ok(SPWrapped::SPValue("12ab".to_spvalue()))
+        );
+    }
+
+    #[test]
+    fn mistyped_comparisons_are_rejected() {
+        let s = State::from_vec(&john_doe());
+        assert!(try_parse_pred("var:height > 180 && var:weight <= var:age", &s).is_ok());
+
+        let errors =
+            try_parse_pred("var:name == 5 || var:weight > 80 || var:smart == var:height", &s)
+                .unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors[0].message,
+            "cannot compare string variable 'name' with i64 value 5"
+        );
+        assert_eq!(errors[0].span, 4..8);
+        assert_eq!(errors[0].suggestions, vec!["\"5\"".to_string()]);
+        assert_eq!(errors[1].suggestions, vec!["80.0".to_string()]);
+        assert_eq!(errors[2].token, "smart");
+        assert!(errors[2].suggestions.is_empty());
+    }
+
+    #[test]
+    fn mistyped_actions_are_rejected() {
+        let s = State::from_vec(&john_doe());
+        assert!(try_parse_action("var:weight += var:age", &s).is_ok());
+        assert!(try_parse_action("var:name <- Jane", &s).is_ok());
+
+        let errors = try_parse_action("var:height <- 190.0", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot assign f64 value 190 to i64 variable 'height'"
+        );
+        assert_eq!(errors[0].suggestions, vec!["190".to_string()]);
+
+        let errors = try_parse_action("var:weight -= 1", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot decrement f64 variable 'weight' by i64 value 1"
+        );
+        assert_eq!(errors[0].suggestions, vec!["1.0".to_string()]);
+
+        let errors = try_parse_action("var:name += 1", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot increment string variable 'name', only i64 and f64 variables can be"
+        );
+    }
+
+    #[test]
+    fn parse_arithmetic() {
+        let s = State::from_vec(&john_doe());
+        let height = iv!("height");
+        assert_eq!(
+            pred_parser::expr("var:height * 2 + 1", &s),
+            Ok(SPExpression::Add(
+                SPExpression::Mul(height.wrap(), 2.wrap()).wrap(),
+                1.wrap()
+            )
+            .wrap())
+        );
+        assert_eq!(
+            pred_parser::expr("var:height - (1 - 2)", &s),
+            Ok(SPExpression::Sub(height.wrap(), SPExpression::Sub(1.wrap(), 2.wrap()).wrap()).wrap())
+        );
+        assert_eq!(
+            pred_parser::expr("abs(min(var:height, 3) - max(1, 2))", &s)
+                .unwrap()
+                .evaluate(&s, "t"),
+            1.to_spvalue()
+        );
+
+        let guard = try_parse_pred("var:weight - var:age > 0.5 && var:height / 5 == 37", &s).unwrap();
+        assert!(guard.eval(&s, "t"));
+        let guard = try_parse_pred("(var:weight - var:age) * 2.0 < 100.0", &s).unwrap();
+        assert!(!guard.eval(&s, "t"));
+
+        let action = try_parse_action("var:height <- var:height * 2 - 10", &s).unwrap();
+        assert_eq!(action.assign(&s, "t").get_value("height", "t"), Some(360.to_spvalue()));
+        let action = try_parse_action("var:weight += abs(var:age - 35.0)", &s).unwrap();
+        assert_eq!(action.assign(&s, "t").get_value("weight", "t"), Some(85.0.to_spvalue()));
+    }
+
+    #[test]
+    fn words_and_implications_still_parse_around_arithmetic() {
+        let s = State::from_vec(&john_doe());
+        assert_eq!(
+            pred_parser::expr("max", &s),
+            Ok(SPWrapped::SPValue("max".to_spvalue()))
+        );
+        assert_eq!(
+            pred_parser::expr("pos-a", &s),
+            Ok(SPWrapped::SPValue("pos-a".to_spvalue()))
+        );
+        let guard = try_parse_pred("var:height > 1 -> var:alive == true", &s).unwrap();
+        assert!(guard.eval(&s, "t"));
+    }
+
+    #[test]
+    fn mistyped_arithmetic_is_rejected() {
+        let s = State::from_vec(&john_doe());
+        let errors = try_parse_pred("var:height + var:weight > 2", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot do arithmetic on i64 variable 'height' and f64 variable 'weight'"
+        );
+        let errors = try_parse_action("var:weight <- var:weight * 2", &s).unwrap_err();
+        assert_eq!(errors[0].suggestions, vec!["2.0".to_string()]);
+        let errors = try_parse_action("var:height <- var:name + 1", &s).unwrap_err();
+        assert_eq!(errors[0].message, "cannot do arithmetic on string variable 'name'");
+        let errors = try_parse_pred("var:height * 2 == 1.5", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot compare i64 expression (height * 2) with f64 value 1.5"
+        );
+    }
+
+    #[test]
+    fn parse_membership_and_collections() {
+        let s = State::from_vec(&john_doe()).add(
+            SPAssignment::new(
+                av!("tools"),
+                vec!["gripper".to_spvalue(), "suction".to_spvalue()].to_spvalue(),
+            ),
+            "t",
+        );
+        let s = s.add(
+            SPAssignment::new(
+                mv!("slots"),
+                SPValue::Map(MapOrUnknown::Map(vec![(1.to_spvalue(), "gripper".to_spvalue())])),
+            ),
+            "t",
+        );
+        let name = v!("name");
+        assert_eq!(
+            pred_parser::pred("var:name in [John, Jane]", &s),
+            Ok(IN(
+                name.wrap(),
+                SPWrapped::Array(vec!["John".wrap(), "Jane".wrap()])
+            ))
+        );
+        assert_eq!(
+            pred_parser::pred("var:tools contains gripper", &s),
+            Ok(IN("gripper".wrap(), av!("tools").wrap()))
+        );
+
+        let holds = [
+            "var:name in [John, Jane]",
+            "var:name not in [Jane, Joe]",
+            "var:height in [185, 190] && var:smart == true",
+            "var:tools contains gripper",
+            "suction in var:tools",
+            "screwdriver not in var:tools",
+            "len(var:tools) == 2 && len(var:slots) < 2",
+            "has_key(var:slots, 1) && !has_key(var:slots, 2)",
+            "!(var:name in [Jane])",
+        ];
+        for guard in holds {
+            assert!(try_parse_pred(guard, &s).unwrap().eval(&s, "t"), "{guard}");
+        }
+        assert!(!try_parse_pred("var:name in []", &s).unwrap().eval(&s, "t"));
+        assert_eq!(
+            pred_parser::pred("var:name == inside", &s),
+            Ok(EQ(name.wrap(), "inside".wrap()))
+        );
+
+        let action = try_parse_action("var:height <- len(var:tools) * 10", &s).unwrap();
+        assert_eq!(action.assign(&s, "t").get_value("height", "t"), Some(20.to_spvalue()));
+    }
+
+    #[test]
+    fn mistyped_membership_is_rejected() {
+        let s = State::from_vec(&john_doe());
+        let errors = try_parse_pred("var:height in [185, 190.0]", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot compare i64 variable 'height' with f64 value 190"
+        );
+        let errors = try_parse_pred("var:height in var:name", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot look for i64 variable 'height' in string variable 'name', only in an array"
+        );
+        assert_eq!(errors[0].token, "name");
+        let errors = try_parse_pred("has_key(var:name, a)", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot look up a key in string variable 'name', only in a map"
+        );
+        let errors = try_parse_pred("len(var:name) > 2", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot take the length of string variable 'name', only of an array or a map"
+        );
+    }
+
+    #[test]
+    fn parse_collection_edits() {
+        let s = State::from_vec(&vec![
+            (av!("queue"), SPValue::Array(ArrayOrUnknown::Array(vec![]))),
+            (mv!("stock"), SPValue::Map(MapOrUnknown::Map(vec![]))),
+            (iv!("count"), 2.to_spvalue()),
+        ]);
+        let queue = av!("queue");
+        let stock = mv!("stock");
+        let cases = [
+            ("push(var:queue, part_1)", Action::push(queue.clone(), "part_1".wrap())),
+            ("pop_front( var:queue )", Action::pop_front(queue.clone())),
+            ("remove(var:queue, var:count)", Action::remove(queue.clone(), iv!("count").wrap())),
+            ("clear(var:stock)", Action::clear(stock.clone())),
+            (
+                "map_insert(var:stock, bolt, var:count + 1)",
+                Action::map_insert(
+                    stock.clone(),
+                    "bolt".wrap(),
+                    SPExpression::Add(iv!("count").wrap(), 1.wrap()).wrap(),
+                ),
+            ),
+            ("map_remove(var:stock, bolt)", Action::map_remove(stock.clone(), "bolt".wrap())),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(try_parse_action(input, &s), Ok(expected), "{input}");
+        }
+
+        let errors = try_parse_action("push(var:stock, 1)", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot apply 'push(stock, 1)' to map variable 'stock', only to an array"
+        );
+        let errors = try_parse_action("map_remove(var:count, a)", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot apply 'map_remove(count, a)' to i64 variable 'count', only to a map"
+        );
+    }
+
+    #[test]
+    fn collect_parsed_keeps_every_diagnostic() {
+        let s = State::from_vec(&john_doe());
+        let guards = ["var:height > 1", "var:nam == John", "var:smart == true", "var:age =< 3"];
+        let errors = collect_parsed(guards.iter().map(|g| try_parse_pred(g, &s))).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].suggestions, vec!["name".to_string()]);
+        assert_eq!(errors[1].suggestions, vec!["<=".to_string()]);
+
+        let parsed = collect_parsed(guards[..1].iter().map(|g| try_parse_pred(g, &s)));
+        assert_eq!(parsed.map(|p| p.len()), Ok(1));
     }
 
     #[test]
@@ -389,10 +1443,18 @@ mod tests {
                 var: SPVariable {
                     name: "weight".to_string(),
                     value_type: SPValueType::Float64,
+                    tolerance: None,
+                    domain: None,
+                    metadata: SPVariableMetadata::default(),
+                    ttl_ms: None,
                 },
                 var_or_val: SPVariable {
                     name: "weight_2".to_string(),
                     value_type: SPValueType::Float64,
+                    tolerance: None,
+                    domain: None,
+                    metadata: SPVariableMetadata::default(),
+                    ttl_ms: None,
                 }
                 .wrap(),
                 action_type: ActionType::Assign
@@ -515,4 +1577,168 @@ mod tests {
         assert!(!eq5.eval(&s, "t"));
         assert!(!eq6.eval(&s, "t"));
     }
+
+    fn robot() -> State {
+        State::from_vec(&vec![
+            (
+                mv!("config"),
+                SPValue::Map(MapOrUnknown::Map(vec![("speed".to_spvalue(), 0.5.to_spvalue())])),
+            ),
+            (av!("queue"), vec!["p1".to_spvalue(), "p2".to_spvalue()].to_spvalue()),
+            (tfv!("pose"), SPValueType::Transform.unknown()),
+            (fv!("limit"), 1.0.to_spvalue()),
+        ])
+    }
+
+    #[test]
+    fn parse_paths_in_guards() {
+        let s = robot();
+        let speed = try_parse_pred("var:config.speed < var:limit", &s).unwrap();
+        let head = try_parse_pred("var:queue[0] == p1 && var:queue[ 1 ] == p2", &s).unwrap();
+        let past_end = try_parse_pred("var:queue[2] == p3", &s).unwrap();
+        let z = try_parse_pred("var:pose.translation.z == 0.3", &s).unwrap();
+        assert_eq!(speed.to_string(), "config.speed < limit");
+        assert!(speed.eval(&s, "t"));
+        assert!(head.eval(&s, "t"));
+        assert!(!past_end.eval(&s, "t"));
+        assert!(!z.eval(&s, "t"));
+    }
+
+    #[test]
+    fn parse_path_assignments() {
+        let s = robot();
+        let faster = try_parse_action("var:config.speed <- var:limit * 2.0", &s).unwrap();
+        let swap = try_parse_action("var:queue[1] <- p9", &s).unwrap();
+        let next = swap.assign(&faster.assign(&s, "t"), "t");
+        assert_eq!(
+            next.get_value("config", "t"),
+            Some(SPValue::Map(MapOrUnknown::Map(vec![("speed".to_spvalue(), 2.0.to_spvalue())])))
+        );
+        assert_eq!(
+            next.get_value("queue", "t"),
+            Some(vec!["p1".to_spvalue(), "p9".to_spvalue()].to_spvalue())
+        );
+    }
+
+    #[test]
+    fn paths_are_type_checked() {
+        let s = robot();
+        let errors = try_parse_pred("var:pose.translation.w > 0.3", &s).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].token, "pose");
+        assert!(errors[0].message.starts_with("cannot read pose.translation.w of transform variable 'pose'"));
+
+        let errors = try_parse_pred("var:pose.translation.z > 1", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot compare f64 part 'pose.translation.z' with i64 value 1"
+        );
+        assert_eq!(errors[0].suggestions, vec!["1.0"]);
+
+        let errors = try_parse_action("var:pose.child_frame_id <- 3", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot assign i64 value 3 to string part 'pose.child_frame_id'"
+        );
+        assert!(try_parse_pred("var:limit[0] == 1.0", &s).is_err());
+    }
+
+    #[test]
+    fn parse_approximate_comparisons() {
+        let s = State::from_vec(&vec![
+            (fv!("x").with_tolerance(0.01), 0.253.to_spvalue()),
+            (iv!("n"), 3.to_spvalue()),
+        ]);
+        let explicit = try_parse_pred("var:x ~= 0.25 +- 0.001", &s).unwrap();
+        let declared = try_parse_pred("var:x ~= 0.25 && var:n == 3", &s).unwrap();
+        assert_eq!(
+            explicit,
+            Predicate::APPROX(fv!("x").with_tolerance(0.01).wrap(), 0.25.wrap(), Some(ordered_float::OrderedFloat(0.001)))
+        );
+        assert!(!explicit.eval(&s, "t"));
+        assert!(declared.eval(&s, "t"));
+        assert!(try_parse_pred("var:x + 0.1 ~= 0.35 +- 0.01", &s).unwrap().eval(&s, "t"));
+
+        let errors = try_parse_pred("var:n ~= 3", &s).unwrap_err();
+        assert_eq!(errors[0].message, "cannot compare i64 variable 'n' approximately, only f64 values");
+        assert!(try_parse_pred("var:x ~= 0.25 +- tight", &s).is_err());
+    }
+
+    #[test]
+    fn parse_time_and_durations() {
+        use std::time::{Duration, SystemTime};
+        let s = State::from_vec(&vec![
+            (tv!("last_seen"), (SystemTime::now() - Duration::from_secs(2)).to_spvalue()),
+            (iv!("timeout"), 1_500.to_spvalue()),
+        ]);
+        let duration = |text: &str| pred_parser::expr(text, &s).unwrap().evaluate(&s, "t");
+        assert_eq!(duration("dur:250ms"), 250.to_spvalue());
+        assert_eq!(duration("dur:1.5s"), 1_500.to_spvalue());
+        assert_eq!(duration("dur:2min"), 120_000.to_spvalue());
+
+        assert!(try_parse_pred("age(var:last_seen) > dur:1s", &s).unwrap().eval(&s, "t"));
+        assert!(!try_parse_pred("age(var:last_seen) > dur:5s", &s).unwrap().eval(&s, "t"));
+        assert!(try_parse_pred("age(var:last_seen) > var:timeout", &s).unwrap().eval(&s, "t"));
+        assert!(try_parse_pred("var:last_seen < now()", &s).unwrap().eval(&s, "t"));
+
+        let touch = try_parse_action("var:last_seen <- now()", &s).unwrap();
+        let touched = touch.assign(&s, "t");
+        assert!(try_parse_pred("age(var:last_seen) < dur:1s", &touched).unwrap().eval(&touched, "t"));
+
+        let errors = try_parse_pred("age(var:timeout) > dur:5s", &s).unwrap_err();
+        assert_eq!(errors[0].message, "cannot take the age of i64 variable 'timeout', only of a time");
+        let errors = try_parse_action("var:timeout <- now()", &s).unwrap_err();
+        assert_eq!(errors[0].message, "cannot assign time expression now() to i64 variable 'timeout'");
+    }
+
+    #[test]
+    fn bare_now_and_durations_are_read_against_a_time_or_an_age() {
+        use std::time::{Duration, SystemTime};
+        let s = State::from_vec(&vec![
+            (tv!("last_seen"), (SystemTime::now() - Duration::from_secs(2)).to_spvalue()),
+            (v!("x"), "now".to_spvalue()),
+        ]);
+        let touch = try_parse_action("var:last_seen <- now", &s).unwrap();
+        assert_eq!(touch.var_or_val, SPExpression::Now.wrap());
+        let touched = touch.assign(&s, "t");
+        assert!(try_parse_pred("age(var:last_seen) < 500ms", &touched).unwrap().eval(&touched, "t"));
+        assert!(try_parse_pred("age(var:last_seen) > 1s", &s).unwrap().eval(&s, "t"));
+        assert!(!try_parse_pred("5s < age(var:last_seen)", &s).unwrap().eval(&s, "t"));
+        assert!(try_parse_pred("var:last_seen < now", &s).unwrap().eval(&s, "t"));
+
+        let errors = try_parse_action("var:last_seen <- later", &s).unwrap_err();
+        assert_eq!(errors[0].suggestions, vec!["now()"]);
+        assert!(try_parse_pred("age(var:last_seen) > 5sec", &s).is_err());
+    }
+
+    #[test]
+    fn keywords_without_their_call_or_sigil_are_plain_words() {
+        let s = State::from_vec(&vec![(v!("x"), "now".to_spvalue())]);
+        for word in ["now", "age", "len", "abs", "min", "max", "10s", "250ms", "2min"] {
+            let action = try_parse_action(&format!("var:x <- {word}"), &s).unwrap();
+            assert_eq!(action.var_or_val, word.to_spvalue().wrap(), "'{word}'");
+            assert!(try_parse_pred(&format!("var:x == {word}"), &s).is_ok(), "'{word}'");
+        }
+    }
+
+    #[test]
+    fn literals_outside_a_domain_are_errors() {
+        let s = State::from_vec(&vec![
+            (v!("pos", ["home", "table", "shelf"]), "home".to_spvalue()),
+            (iv!("slot", 1..=8), 1.to_spvalue()),
+        ]);
+        assert!(try_parse_pred("var:pos == table && var:slot != 8", &s).is_ok());
+        assert!(try_parse_action("var:slot <- 3", &s).is_ok());
+
+        let errors = try_parse_pred("var:pos == tabel", &s).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "string value tabel is outside the domain {home, table, shelf} of string variable 'pos'"
+        );
+        assert_eq!(errors[0].suggestions, vec!["table"]);
+        let errors = try_parse_action("var:slot <- 9", &s).unwrap_err();
+        assert_eq!(errors[0].message, "i64 value 9 is outside the domain 1..=8 of i64 variable 'slot'");
+        // Ordering comparisons may reach outside the domain.
+        assert!(try_parse_pred("var:slot < 100", &s).is_ok());
+    }
 }
@@ -5,6 +5,7 @@
 //! a planning goal are made of. Build them by hand, with the `eq!`/`and!`/`or!`
 //! macros, or by parsing a string with [`pred_parser::pred`].
 
+use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
 use crate::*;
@@ -64,6 +65,21 @@ pub enum Predicate {
     LT(SPWrapped, SPWrapped),
     /// Holds when the left term is strictly greater than the right one.
     GT(SPWrapped, SPWrapped),
+    /// Holds when the left term is an element of the right one, an array.
+    /// Never holds for an `UNKNOWN` array.
+    IN(SPWrapped, SPWrapped),
+    /// Holds exactly when the matching [`Predicate::IN`] does not.
+    NOTIN(SPWrapped, SPWrapped),
+    /// Holds when the left term, a map, has the right term as a key. Never
+    /// holds for an `UNKNOWN` map.
+    HASKEY(SPWrapped, SPWrapped),
+    /// Holds when the two terms, both `f64`, are at most a tolerance apart.
+    /// The tolerance is the one given here, or else the
+    /// [`SPVariable::tolerance`] of the first variable in either term that
+    /// has one, in the evaluated state or as written here; with neither, the
+    /// comparison is exact. Terms that are not both known `f64` values must be
+    /// equal. Written `var:x ~= 0.25 +- 0.01`, or `var:x ~= 0.25`.
+    APPROX(SPWrapped, SPWrapped, Option<OrderedFloat<f64>>),
 }
 
 impl Predicate {
@@ -88,6 +104,109 @@ impl Predicate {
             Predicate::GTEQ(x, y) => x.evaluate(&state, log_target) >= y.evaluate(&state, log_target),
             Predicate::LT(x, y) => x.evaluate(&state, log_target) < y.evaluate(&state, log_target),
             Predicate::GT(x, y) => x.evaluate(&state, log_target) > y.evaluate(&state, log_target),
+            Predicate::IN(x, y)
+            | Predicate::NOTIN(x, y)
+            | Predicate::HASKEY(x, y)
+            | Predicate::APPROX(x, y, _) => self.compare(
+                &x.evaluate(state, log_target),
+                &y.evaluate(state, log_target),
+                state,
+            ),
+        }
+    }
+
+    /// Whether this comparison holds between the already evaluated `lhs` and
+    /// `rhs`. Only meaningful for the two-term variants; `state` supplies the
+    /// declared tolerances for [`Predicate::APPROX`].
+    fn compare(&self, lhs: &SPValue, rhs: &SPValue, state: &State) -> bool {
+        let is_element = || match rhs {
+            SPValue::Array(ArrayOrUnknown::Array(items)) => items.contains(lhs),
+            _ => false,
+        };
+        match self {
+            Predicate::EQ(..) => lhs == rhs,
+            Predicate::NEQ(..) => lhs != rhs,
+            Predicate::LTEQ(..) => lhs <= rhs,
+            Predicate::GTEQ(..) => lhs >= rhs,
+            Predicate::LT(..) => lhs < rhs,
+            Predicate::GT(..) => lhs > rhs,
+            Predicate::IN(..) => is_element(),
+            Predicate::NOTIN(..) => !is_element(),
+            Predicate::HASKEY(..) => match lhs {
+                SPValue::Map(MapOrUnknown::Map(pairs)) => pairs.iter().any(|(key, _)| key == rhs),
+                _ => false,
+            },
+            Predicate::APPROX(..) => match (lhs, rhs) {
+                (
+                    SPValue::Float64(FloatOrUnknown::Float64(a)),
+                    SPValue::Float64(FloatOrUnknown::Float64(b)),
+                ) => (a.into_inner() - b.into_inner()).abs() <= self.tolerance(state),
+                _ => lhs == rhs,
+            },
+            _ => false,
+        }
+    }
+
+    /// The tolerance a [`Predicate::APPROX`] compares with in `state`: its own,
+    /// or the first one declared on a variable it mentions, or zero. A
+    /// declaration in `state` wins over the one the predicate was built with,
+    /// which still counts for states read back from Redis, where variables
+    /// carry only their name and type.
+    fn tolerance(&self, state: &State) -> f64 {
+        let Predicate::APPROX(x, y, own) = self else {
+            return 0.0;
+        };
+        let declared = || {
+            x.get_variables()
+                .into_iter()
+                .chain(y.get_variables())
+                .find_map(|var| {
+                    let in_state = state.state.get(&var.name).and_then(|a| a.var.tolerance);
+                    in_state.or(var.tolerance)
+                })
+        };
+        own.or_else(declared).map_or(0.0, |t| t.into_inner())
+    }
+
+    /// The `variable == value` comparisons this predicate cannot do without
+    /// whose value is outside the variable's [`SPVariable::domain`]. While
+    /// there is one the predicate can never hold. Required comparisons are
+    /// those at the top level or under `AND`s, and an `OR` only when every
+    /// alternative has such a comparison. The domain is the one declared in
+    /// `state`, or else the one the predicate's variable was built with.
+    ///
+    /// ```
+    /// use micro_sp::*;
+    ///
+    /// let state = State::from_vec(&vec![(v!("pos", ["a", "b"]), "a".to_spvalue())]);
+    /// let goal = pred_parser::pred("var:pos == c", &state).unwrap();
+    /// assert_eq!(goal.domain_violations(&state), vec![(v!("pos", ["a", "b"]), "c".to_spvalue())]);
+    /// ```
+    pub fn domain_violations(&self, state: &State) -> Vec<(SPVariable, SPValue)> {
+        match self {
+            Predicate::AND(children) => children
+                .iter()
+                .flat_map(|child| child.domain_violations(state))
+                .collect(),
+            Predicate::OR(children) => {
+                let violations: Vec<Vec<(SPVariable, SPValue)>> = children
+                    .iter()
+                    .map(|child| child.domain_violations(state))
+                    .collect();
+                match !violations.is_empty() && violations.iter().all(|v| !v.is_empty()) {
+                    true => violations.concat(),
+                    false => vec![],
+                }
+            }
+            Predicate::EQ(SPWrapped::SPVariable(var), SPWrapped::SPValue(value))
+            | Predicate::EQ(SPWrapped::SPValue(value), SPWrapped::SPVariable(var)) => {
+                let in_state = state.state.get(&var.name).and_then(|a| a.var.domain.as_ref());
+                match in_state.or(var.domain.as_ref()) {
+                    Some(domain) if !domain.contains(value) => vec![(var.clone(), value.clone())],
+                    _ => vec![],
+                }
+            }
+            _ => vec![],
         }
     }
 
@@ -125,7 +244,11 @@ impl Predicate {
             | Predicate::LTEQ(x, y)
             | Predicate::GTEQ(x, y)
             | Predicate::LT(x, y)
-            | Predicate::GT(x, y) => {
+            | Predicate::GT(x, y)
+            | Predicate::IN(x, y)
+            | Predicate::NOTIN(x, y)
+            | Predicate::HASKEY(x, y)
+            | Predicate::APPROX(x, y, _) => {
                 let remove_x = x.get_variables().iter().any(|v| !only.contains(&v.name));
                 let remove_y = y.get_variables().iter().any(|v| !only.contains(&v.name));
 
@@ -171,7 +294,11 @@ impl Predicate {
             | Predicate::LTEQ(x, y)
             | Predicate::GTEQ(x, y)
             | Predicate::LT(x, y)
-            | Predicate::GT(x, y) => {
+            | Predicate::GT(x, y)
+            | Predicate::IN(x, y)
+            | Predicate::NOTIN(x, y)
+            | Predicate::HASKEY(x, y)
+            | Predicate::APPROX(x, y, _) => {
                 let remove_x = x.get_variables().iter().any(|v| remove.contains(&v.name));
                 let remove_y = y.get_variables().iter().any(|v| remove.contains(&v.name));
 
@@ -184,6 +311,72 @@ impl Predicate {
         }
     }
 
+    /// Evaluate this predicate against `state` and keep the whole evaluation:
+    /// every sub-predicate with its truth value and, for comparisons, the two
+    /// values that were compared.
+    ///
+    /// Unlike [`Predicate::eval`] it does not short-circuit, so every branch
+    /// is explained. Use [`PredicateExplanation::failing_leaves`] to find out
+    /// what keeps a predicate from holding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the predicate mentions a variable that is not in `state`.
+    ///
+    /// ```
+    /// use micro_sp::*;
+    ///
+    /// let ready = SPVariable::new("ready", SPValueType::Bool);
+    /// let state = State::from_vec(&vec![(ready.clone(), false.to_spvalue())]);
+    /// let guard = Predicate::AND(vec![Predicate::TRUE, Predicate::EQ(ready.wrap(), true.wrap())]);
+    ///
+    /// let explanation = guard.explain(&state, "docs");
+    /// assert!(!explanation.holds);
+    /// let blocking: Vec<String> = explanation.failing_leaves().iter().map(|l| l.to_string()).collect();
+    /// assert_eq!(blocking, vec!["ready = true (ready is false)"]);
+    /// ```
+    pub fn explain(&self, state: &State, log_target: &str) -> PredicateExplanation {
+        let node = |holds: bool, compared: Option<(SPValue, SPValue)>, children| {
+            PredicateExplanation {
+                predicate: self.clone(),
+                holds,
+                compared,
+                children,
+            }
+        };
+        match self {
+            Predicate::TRUE => node(true, None, vec![]),
+            Predicate::FALSE => node(false, None, vec![]),
+            Predicate::NOT(p) => {
+                let child = p.explain(state, log_target);
+                node(!child.holds, None, vec![child])
+            }
+            Predicate::AND(p) | Predicate::OR(p) => {
+                let children: Vec<PredicateExplanation> =
+                    p.iter().map(|pp| pp.explain(state, log_target)).collect();
+                let holds = match self {
+                    Predicate::AND(_) => children.iter().all(|c| c.holds),
+                    _ => children.iter().any(|c| c.holds),
+                };
+                node(holds, None, children)
+            }
+            Predicate::EQ(x, y)
+            | Predicate::NEQ(x, y)
+            | Predicate::LTEQ(x, y)
+            | Predicate::GTEQ(x, y)
+            | Predicate::LT(x, y)
+            | Predicate::GT(x, y)
+            | Predicate::IN(x, y)
+            | Predicate::NOTIN(x, y)
+            | Predicate::HASKEY(x, y)
+            | Predicate::APPROX(x, y, _) => {
+                let (lhs, rhs) = (x.evaluate(state, log_target), y.evaluate(state, log_target));
+                let holds = self.compare(&lhs, &rhs, state);
+                node(holds, Some((lhs, rhs)), vec![])
+            }
+        }
+    }
+
     /// Every variable this predicate mentions, sorted and deduplicated.
     pub fn get_predicate_vars(&self) -> Vec<SPVariable> {
         let mut vars = match self {
@@ -196,7 +389,11 @@ impl Predicate {
             | Predicate::LTEQ(lhs, rhs)
             | Predicate::GTEQ(lhs, rhs)
             | Predicate::LT(lhs, rhs)
-            | Predicate::GT(lhs, rhs) => {
+            | Predicate::GT(lhs, rhs)
+            | Predicate::IN(lhs, rhs)
+            | Predicate::NOTIN(lhs, rhs)
+            | Predicate::HASKEY(lhs, rhs)
+            | Predicate::APPROX(lhs, rhs, _) => {
                 let mut found = lhs.get_variables();
                 found.extend(rhs.get_variables());
                 found
@@ -241,12 +438,85 @@ impl fmt::Display for Predicate {
             Predicate::GTEQ(x, y) => format!("{} >= {}", x, y),
             Predicate::LT(x, y) => format!("{} < {}", x, y),
             Predicate::GT(x, y) => format!("{} > {}", x, y),
+            Predicate::IN(x, y) => format!("{} in {}", x, y),
+            Predicate::NOTIN(x, y) => format!("{} not in {}", x, y),
+            Predicate::HASKEY(x, y) => format!("has_key({}, {})", x, y),
+            Predicate::APPROX(x, y, None) => format!("{} ~= {}", x, y),
+            Predicate::APPROX(x, y, Some(t)) => format!("{} ~= {} +- {}", x, y, t),
         };
 
         write!(fmtr, "{}", &s)
     }
 }
 
+/// The result of [`Predicate::explain`]: one node per sub-predicate.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PredicateExplanation {
+    /// The sub-predicate this node explains.
+    pub predicate: Predicate,
+    /// Whether it holds in the explained state.
+    pub holds: bool,
+    /// For a comparison, the left and right values it compared.
+    pub compared: Option<(SPValue, SPValue)>,
+    /// One explanation per operand of a `NOT`, `AND` or `OR`.
+    pub children: Vec<PredicateExplanation>,
+}
+
+impl PredicateExplanation {
+    /// The smallest parts that keep the predicate from holding: the false
+    /// comparisons and constants under a false `AND` or `OR`. A false `NOT` is
+    /// reported whole, since what blocks it is something that *does* hold, and
+    /// so is an empty `OR`. Empty exactly when the predicate holds.
+    pub fn failing_leaves(&self) -> Vec<&PredicateExplanation> {
+        if self.holds {
+            return vec![];
+        }
+        let leaves: Vec<&PredicateExplanation> = match self.predicate {
+            Predicate::AND(_) | Predicate::OR(_) => self
+                .children
+                .iter()
+                .flat_map(|child| child.failing_leaves())
+                .collect(),
+            _ => vec![],
+        };
+        match leaves.is_empty() {
+            true => vec![self],
+            false => leaves,
+        }
+    }
+}
+
+impl fmt::Display for PredicateExplanation {
+    /// Renders the sub-predicate followed by the values of the variables it
+    /// compared, e.g. `ready = true (ready is false)`.
+    fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sides = match (&self.predicate, &self.compared) {
+            (
+                Predicate::EQ(x, y)
+                | Predicate::NEQ(x, y)
+                | Predicate::LTEQ(x, y)
+                | Predicate::GTEQ(x, y)
+                | Predicate::LT(x, y)
+                | Predicate::GT(x, y)
+                | Predicate::IN(x, y)
+                | Predicate::NOTIN(x, y)
+                | Predicate::HASKEY(x, y)
+                | Predicate::APPROX(x, y, _),
+                Some((lhs, rhs)),
+            ) => [(x, lhs), (y, rhs)]
+                .iter()
+                .filter(|(side, _)| !matches!(side, SPWrapped::SPValue(_)))
+                .map(|(side, value)| format!("{} is {}", side, value))
+                .collect::<Vec<String>>(),
+            _ => vec![],
+        };
+        match sides.is_empty() {
+            true => write!(fmtr, "{}", self.predicate),
+            false => write!(fmtr, "{} ({})", self.predicate, sides.join(", ")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -913,3 +1183,253 @@ mod projection_tests {
         );
     }
 }
+
+/// [`Predicate::explain`] has to agree with [`Predicate::eval`] everywhere, and
+/// its failing leaves have to be exactly the parts worth showing an operator.
+#[cfg(test)]
+mod explain_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (SPVariable::new("a", SPValueType::Bool), true.to_spvalue()),
+            (SPVariable::new("b", SPValueType::Bool), false.to_spvalue()),
+            (SPVariable::new("n", SPValueType::Int64), 5.to_spvalue()),
+        ])
+    }
+
+    fn var(name: &str) -> SPWrapped {
+        state().get_assignment(name, TARGET).var.wrap()
+    }
+
+    fn failing(predicate: &Predicate) -> Vec<String> {
+        predicate
+            .explain(&state(), TARGET)
+            .failing_leaves()
+            .iter()
+            .map(|leaf| leaf.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn explain_agrees_with_eval() {
+        let predicates = [
+            Predicate::TRUE,
+            Predicate::FALSE,
+            Predicate::EQ(var("a"), true.wrap()),
+            Predicate::NEQ(var("a"), var("b")),
+            Predicate::LT(var("n"), 5.wrap()),
+            Predicate::GTEQ(var("n"), 5.wrap()),
+            Predicate::NOT(Box::new(Predicate::EQ(var("b"), false.wrap()))),
+            Predicate::AND(vec![]),
+            Predicate::OR(vec![]),
+            Predicate::OR(vec![
+                Predicate::EQ(var("b"), true.wrap()),
+                Predicate::AND(vec![Predicate::TRUE, Predicate::GT(var("n"), 4.wrap())]),
+            ]),
+        ];
+        for predicate in predicates {
+            let explanation = predicate.explain(&state(), TARGET);
+            assert_eq!(explanation.holds, predicate.eval(&state(), TARGET), "{predicate}");
+            assert_eq!(explanation.failing_leaves().is_empty(), explanation.holds, "{predicate}");
+        }
+    }
+
+    #[test]
+    fn comparisons_keep_the_values_they_compared() {
+        let explanation = Predicate::LT(var("n"), 3.wrap()).explain(&state(), TARGET);
+        assert_eq!(explanation.compared, Some((5.to_spvalue(), 3.to_spvalue())));
+        assert_eq!(explanation.to_string(), "n < 3 (n is 5)");
+    }
+
+    #[test]
+    fn failing_leaves_skip_what_holds_and_keep_not_whole() {
+        let guard = Predicate::AND(vec![
+            Predicate::EQ(var("a"), true.wrap()),
+            Predicate::EQ(var("b"), var("a")),
+            Predicate::OR(vec![Predicate::FALSE, Predicate::GT(var("n"), 9.wrap())]),
+            Predicate::NOT(Box::new(Predicate::EQ(var("b"), false.wrap()))),
+        ]);
+
+        assert_eq!(
+            failing(&guard),
+            vec![
+                "b = a (b is false, a is true)",
+                "FALSE",
+                "n > 9 (n is 5)",
+                "!(b = false)",
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod collection_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (v!("pos"), "b".to_spvalue()),
+            (
+                av!("visited"),
+                vec!["a".to_spvalue(), "b".to_spvalue()].to_spvalue(),
+            ),
+            (av!("unvisited"), SPValue::Array(ArrayOrUnknown::UNKNOWN)),
+            (
+                mv!("stock"),
+                SPValue::Map(MapOrUnknown::Map(vec![("bolt".to_spvalue(), 3.to_spvalue())])),
+            ),
+        ])
+    }
+
+    #[test]
+    fn membership_and_key_lookup() {
+        let s = state();
+        let pos = v!("pos").wrap();
+        assert!(Predicate::IN(pos.clone(), av!("visited").wrap()).eval(&s, TARGET));
+        assert!(!Predicate::NOTIN(pos.clone(), av!("visited").wrap()).eval(&s, TARGET));
+        assert!(Predicate::IN(
+            pos.clone(),
+            SPWrapped::Array(vec!["c".wrap(), v!("pos").wrap()])
+        )
+        .eval(&s, TARGET));
+        assert!(Predicate::HASKEY(mv!("stock").wrap(), "bolt".wrap()).eval(&s, TARGET));
+        assert!(!Predicate::HASKEY(mv!("stock").wrap(), "nut".wrap()).eval(&s, TARGET));
+    }
+
+    #[test]
+    fn unknown_collections_contain_nothing() {
+        let s = state();
+        let unvisited = av!("unvisited").wrap();
+        assert!(!Predicate::IN("a".wrap(), unvisited.clone()).eval(&s, TARGET));
+        assert!(Predicate::NOTIN("a".wrap(), unvisited.clone()).eval(&s, TARGET));
+        assert!(!Predicate::HASKEY(unvisited, "a".wrap()).eval(&s, TARGET));
+    }
+
+    #[test]
+    fn collection_predicates_report_their_variables_and_explain_themselves() {
+        let s = state();
+        let guard = Predicate::NOTIN(v!("pos").wrap(), av!("visited").wrap());
+        assert_eq!(guard.get_predicate_var_keys(), vec!["pos", "visited"]);
+        assert_eq!(guard.keep_only(&vec!["pos".to_string()]), None);
+
+        let explanation = guard.explain(&s, TARGET);
+        assert!(!explanation.holds);
+        assert_eq!(
+            explanation.failing_leaves()[0].to_string(),
+            "pos not in visited (pos is b, visited is [a, b])"
+        );
+    }
+
+    #[test]
+    fn the_planner_understands_membership_goals() {
+        let pos = v!("pos");
+        let state = State::from_vec(&vec![(pos.clone(), "a".to_spvalue())]);
+        let step = |from: &str, to: &str| {
+            t_plan!(
+                &format!("{from}_to_{to}"),
+                eq!(pos.wrap(), from.wrap()),
+                vec!(a!(pos.clone(), to.wrap()))
+            )
+        };
+        let goal = Predicate::IN(
+            pos.wrap(),
+            SPWrapped::Array(vec!["c".wrap(), "d".wrap()]),
+        );
+        let result = bfs_transition_planner(
+            state,
+            goal,
+            vec![step("a", "b"), step("b", "c"), step("c", "d")],
+            10,
+            TARGET,
+        );
+        assert!(result.found);
+        assert_eq!(result.plan, vec!["a_to_b", "b_to_c"]);
+    }
+}
+
+#[cfg(test)]
+mod approx_tests {
+    use crate::*;
+    use ordered_float::OrderedFloat;
+
+    const TARGET: &str = "test";
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (fv!("x"), 0.253.to_spvalue()),
+            (fv!("y").with_tolerance(0.01), 0.253.to_spvalue()),
+            (iv!("n"), 3.to_spvalue()),
+        ])
+    }
+
+    #[test]
+    fn an_explicit_tolerance_wins() {
+        let s = state();
+        let near = |var: SPVariable, t: f64| {
+            Predicate::APPROX(var.wrap(), 0.25.wrap(), Some(OrderedFloat(t))).eval(&s, TARGET)
+        };
+        assert!(near(fv!("x"), 0.005));
+        assert!(!near(fv!("x"), 0.001));
+        assert!(!near(fv!("y"), 0.001));
+    }
+
+    #[test]
+    fn the_declared_tolerance_is_the_default() {
+        let s = state();
+        // The declaration in the state counts even if the predicate's variable
+        // lacks it, and the predicate's counts in a state read from Redis.
+        let from_redis = State::from_vec(&vec![(fv!("y"), 0.253.to_spvalue())]);
+        let written = fv!("y").with_tolerance(0.01).wrap();
+        assert!(Predicate::APPROX(written, 0.25.wrap(), None).eval(&from_redis, TARGET));
+        assert!(Predicate::APPROX(fv!("y").wrap(), 0.25.wrap(), None).eval(&s, TARGET));
+        assert!(Predicate::APPROX(0.26.wrap(), fv!("y").wrap(), None).eval(&s, TARGET));
+        assert!(!Predicate::APPROX(fv!("x").wrap(), 0.25.wrap(), None).eval(&s, TARGET));
+        assert!(Predicate::APPROX(fv!("x").wrap(), 0.253.wrap(), None).eval(&s, TARGET));
+    }
+
+    #[test]
+    fn other_values_must_be_equal() {
+        let s = state();
+        let unknown = SPValue::Float64(FloatOrUnknown::UNKNOWN).wrap();
+        assert!(Predicate::APPROX(iv!("n").wrap(), 3.wrap(), Some(OrderedFloat(1.0))).eval(&s, TARGET));
+        assert!(!Predicate::APPROX(iv!("n").wrap(), 4.wrap(), Some(OrderedFloat(1.0))).eval(&s, TARGET));
+        assert!(!Predicate::APPROX(unknown, 0.25.wrap(), Some(OrderedFloat(1.0))).eval(&s, TARGET));
+    }
+
+    #[test]
+    fn explain_and_display_show_the_tolerance() {
+        let s = state();
+        let near = Predicate::APPROX(fv!("x").wrap(), 0.25.wrap(), Some(OrderedFloat(0.001)));
+        assert_eq!(near.to_string(), "x ~= 0.25 +- 0.001");
+        assert_eq!(
+            near.explain(&s, TARGET).to_string(),
+            "x ~= 0.25 +- 0.001 (x is 0.253)"
+        );
+        assert!(!near.explain(&s, TARGET).holds);
+    }
+
+    /// Planning evaluates guards and goals with the same tolerance as the
+    /// runtime does, so a goal that is only ever approximately reached is
+    /// still found.
+    #[test]
+    fn the_planner_reaches_an_approximate_goal() {
+        let s = State::from_vec(&vec![(fv!("z").with_tolerance(0.05), 0.0.to_spvalue())]);
+        let transitions = vec![Transition::parse(
+            "step_up",
+            "var:z < 0.3",
+            "true",
+            vec!["var:z += 0.33"],
+            Vec::<&str>::new(),
+            &s,
+        )];
+        let goal = pred_parser::pred("var:z ~= 0.3", &s).unwrap();
+        let result = bfs_transition_planner(s, goal, transitions, 5, TARGET);
+        assert!(result.found);
+        assert_eq!(result.plan, vec!["step_up"]);
+    }
+}
@@ -0,0 +1,215 @@
+//! [`RetryPolicy`]s: how an operation goes about its retries.
+//!
+//! [`Operation::failure_retries`] and [`Operation::timeout_retries`] say how
+//! many retries there are; without a policy each one is taken on the very
+//! next tick with nothing done in between. A policy can hold the operation in
+//! `failed` or `timedout` for a while first, growing the wait with every
+//! attempt, retry only for some causes, and take recovery transitions before
+//! the operation goes back to `initial`. It publishes three variables:
+//!
+//! * `{name}_retry_attempt` - which attempt the operation is on, `1` before
+//!   any retry. Guards can read it, say to approach more slowly the second
+//!   time.
+//! * `{name}_retry_failure_cause` - the failure transition that failed the
+//!   last attempt, or `timeout`.
+//! * `{name}_retry_wait_ms` - how much of the wait before the next attempt
+//!   is left, `UNKNOWN` when not waiting.
+//!
+//! The variables are named after the policy, not after the operation, so that
+//! they keep their names when a plan or SOP uniquifies the operation.
+//! [`generate_operation_state_variables`] seeds them, from
+//! [`RetryPolicy::state_variables`], for every policy in the model.
+//!
+//! ```
+//! use micro_sp::*;
+//!
+//! let state = State::from_vec(&vec![(v!("gripper_error"), "slipped".to_spvalue())]);
+//! let policy = RetryPolicy::new("grip")
+//!     .with_delay_ms(500)
+//!     .with_backoff(2, Some(4000))
+//!     .retrying_if(pred_parser::pred("var:gripper_error == slipped", &state).unwrap());
+//!
+//! assert_eq!(policy.delay_ms_before(1), 500);
+//! assert_eq!(policy.delay_ms_before(3), 2000);
+//! assert_eq!(policy.delay_ms_before(5), 4000);
+//! assert!(policy.allows_retry(&state, "docs"));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// How one operation retries; see the [module documentation](self).
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Prefixes the policy's variables; usually the operation's name.
+    pub name: String,
+    /// How long to wait before the first retry, in milliseconds.
+    pub delay_ms: i64,
+    /// What the wait is multiplied by for every further retry. `1` keeps it
+    /// constant.
+    pub backoff_multiplier: i64,
+    /// The longest the wait may grow to, in milliseconds.
+    pub max_delay_ms: Option<i64>,
+    /// Retries are only taken while this holds; when it does not the
+    /// operation is bypassed or goes fatal as if it were out of retries.
+    pub retry_if: Predicate,
+    /// Taken once per retry, when the wait begins; the first one whose guard
+    /// holds is taken, as for the operation's own transitions.
+    pub recovery_transitions: Vec<Transition>,
+}
+
+impl RetryPolicy {
+    /// A policy that retries on the next tick for any cause, which is what an
+    /// operation without a policy does, but with the attempt published.
+    pub fn new(name: &str) -> RetryPolicy {
+        RetryPolicy {
+            name: name.to_string(),
+            delay_ms: 0,
+            backoff_multiplier: 1,
+            max_delay_ms: None,
+            retry_if: Predicate::TRUE,
+            recovery_transitions: vec![],
+        }
+    }
+
+    /// The same policy, waiting `delay_ms` before the first retry.
+    pub fn with_delay_ms(mut self, delay_ms: i64) -> RetryPolicy {
+        self.delay_ms = delay_ms;
+        self
+    }
+
+    /// The same policy, multiplying the wait by `multiplier` for every further
+    /// retry, up to `max_delay_ms`.
+    pub fn with_backoff(mut self, multiplier: i64, max_delay_ms: Option<i64>) -> RetryPolicy {
+        self.backoff_multiplier = multiplier;
+        self.max_delay_ms = max_delay_ms;
+        self
+    }
+
+    /// The same policy, retrying only while `predicate` holds.
+    pub fn retrying_if(mut self, predicate: Predicate) -> RetryPolicy {
+        self.retry_if = predicate;
+        self
+    }
+
+    /// The same policy, taking one of `transitions` before every retry.
+    pub fn recovering_with(mut self, transitions: Vec<Transition>) -> RetryPolicy {
+        self.recovery_transitions = transitions;
+        self
+    }
+
+    /// `{name}_retry_attempt`.
+    pub fn attempt(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_retry_attempt", self.name), SPValueType::Int64)
+    }
+
+    /// `{name}_retry_failure_cause`.
+    pub fn failure_cause(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_retry_failure_cause", self.name), SPValueType::String)
+    }
+
+    /// `{name}_retry_wait_ms`.
+    pub fn wait(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_retry_wait_ms", self.name), SPValueType::Int64)
+    }
+
+    /// The initial state of the policy: on the first attempt, with no failure
+    /// and no wait.
+    pub fn state_variables(&self) -> State {
+        State::from_vec(&vec![
+            (self.attempt(), 1.to_spvalue()),
+            (self.failure_cause(), SPValue::String(StringOrUnknown::UNKNOWN)),
+            (self.wait(), SPValue::Int64(IntOrUnknown::UNKNOWN)),
+        ])
+    }
+
+    /// How long to wait before retry number `retry`, counting from `1`.
+    pub fn delay_ms_before(&self, retry: i64) -> i64 {
+        let mut delay = self.delay_ms;
+        for _ in 1..retry {
+            delay = delay.saturating_mul(self.backoff_multiplier);
+            if self.max_delay_ms.is_some_and(|max| delay >= max) {
+                break;
+            }
+        }
+        match self.max_delay_ms {
+            Some(max) => delay.min(max),
+            None => delay,
+        }
+    }
+
+    /// Whether [`RetryPolicy::retry_if`] lets the operation retry.
+    pub fn allows_retry(&self, state: &State, log_target: &str) -> bool {
+        self.retry_if.eval(state, log_target)
+    }
+
+    /// `state` after the first recovery transition that can be taken, if any.
+    pub fn recover(&self, state: &State, log_target: &str) -> State {
+        match self
+            .recovery_transitions
+            .iter()
+            .find(|transition| transition.eval(state, log_target))
+        {
+            Some(transition) => {
+                let mut new_state = state.clone();
+                transition.take_mut(&mut new_state, log_target);
+                new_state
+            }
+            None => state.clone(),
+        }
+    }
+
+    /// The variables the policy itself publishes, and those its predicate
+    /// and recovery transitions read or write.
+    pub fn get_all_var_keys(&self) -> Vec<String> {
+        let mut keys = vec![self.attempt().name, self.failure_cause().name, self.wait().name];
+        keys.extend(self.retry_if.get_predicate_vars().into_iter().map(|var| var.name));
+        for transition in &self.recovery_transitions {
+            keys.extend(transition.get_all_var_keys());
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod retry_policy_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    #[test]
+    fn the_wait_grows_with_every_retry_up_to_the_limit() {
+        let constant = RetryPolicy::new("grip").with_delay_ms(300);
+        assert_eq!(constant.delay_ms_before(1), 300);
+        assert_eq!(constant.delay_ms_before(4), 300);
+
+        let doubling = RetryPolicy::new("grip").with_delay_ms(100).with_backoff(2, Some(1000));
+        let delays: Vec<i64> = (1..=6).map(|retry| doubling.delay_ms_before(retry)).collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+
+        let unbounded = RetryPolicy::new("grip").with_delay_ms(1).with_backoff(10, None);
+        assert_eq!(unbounded.delay_ms_before(100), i64::MAX);
+    }
+
+    #[test]
+    fn the_first_recovery_that_can_be_taken_is() {
+        let state = State::from_vec(&vec![
+            (v!("gripper"), "closed".to_spvalue()),
+            (bv!("homed"), false.to_spvalue()),
+        ]);
+        let recovery = |name: &str, guard: &str, action: &str| {
+            Transition::parse(name, guard, "true", vec![action], Vec::<&str>::new(), &state)
+        };
+        let policy = RetryPolicy::new("grip").recovering_with(vec![
+            recovery("home", "var:homed == true", "var:gripper <- homing"),
+            recovery("open", "var:gripper == closed", "var:gripper <- open"),
+            recovery("close", "var:gripper == open", "var:gripper <- closed"),
+        ]);
+
+        let recovered = policy.recover(&state, TARGET);
+        assert_eq!(recovered.get_value("gripper", TARGET), Some("open".to_spvalue()));
+        assert_eq!(policy.state_variables().get_value("grip_retry_attempt", TARGET), Some(1.to_spvalue()));
+        assert!(policy.get_all_var_keys().contains(&"homed".to_string()));
+    }
+}
@@ -0,0 +1,389 @@
+//! [`ServiceSpec`]s: the model side of a service, generated from one declaration.
+//!
+//! Calling a service from a model takes the handshake keys
+//! `{name}_request_trigger` and `{name}_request_state`, the request and response
+//! variables, and one [`Operation`] per command whose transitions raise the
+//! trigger, wait for the answer in their runner guards and reset the handshake
+//! afterwards. A [`ServiceSpec`] declares the variables once and generates all
+//! of that; each [`ServiceCommand`] only says what it asks for and what it
+//! achieves.
+//!
+//! Every generated operation follows the same shape:
+//!
+//! * `start_{command}` - guard: the command's own guard, the request state is
+//!   `initial` and the trigger is down. Actions: the command's request
+//!   assignments, then the trigger goes up.
+//! * `complete_{command}` - runner guard: the request state is `succeeded`.
+//!   Actions: the handshake is reset and the command's effects are applied.
+//! * `fail_{command}` - runner guard: the request state is `failed`. Actions:
+//!   the handshake is reset, so a retry can go again.
+//! * `timeout_{command}` - the handshake is reset.
+//!
+//! The driver side is a [`ServiceServer`], which [`ServiceSpec::to_server`]
+//! builds from the same declaration.
+//!
+//! ```
+//! use micro_sp::*;
+//!
+//! let command = SPVariable::new("gantry_command_command", SPValueType::String);
+//! let locked = SPVariable::new("gantry_locked_estimated", SPValueType::Bool);
+//! let gantry = ServiceSpec::new(
+//!     "gantry",
+//!     vec![command.clone()],
+//!     vec![locked.clone()],
+//!     vec![ServiceCommand::new(
+//!         "gantry_unlock",
+//!         Predicate::TRUE,
+//!         vec![Action::new(command, "unlock".to_spvalue().wrap())],
+//!         vec![Action::new(locked, false.to_spvalue().wrap())],
+//!     )],
+//!     Some(10_000),
+//! );
+//!
+//! let operations = gantry.to_operations();
+//! let state = gantry.state_variables();
+//! assert_eq!(operations[0].preconditions[0].name, "start_gantry_unlock");
+//! assert!(state.contains("gantry_request_trigger"));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// One thing a service can be asked to do, and what it achieves when it does.
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
+pub struct ServiceCommand {
+    /// Name of the generated operation.
+    pub name: String,
+    /// When the command may be requested, on top of the service being idle.
+    pub guard: Predicate,
+    /// Assignments to the request variables that make up the request.
+    pub request: Vec<Action>,
+    /// What a successful call achieves, for the planner and for the state.
+    pub effects: Vec<Action>,
+}
+
+impl ServiceCommand {
+    /// Define a service command.
+    pub fn new(
+        name: &str,
+        guard: Predicate,
+        request: Vec<Action>,
+        effects: Vec<Action>,
+    ) -> ServiceCommand {
+        ServiceCommand {
+            name: name.to_string(),
+            guard,
+            request,
+            effects,
+        }
+    }
+}
+
+/// A service as the model sees it: its handshake, its typed request and
+/// response variables, and the commands it offers.
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
+pub struct ServiceSpec {
+    /// The service name, which prefixes the handshake keys.
+    pub name: String,
+    /// Variables a request is made of.
+    pub request: Vec<SPVariable>,
+    /// Variables the service answers in.
+    pub response: Vec<SPVariable>,
+    /// The commands, one generated operation each.
+    pub commands: Vec<ServiceCommand>,
+    /// Execution timeout of every generated operation, in milliseconds. `None`
+    /// means [`MAX_ALLOWED_OPERATION_DURATION_MS`], as for [`Operation::new`].
+    pub timeout_ms: Option<i64>,
+}
+
+impl ServiceSpec {
+    /// Declare a service.
+    pub fn new(
+        name: &str,
+        request: Vec<SPVariable>,
+        response: Vec<SPVariable>,
+        commands: Vec<ServiceCommand>,
+        timeout_ms: Option<i64>,
+    ) -> ServiceSpec {
+        ServiceSpec {
+            name: name.to_string(),
+            request,
+            response,
+            commands,
+            timeout_ms,
+        }
+    }
+
+    /// `{name}_request_trigger`.
+    pub fn trigger(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_request_trigger", self.name), SPValueType::Bool)
+    }
+
+    /// `{name}_request_state`.
+    pub fn request_state(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_request_state", self.name), SPValueType::String)
+    }
+
+    /// `{name}_request_information`.
+    pub fn request_information(&self) -> SPVariable {
+        SPVariable::new(
+            &format!("{}_request_information", self.name),
+            SPValueType::String,
+        )
+    }
+
+    /// The initial state of the service: the trigger down, the request state
+    /// `initial`, and the information, request and response variables
+    /// `UNKNOWN`. A variable listed as both request and response appears once.
+    pub fn state_variables(&self) -> State {
+        let mut state = State::from_vec(&vec![
+            (self.trigger(), false.to_spvalue()),
+            (
+                self.request_state(),
+                ServiceRequestState::Initial.to_string().to_spvalue(),
+            ),
+            (
+                self.request_information(),
+                SPValue::String(StringOrUnknown::UNKNOWN),
+            ),
+        ]);
+        for variable in self.request.iter().chain(self.response.iter()) {
+            if !state.contains(&variable.name) {
+                state.add_mut(
+                    SPAssignment::new(variable.clone(), variable.value_type.unknown()),
+                    &self.name,
+                );
+            }
+        }
+        state
+    }
+
+    /// One [`Operation`] per command, wired to the handshake as described in
+    /// the [module documentation](self). Pass them to [`Model::new`] like any
+    /// hand-written operation.
+    pub fn to_operations(&self) -> Vec<Operation> {
+        self.commands
+            .iter()
+            .map(|command| self.to_operation(command))
+            .collect()
+    }
+
+    /// The driver side of this service, with the same name and variables.
+    ///
+    /// The server gets no timeout of its own: the operation's timeout already
+    /// bounds the call, and a second clock would only race it.
+    pub fn to_server(&self) -> ServiceServer {
+        ServiceServer::new(&self.name, self.request.clone(), self.response.clone(), None)
+    }
+
+    fn to_operation(&self, command: &ServiceCommand) -> Operation {
+        let request_state_is = |request_state: ServiceRequestState| {
+            Predicate::EQ(
+                SPWrapped::SPVariable(self.request_state()),
+                SPWrapped::SPValue(request_state.to_string().to_spvalue()),
+            )
+        };
+        let reset = vec![
+            Action::new(self.trigger(), false.to_spvalue().wrap()),
+            Action::new(
+                self.request_state(),
+                ServiceRequestState::Initial.to_string().to_spvalue().wrap(),
+            ),
+        ];
+
+        let mut request = command.request.clone();
+        request.push(Action::new(self.trigger(), true.to_spvalue().wrap()));
+        let start = Transition::new(
+            &format!("start_{}", command.name),
+            Predicate::AND(vec![
+                command.guard.clone(),
+                request_state_is(ServiceRequestState::Initial),
+                Predicate::EQ(
+                    SPWrapped::SPVariable(self.trigger()),
+                    SPWrapped::SPValue(false.to_spvalue()),
+                ),
+            ]),
+            Predicate::TRUE,
+            request,
+            vec![],
+        );
+
+        let mut effects = reset.clone();
+        effects.extend(command.effects.iter().cloned());
+        let complete = Transition::new(
+            &format!("complete_{}", command.name),
+            Predicate::TRUE,
+            request_state_is(ServiceRequestState::Succeeded),
+            effects,
+            vec![],
+        );
+
+        let fail = Transition::new(
+            &format!("fail_{}", command.name),
+            Predicate::TRUE,
+            request_state_is(ServiceRequestState::Failed),
+            reset.clone(),
+            vec![],
+        );
+
+        let timeout = Transition::new(
+            &format!("timeout_{}", command.name),
+            Predicate::TRUE,
+            Predicate::TRUE,
+            reset,
+            vec![],
+        );
+
+        Operation::new(
+            &command.name,
+            self.timeout_ms,
+            None,
+            None,
+            None,
+            false,
+            vec![start],
+            vec![complete],
+            vec![fail],
+            vec![timeout],
+            vec![],
+            vec![],
+        )
+    }
+}
+
+#[cfg(test)]
+mod service_spec_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn command() -> SPVariable {
+        SPVariable::new("gantry_command_command", SPValueType::String)
+    }
+
+    fn locked() -> SPVariable {
+        SPVariable::new("gantry_locked_estimated", SPValueType::Bool)
+    }
+
+    fn gantry() -> ServiceSpec {
+        ServiceSpec::new(
+            "gantry",
+            vec![command()],
+            vec![locked()],
+            vec![ServiceCommand::new(
+                "gantry_unlock",
+                Predicate::EQ(locked().wrap(), true.to_spvalue().wrap()),
+                vec![Action::new(command(), "unlock".to_spvalue().wrap())],
+                vec![Action::new(locked(), false.to_spvalue().wrap())],
+            )],
+            Some(10_000),
+        )
+    }
+
+    fn state() -> State {
+        let mut state = gantry().state_variables();
+        state.update_mut("gantry_locked_estimated", true.to_spvalue());
+        state.add_mut(
+            SPAssignment::new(
+                SPVariable::new("gantry_unlock", SPValueType::String),
+                OperationState::Initial.to_spvalue(),
+            ),
+            TARGET,
+        );
+        state
+    }
+
+    fn request_state(state: &State) -> String {
+        state.get_string_or_default_to_unknown("gantry_request_state", TARGET)
+    }
+
+    #[test]
+    fn the_initial_state_is_an_idle_handshake() {
+        let state = gantry().state_variables();
+        assert_eq!(
+            state.get_value("gantry_request_trigger", TARGET),
+            Some(false.to_spvalue())
+        );
+        assert_eq!(request_state(&state), "initial");
+        assert_eq!(
+            state.get_value("gantry_command_command", TARGET),
+            Some(SPValue::String(StringOrUnknown::UNKNOWN))
+        );
+        assert_eq!(
+            state.get_value("gantry_locked_estimated", TARGET),
+            Some(SPValue::Bool(BoolOrUnknown::UNKNOWN))
+        );
+    }
+
+    #[test]
+    fn starting_sends_the_request() {
+        let operation = &gantry().to_operations()[0];
+        assert!(!operation.eval(&state().update("gantry_locked_estimated", false.to_spvalue()), TARGET));
+        assert!(!operation.eval(&state().update("gantry_request_trigger", true.to_spvalue()), TARGET));
+        assert!(operation.eval(&state(), TARGET));
+
+        let state = operation.start(&state(), TARGET);
+        assert_eq!(
+            state.get_value("gantry_request_trigger", TARGET),
+            Some(true.to_spvalue())
+        );
+        assert_eq!(
+            state.get_value("gantry_command_command", TARGET),
+            Some("unlock".to_spvalue())
+        );
+    }
+
+    #[test]
+    fn the_answer_completes_or_fails_and_resets_the_handshake() {
+        let operation = &gantry().to_operations()[0];
+        let started = operation.start(&state(), TARGET);
+        assert!(!operation.can_be_completed(&started, TARGET));
+        assert!(!operation.can_be_failed(&started, TARGET));
+
+        let succeeded = started
+            .update("gantry_request_trigger", false.to_spvalue())
+            .update("gantry_request_state", "succeeded".to_spvalue());
+        assert!(operation.can_be_completed(&succeeded, TARGET));
+        let completed = operation.complete(&succeeded, TARGET);
+        assert_eq!(request_state(&completed), "initial");
+        assert_eq!(
+            completed.get_value("gantry_locked_estimated", TARGET),
+            Some(false.to_spvalue())
+        );
+
+        let failed = started.update("gantry_request_state", "failed".to_spvalue());
+        assert!(operation.can_be_failed(&failed, TARGET));
+        let failed = operation.fail(&failed, TARGET);
+        assert_eq!(request_state(&failed), "initial");
+        assert_eq!(
+            failed.get_value("gantry_request_trigger", TARGET),
+            Some(false.to_spvalue())
+        );
+        assert_eq!(
+            failed.get_value("gantry_locked_estimated", TARGET),
+            Some(true.to_spvalue())
+        );
+    }
+
+    #[tokio::test]
+    async fn the_generated_server_answers_the_generated_operation() {
+        let spec = gantry();
+        let operation = &spec.to_operations()[0];
+        let started = operation.start(&state(), TARGET);
+
+        let unlock = |_: State| async {
+            ServiceResponse::succeeded(State::from_vec(&vec![(locked(), false.to_spvalue())]))
+        };
+        let answered = spec.to_server().serve(&started, &unlock, TARGET).await;
+
+        assert!(operation.can_be_completed(&answered, TARGET));
+    }
+
+    #[test]
+    fn the_operations_fit_a_model() {
+        let model = Model::new("m", vec![], vec![], vec![], vec![], gantry().to_operations());
+        let state = generate_operation_state_variables(&model, false, TARGET);
+        assert!(state.contains("op_gantry_unlock"));
+    }
+}
@@ -102,7 +102,8 @@ impl Transition {
     /// becomes [`Predicate::FALSE`] (the transition can never fire) and an
     /// unparseable action becomes [`Action::empty`] (which panics if applied).
     /// A typo in a model disables one transition rather than stopping the
-    /// process.
+    /// process. Comparisons and assignments whose two sides have different
+    /// types are parse failures too; see [`try_parse_pred`].
     ///
     /// # Example
     ///
@@ -140,57 +141,115 @@ impl Transition {
         runner_actions: Vec<&str>,
         state: &State,
     ) -> Transition {
+        let log_failure = |errors: Vec<ParseDiagnostic>, fallback: &str| {
+            for e in errors {
+                log::error!(target: &&format!("transition_parser"), "Failed to parse {e}");
+            }
+            log::error!(target: &&format!("transition_parser"), "{fallback}, fix the model.");
+        };
         Transition::new(
             name,
-            match pred_parser::pred(guard, state) {
-                Ok(guard_predicate) => guard_predicate,
-                Err(e) => {
-                    log::error!(target: &&format!("transition_parser"), 
-                        "Failed to parse guard {guard} with: {e}");
-                    log::error!(target: &&format!("transition_parser"), 
-                        "Guard set to FALSE, fix the model.");
-                    Predicate::FALSE
-                }
-            },
-            match pred_parser::pred(runner_guard, state) {
-                Ok(guard_predicate) => guard_predicate,
-                Err(e) => {
-                    log::error!(target: &&format!("transition_parser"), 
-                        "Failed to parse guard {runner_guard} with: {e}");
-                    log::error!(target: &&format!("transition_parser"), 
-                        "Runner guard set to FALSE, fix the model.");
-                    Predicate::FALSE
-                }
-            },
+            try_parse_pred(guard, state).unwrap_or_else(|errors| {
+                log_failure(in_context(errors, &format!("guard of '{name}'")), "Guard set to FALSE");
+                Predicate::FALSE
+            }),
+            try_parse_pred(runner_guard, state).unwrap_or_else(|errors| {
+                log_failure(
+                    in_context(errors, &format!("runner guard of '{name}'")),
+                    "Runner guard set to FALSE",
+                );
+                Predicate::FALSE
+            }),
             actions
                 .iter()
-                .map(|action| match pred_parser::action(action, state) {
-                    Ok(action_def) => action_def,
-                    Err(e) => {
-                        log::error!(target: &&format!("transition_parser"), 
-                            "Failed to parse action {action} with: {e}");
-                        log::error!(target: &&format!("transition_parser"), 
-                            "Action set to EMPTY, fix the model.");
+                .map(|action| {
+                    try_parse_action(action, state).unwrap_or_else(|errors| {
+                        log_failure(
+                            in_context(errors, &format!("action of '{name}'")),
+                            "Action set to EMPTY",
+                        );
                         Action::empty()
-                    }
+                    })
                 })
                 .collect::<Vec<Action>>(),
             runner_actions
                 .iter()
-                .map(|action| match pred_parser::action(action, state) {
-                    Ok(action_def) => action_def,
-                    Err(e) => {
-                        log::error!(target: &&format!("transition_parser"), 
-                            "Failed to parse runner_actions {action} with: {e}");
-                        log::error!(target: &&format!("transition_parser"), 
-                            "Runner action set to EMPTY, fix the model.");
+                .map(|action| {
+                    try_parse_action(action, state).unwrap_or_else(|errors| {
+                        log_failure(
+                            in_context(errors, &format!("runner action of '{name}'")),
+                            "Runner action set to EMPTY",
+                        );
                         Action::empty()
-                    }
+                    })
                 })
                 .collect::<Vec<Action>>(),
         )
     }
 
+    /// Like [`Transition::parse`], but fails instead of falling back.
+    ///
+    /// Every guard and action is parsed even after one fails, so the error
+    /// lists all the problems in this transition, each attributed to the part
+    /// it came from (`guard of 'move_to_b'`, `runner action of 'move_to_b'`,
+    /// ...). Use [`collect_parsed`] to do the same across a whole model.
+    ///
+    /// ```
+    /// use micro_sp::*;
+    ///
+    /// let state = State::from_vec(&vec![
+    ///     (SPVariable::new("pos", SPValueType::String), "a".to_spvalue()),
+    /// ]);
+    ///
+    /// let errors = Transition::try_parse(
+    ///     "move_to_b",
+    ///     "var:pso == a",
+    ///     "true",
+    ///     vec!["var:pos = b"],
+    ///     Vec::<&str>::new(),
+    ///     &state,
+    /// )
+    /// .unwrap_err();
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!(errors[0].context, "guard of 'move_to_b'");
+    /// assert_eq!(errors[1].suggestions, vec!["<-".to_string()]);
+    /// ```
+    pub fn try_parse(
+        name: &str,
+        guard: &str,
+        runner_guard: &str,
+        actions: Vec<&str>,
+        runner_actions: Vec<&str>,
+        state: &State,
+    ) -> Result<Transition, Vec<ParseDiagnostic>> {
+        let guard = try_parse_pred(guard, state)
+            .map_err(|e| in_context(e, &format!("guard of '{name}'")));
+        let runner_guard = try_parse_pred(runner_guard, state)
+            .map_err(|e| in_context(e, &format!("runner guard of '{name}'")));
+        let actions = collect_parsed(actions.iter().map(|a| {
+            try_parse_action(a, state).map_err(|e| in_context(e, &format!("action of '{name}'")))
+        }));
+        let runner_actions = collect_parsed(runner_actions.iter().map(|a| {
+            try_parse_action(a, state)
+                .map_err(|e| in_context(e, &format!("runner action of '{name}'")))
+        }));
+        match (guard, runner_guard, actions, runner_actions) {
+            (Ok(guard), Ok(runner_guard), Ok(actions), Ok(runner_actions)) => Ok(
+                Transition::new(name, guard, runner_guard, actions, runner_actions),
+            ),
+            (guard, runner_guard, actions, runner_actions) => Err([
+                guard.err(),
+                runner_guard.err(),
+                actions.err(),
+                runner_actions.err(),
+            ]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect()),
+        }
+    }
+
     /// A placeholder transition named `"empty"` that can never be taken.
     ///
     /// Both guards are [`Predicate::FALSE`] and it has no actions.
@@ -218,8 +277,12 @@ impl Transition {
     }
 
     /// Apply this transition's planning actions to `state` in place.
+    ///
+    /// Actions that read the clock, through `now()` or `age(..)`, are skipped:
+    /// no time passes in a plan, and a fresh timestamp would make every
+    /// simulated state a new one to the search. The runner still takes them.
     pub fn take_planning_mut(&self, state: &mut State, log_target: &str) {
-        for a in &self.actions {
+        for a in self.actions.iter().filter(|a| !a.var_or_val.reads_clock()) {
             a.assign_mut(state, &log_target);
         }
     }
@@ -324,6 +387,10 @@ impl fmt::Display for Transition {
     }
 }
 
+fn in_context(errors: Vec<ParseDiagnostic>, context: &str) -> Vec<ParseDiagnostic> {
+    errors.into_iter().map(|e| e.in_context(context)).collect()
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -426,6 +493,56 @@ mod tests {
         assert!(t1.eval(&s, "t"));
     }
 
+    #[test]
+    fn try_parse_reports_every_part_that_fails() {
+        let s = State::from_vec(&john_doe());
+        let errors = Transition::try_parse(
+            "gains_weight",
+            "var:wieght == 80.0",
+            "var:alive = true",
+            vec!["var:weight <- 85.0", "var:hight <- 190"],
+            vec!["var:smart <-"],
+            &s,
+        )
+        .unwrap_err();
+        let contexts: Vec<&str> = errors.iter().map(|e| e.context.as_str()).collect();
+        assert_eq!(
+            contexts,
+            vec![
+                "guard of 'gains_weight'",
+                "runner guard of 'gains_weight'",
+                "action of 'gains_weight'",
+                "runner action of 'gains_weight'",
+            ]
+        );
+        assert_eq!(errors[2].suggestions[0], "height");
+
+        let t = Transition::try_parse(
+            "gains_weight",
+            "var:weight == 80.0",
+            "true",
+            vec!["var:weight <- 85.0"],
+            Vec::<&str>::new(),
+            &s,
+        )
+        .unwrap();
+        assert!(t.eval(&s, "t"));
+    }
+
+    #[test]
+    fn parse_falls_back_instead_of_panicking_on_unknown_variables() {
+        let s = State::from_vec(&john_doe());
+        let t = Transition::parse(
+            "gains_weight",
+            "var:wealth > 9",
+            "true",
+            Vec::<&str>::new(),
+            Vec::<&str>::new(),
+            &s,
+        );
+        assert_eq!(t.guard, Predicate::FALSE);
+    }
+
     #[test]
     fn test_transition_take_planning() {
         let s = State::from_vec(&john_doe());
@@ -493,6 +610,30 @@ mod tests {
         assert_ne!(s_next_1.get_value("weight", "t"), Some(85.0.to_spvalue()));
     }
 
+    #[test]
+    fn planning_leaves_the_clock_alone() {
+        let s = State::from_vec(&vec![
+            (tv!("last_seen"), SPValue::Time(TimeOrUnknown::UNKNOWN)),
+            (bv!("seen"), false.to_spvalue()),
+        ]);
+        let t = Transition::parse(
+            "see",
+            "true",
+            "true",
+            vec!["var:last_seen <- now()", "var:seen <- true"],
+            Vec::<&str>::new(),
+            &s,
+        );
+
+        let planned = t.clone().take_planning(&s, "t");
+        assert_eq!(planned, s.clone().update("seen", true.to_spvalue()));
+        assert_eq!(t.clone().take_planning(&s, "t"), planned, "the same state every time");
+
+        let mut taken = s.clone();
+        t.take_mut(&mut taken, "t");
+        assert!(!taken.get_value("last_seen", "t").unwrap().is_unknown());
+    }
+
     #[test]
     fn test_transition_equality() {
         let weight = fv!("weight");
@@ -0,0 +1,263 @@
+//! [`Watchdog`]s: noticing that the process behind a service has gone quiet.
+//!
+//! A watchdog watches a signal the service keeps changing while it is alive -
+//! a heartbeat counter or timestamp it writes, or the progress of its request
+//! handshake - and publishes two variables:
+//!
+//! * `{name}_watchdog_alive` - `false` once the signal has been silent for
+//!   longer than [`Watchdog::silence_ms`], `true` again as soon as it moves.
+//! * `{name}_watchdog_fault` - a [`WatchdogFault`] as JSON while the
+//!   watchdog is tripped, `UNKNOWN` otherwise.
+//!
+//! [`Model::with_watchdogs`] makes the operations that depend on the service
+//! require `{name}_watchdog_alive` to start, which keeps the planner from
+//! choosing them, and fail while it is `false`, which stops the ones already
+//! executing. The watchdog runner keeps the variables up to date.
+//!
+//! ```
+//! use micro_sp::*;
+//!
+//! let heartbeat = iv!("gantry_heartbeat");
+//! let watchdog = Watchdog::heartbeat("gantry", heartbeat, 2000, vec!["gantry_unlock"]);
+//!
+//! let state = watchdog.state_variables();
+//! assert_eq!(state.get_value("gantry_watchdog_alive", "docs"), Some(true.to_spvalue()));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// What a [`Watchdog`] takes as a sign of life.
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
+pub enum WatchedSignal {
+    /// Any change of this variable's value. The service is expected to
+    /// change it at a steady rate, for example by counting up.
+    Heartbeat(SPVariable),
+    /// Any change of the named service's `{name}_request_state` or
+    /// `{name}_request_information`, counted only while its
+    /// `{name}_request_trigger` is up. An idle service is never silent.
+    RequestProgress(String),
+}
+
+/// Supervision of one service; see the [module documentation](self).
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
+pub struct Watchdog {
+    /// The service name, which prefixes the watchdog's variables.
+    pub name: String,
+    /// What counts as a sign of life.
+    pub signal: WatchedSignal,
+    /// How long the signal may stay unchanged, in milliseconds, before the
+    /// watchdog trips.
+    pub silence_ms: i64,
+    /// The operations that need the service, by the name they were given to
+    /// [`Model::new`] (with or without the `op_` prefix).
+    pub operations: Vec<String>,
+}
+
+/// Why a [`Watchdog`] tripped, as published in `{name}_watchdog_fault`.
+#[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
+pub struct WatchdogFault {
+    /// The watchdog that tripped.
+    pub watchdog: String,
+    /// The variables that stopped changing.
+    pub signal: Vec<String>,
+    /// How long they had been silent when it tripped, in milliseconds.
+    pub silent_ms: i64,
+    /// The operations that are failed and blocked until the signal returns.
+    pub operations: Vec<String>,
+}
+
+impl Watchdog {
+    /// Watch `heartbeat`, which the service changes at least every
+    /// `silence_ms`, on behalf of `operations`.
+    pub fn heartbeat(
+        name: &str,
+        heartbeat: SPVariable,
+        silence_ms: i64,
+        operations: Vec<&str>,
+    ) -> Watchdog {
+        Watchdog {
+            name: name.to_string(),
+            signal: WatchedSignal::Heartbeat(heartbeat),
+            silence_ms,
+            operations: operations.iter().map(|o| o.to_string()).collect(),
+        }
+    }
+
+    /// Watch the request handshake of `service` on behalf of every operation
+    /// it generates: a pending request that makes no progress for
+    /// `silence_ms` trips the watchdog.
+    pub fn request_progress(service: &ServiceSpec, silence_ms: i64) -> Watchdog {
+        Watchdog {
+            name: service.name.clone(),
+            signal: WatchedSignal::RequestProgress(service.name.clone()),
+            silence_ms,
+            operations: service.commands.iter().map(|c| c.name.clone()).collect(),
+        }
+    }
+
+    /// `{name}_watchdog_alive`.
+    pub fn alive(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_watchdog_alive", self.name), SPValueType::Bool)
+    }
+
+    /// `{name}_watchdog_fault`.
+    pub fn fault(&self) -> SPVariable {
+        SPVariable::new(&format!("{}_watchdog_fault", self.name), SPValueType::String)
+    }
+
+    /// The initial state of the watchdog: alive, with no fault. The watched
+    /// variables themselves belong to the service and are not included.
+    pub fn state_variables(&self) -> State {
+        State::from_vec(&vec![
+            (self.alive(), true.to_spvalue()),
+            (self.fault(), SPValue::String(StringOrUnknown::UNKNOWN)),
+        ])
+    }
+
+    /// The variables whose changes count as a sign of life.
+    pub fn signal_keys(&self) -> Vec<String> {
+        match &self.signal {
+            WatchedSignal::Heartbeat(var) => vec![var.name.clone()],
+            WatchedSignal::RequestProgress(service) => vec![
+                format!("{}_request_state", service),
+                format!("{}_request_information", service),
+            ],
+        }
+    }
+
+    /// The variable that has to be up for silence to count, if any.
+    pub fn pending_key(&self) -> Option<String> {
+        match &self.signal {
+            WatchedSignal::Heartbeat(_) => None,
+            WatchedSignal::RequestProgress(service) => {
+                Some(format!("{}_request_trigger", service))
+            }
+        }
+    }
+
+    /// Whether `operation` is one this watchdog supervises.
+    pub fn supervises(&self, operation: &Operation) -> bool {
+        self.operations
+            .iter()
+            .any(|requested| operation_answers_to(&operation.name, requested))
+    }
+
+    /// `operation` with every precondition also requiring the service alive,
+    /// and one more failure transition, `watchdog_{name}`, for when it is not.
+    pub(crate) fn supervise(&self, mut operation: Operation) -> Operation {
+        let alive_is = |alive: bool| {
+            Predicate::EQ(self.alive().wrap(), alive.to_spvalue().wrap())
+        };
+        for precondition in operation.preconditions.iter_mut() {
+            precondition.guard = Predicate::AND(vec![alive_is(true), precondition.guard.clone()]);
+        }
+        operation.failure_transitions.push(Transition::new(
+            &format!("watchdog_{}", self.name),
+            alive_is(false),
+            Predicate::TRUE,
+            vec![],
+            vec![],
+        ));
+        operation
+    }
+}
+
+#[cfg(test)]
+mod watchdog_tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn unlock() -> Operation {
+        let state = State::from_vec(&vec![(bv!("locked"), true.to_spvalue())]);
+        Operation {
+            name: "gantry_unlock".to_string(),
+            preconditions: vec![Transition::parse(
+                "start_gantry_unlock",
+                "var:locked == true",
+                "true",
+                vec!["var:locked <- false"],
+                Vec::<&str>::new(),
+                &state,
+            )],
+            ..Default::default()
+        }
+    }
+
+    fn watchdog() -> Watchdog {
+        Watchdog::heartbeat("gantry", iv!("gantry_heartbeat"), 1000, vec!["gantry_unlock"])
+    }
+
+    fn state(alive: bool) -> State {
+        watchdog()
+            .state_variables()
+            .update("gantry_watchdog_alive", alive.to_spvalue())
+            .extend(
+                State::from_vec(&vec![
+                    (bv!("locked"), true.to_spvalue()),
+                    (v!("op_gantry_unlock"), OperationState::Executing.to_spvalue()),
+                ]),
+                true,
+            )
+    }
+
+    #[test]
+    fn supervised_operations_need_the_service_alive_to_start() {
+        let model = Model::new("cell", vec![], vec![], vec![], vec![], vec![unlock()])
+            .with_watchdogs(vec![watchdog()]);
+        let operation = &model.operations[0];
+        assert!(watchdog().supervises(operation));
+
+        assert!(operation.preconditions[0].eval_planning(&state(true), TARGET));
+        assert!(!operation.preconditions[0].eval_planning(&state(false), TARGET));
+        assert!(model.variables.iter().any(|v| v.name == "gantry_watchdog_alive"));
+    }
+
+    #[test]
+    fn a_tripped_watchdog_fails_executing_operations() {
+        let model = Model::new("cell", vec![], vec![], vec![], vec![], vec![unlock()])
+            .with_watchdogs(vec![watchdog()]);
+        let operation = &model.operations[0];
+
+        assert!(!operation.can_be_failed(&state(true), TARGET));
+        assert!(operation.can_be_failed(&state(false), TARGET));
+    }
+
+    #[test]
+    fn operations_in_a_sop_are_supervised_too() {
+        let sop = SOPStruct {
+            id: "unlock_twice".to_string(),
+            sop: SOP::Sequence(vec![SOP::Parallel(vec![SOP::Operation(Box::new(unlock()))])]),
+        };
+        let model = Model::new("cell", vec![], vec![], vec![], vec![sop], vec![])
+            .with_watchdogs(vec![watchdog()]);
+        let operation = &get_all_operations_from_sop(&model.sops[0].sop)[0];
+
+        assert!(operation.preconditions[0].eval_planning(&state(true), TARGET));
+        assert!(!operation.preconditions[0].eval_planning(&state(false), TARGET));
+        assert_eq!(operation.failure_transitions.len(), 1);
+    }
+
+    #[test]
+    fn other_operations_are_left_alone() {
+        let mut other = unlock();
+        other.name = "gantry_unlock_all".to_string();
+        let model = Model::new("cell", vec![], vec![], vec![], vec![], vec![other.clone()])
+            .with_watchdogs(vec![watchdog()]);
+        assert_eq!(model.operations[0].preconditions, other.preconditions);
+        assert!(model.operations[0].failure_transitions.is_empty());
+    }
+
+    #[test]
+    fn request_progress_watches_the_handshake() {
+        let service = ServiceSpec::new("gantry", vec![], vec![], vec![], None);
+        let watchdog = Watchdog::request_progress(&service, 500);
+        assert_eq!(
+            watchdog.signal_keys(),
+            vec!["gantry_request_state", "gantry_request_information"]
+        );
+        assert_eq!(watchdog.pending_key().as_deref(), Some("gantry_request_trigger"));
+    }
+}
@@ -54,8 +54,9 @@ fn state_identity(state: &State, keys: &[String]) -> Vec<Option<SPValue>> {
 /// Explores from `state`, applying any operation in `model` whose planning guard
 /// holds, and returns the shortest sequence of operation names to the first
 /// state satisfying `goal`. The search stops at `max_depth` steps or after
-/// `deadline_ms` milliseconds, reporting `found: false` either way; `log_target`
-/// is the log target used for guard evaluation diagnostics.
+/// `deadline_ms` milliseconds, reporting `found: false` either way, and fails
+/// at once for a goal outside a variable's domain; `log_target` is the log
+/// target used for guard evaluation diagnostics.
 ///
 /// Pure - no Redis, no async - so it can be called from `spawn_blocking`, which
 /// is what `planner_ticker` does.
@@ -69,6 +70,9 @@ pub fn bfs_operation_planner(
 ) -> PlanningResult {
     let now = Instant::now();
     let limit = Duration::from_millis(deadline_ms);
+    if goal_out_of_domain(goal, state, log_target) {
+        return PlanningResult::default();
+    }
 
     let identity_keys = planning_identity_keys(model);
 
@@ -27,13 +27,27 @@ pub struct PlanningResult {
     pub time: Duration,
 }
 
+/// Whether `goal` requires a value outside some variable's domain, so that no
+/// plan can reach it; see [`Predicate::domain_violations`]. Each such
+/// requirement is logged under `log_target`.
+pub(crate) fn goal_out_of_domain(goal: &Predicate, state: &State, log_target: &str) -> bool {
+    let violations = goal.domain_violations(state);
+    for (var, value) in &violations {
+        log::warn!(target: log_target,
+            "The goal requires '{}' = {}, which is outside its domain. No plan can reach it.",
+            var.name, value);
+    }
+    !violations.is_empty()
+}
+
 /// Breadth-first search for a sequence of transitions that reaches `goal`.
 ///
 /// Explores from `state`, taking any transition whose planning guard holds, and
 /// returns the shortest sequence of transition names to the first state
 /// satisfying `goal`. Gives up once a path exceeds `max_depth`; a `visited` set
-/// keeps a cyclic model from searching forever. `log_target` is the log target
-/// used for guard evaluation diagnostics.
+/// keeps a cyclic model from searching forever. A goal that requires a value
+/// outside a variable's domain fails at once, without a search. `log_target`
+/// is the log target used for guard evaluation diagnostics.
 ///
 /// ```
 /// use micro_sp::*;
@@ -56,6 +70,9 @@ pub fn bfs_transition_planner(
     log_target: &str
 ) -> PlanningResult {
     let now = Instant::now();
+    if goal_out_of_domain(&goal, &state, log_target) {
+        return PlanningResult::default();
+    }
     let mut visited: HashSet<State> = HashSet::new();
     let mut stack: Vec<(State, Vec<String>)> = vec![(state, vec![])];
     loop {
@@ -264,4 +281,21 @@ mod tests {
         assert!(result.plan.is_empty());
         assert_eq!(result.time, std::time::Duration::default());
     }
+
+    /// A goal outside a variable's domain cannot be reached however deep the
+    /// search goes, so the planner says so without searching.
+    #[test]
+    fn a_goal_outside_the_domain_fails_at_once() {
+        let pos = v!("pos", ["s0", "s1", "s2"]);
+        let state = State::from_vec(&vec![(pos.clone(), "s0".to_spvalue())]);
+        let goal = or!(eq!(pos.wrap(), "s9".wrap()), eq!(v!("pos").wrap(), "s7".wrap()));
+        assert_eq!(goal.domain_violations(&state).len(), 2);
+        let (_, transitions, _) = chain(2);
+        let result = bfs_transition_planner(state.clone(), goal, transitions.clone(), 10, TARGET);
+        assert_eq!(result, PlanningResult::default());
+
+        let reachable = or!(eq!(pos.wrap(), "s9".wrap()), eq!(pos.wrap(), "s2".wrap()));
+        assert!(reachable.domain_violations(&state).is_empty());
+        assert!(bfs_transition_planner(state, reachable, transitions, 10, TARGET).found);
+    }
 }
@@ -0,0 +1,421 @@
+//! Raising, acknowledging and clearing the model's [`Alarm`]s.
+//!
+//! Every tick this runner evaluates each alarm against the state and
+//! publishes:
+//!
+//! * `{sp_id}_active_alarms` - one [`ActiveAlarm`] per alarm that is raised or
+//!   still waiting for its acknowledgement, as JSON strings.
+//! * `{sp_id}_alarm_history` - the last [`ALARM_HISTORY_LENGTH`]
+//!   [`AlarmEvent`]s, oldest first, as JSON strings.
+//! * `{sp_id}_alarms_block_goals` - whether an active alarm keeps new goals
+//!   out; `goal_runner` reads it.
+//!
+//! It also applies the dashboard's `ack:<name>` and, when a pausing alarm is
+//! raised, turns on `{sp_id}_step_mode`.
+
+use std::{sync::Arc, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// How many events `{sp_id}_alarm_history` keeps.
+pub const ALARM_HISTORY_LENGTH: usize = 200;
+
+/// An alarm as listed in `{sp_id}_active_alarms`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ActiveAlarm {
+    /// The [`Alarm::name`].
+    pub name: String,
+    /// The [`Alarm::severity`].
+    pub severity: AlarmSeverity,
+    /// The message as rendered when the alarm was raised.
+    pub message: String,
+    /// When it was raised.
+    pub raised_at: SystemTime,
+    /// Whether an operator has acknowledged it.
+    pub acknowledged: bool,
+    /// Whether its predicate still holds.
+    pub condition: bool,
+}
+
+/// What happened to an alarm, in an [`AlarmEvent`].
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum AlarmEventKind {
+    /// Its predicate started to hold.
+    Raised,
+    /// An operator acknowledged it.
+    Acknowledged,
+    /// It left the active alarms.
+    Cleared,
+}
+
+/// One entry of `{sp_id}_alarm_history`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AlarmEvent {
+    /// The [`Alarm::name`].
+    pub name: String,
+    /// The [`Alarm::severity`].
+    pub severity: AlarmSeverity,
+    /// What happened.
+    pub kind: AlarmEventKind,
+    /// The message the alarm was raised with.
+    pub message: String,
+    /// When it happened.
+    pub at: SystemTime,
+}
+
+/// The next active alarms, given the `active` ones from the previous tick.
+///
+/// Raises every alarm whose predicate has started to hold, applies the
+/// operator's `acknowledge`, if any, and clears the alarms that
+/// [`Alarm::clearing`] lets go. Returns the new active alarms in model order,
+/// what happened to them, and whether `acknowledge` named an active alarm.
+/// Active alarms the model no longer has are dropped.
+pub fn update_alarms(
+    alarms: &[Alarm],
+    active: &[ActiveAlarm],
+    state: &State,
+    acknowledge: Option<&str>,
+    now: SystemTime,
+    log_target: &str,
+) -> (Vec<ActiveAlarm>, Vec<AlarmEvent>, bool) {
+    let mut next = vec![];
+    let mut events = vec![];
+    let mut acknowledged_any = false;
+    let event = |active: &ActiveAlarm, kind: AlarmEventKind| AlarmEvent {
+        name: active.name.clone(),
+        severity: active.severity,
+        kind,
+        message: active.message.clone(),
+        at: now,
+    };
+
+    for alarm in alarms {
+        let raised = alarm.is_raised(state, log_target);
+        let mut current = match active.iter().find(|a| a.name == alarm.name) {
+            Some(current) => current.clone(),
+            None if raised => {
+                let current = ActiveAlarm {
+                    name: alarm.name.clone(),
+                    severity: alarm.severity,
+                    message: alarm.render_message(state),
+                    raised_at: now,
+                    acknowledged: false,
+                    condition: true,
+                };
+                events.push(event(&current, AlarmEventKind::Raised));
+                current
+            }
+            None => continue,
+        };
+
+        current.condition = raised;
+        if acknowledge == Some(alarm.name.as_str()) {
+            acknowledged_any = true;
+            if !current.acknowledged {
+                current.acknowledged = true;
+                events.push(event(&current, AlarmEventKind::Acknowledged));
+            }
+        }
+        if !raised && (alarm.clearing == AlarmClearing::Auto || current.acknowledged) {
+            events.push(event(&current, AlarmEventKind::Cleared));
+        } else {
+            next.push(current);
+        }
+    }
+    (next, events, acknowledged_any)
+}
+
+/// Turn `step_mode_key` on in `state` if one of `events` raised an alarm that
+/// [pauses](Alarm::pauses). A state without the key is left as it is.
+fn pause_on_raise(
+    alarms: &[Alarm],
+    events: &[AlarmEvent],
+    step_mode_key: &str,
+    state: &mut State,
+    log_target: &str,
+) {
+    for event in events {
+        let pauses = alarms.iter().any(|a| a.name == event.name && a.pauses);
+        if event.kind == AlarmEventKind::Raised && pauses && state.contains(step_mode_key) {
+            log::warn!(target: log_target, "Alarm '{}' paused the runtime.", event.name);
+            state.update_mut(step_mode_key, true.to_spvalue());
+        }
+    }
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(values: Vec<SPValue>) -> Vec<T> {
+    values
+        .iter()
+        .filter_map(|value| match value {
+            SPValue::String(StringOrUnknown::String(json)) => serde_json::from_str(json).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+fn encode<T: Serialize>(items: &[T]) -> SPValue {
+    items
+        .iter()
+        .filter_map(|item| serde_json::to_string(item).ok())
+        .map(|json| json.to_spvalue())
+        .collect::<Vec<SPValue>>()
+        .to_spvalue()
+}
+
+/// Runs the alarms of `model` until the process ends.
+///
+/// With no alarms it returns straight away. Raises are logged as errors,
+/// warnings or info by severity, to the `{sp_id}_alarm_runner` target.
+pub async fn alarm_runner(
+    sp_id: &str,
+    model: &Model,
+    connection_manager: &Arc<ConnectionManager>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if model.alarms.is_empty() {
+        return Ok(());
+    }
+    let log_target = format!("{}_alarm_runner", sp_id);
+    log::info!(target: &log_target, "Online.");
+
+    let active_key = format!("{}_active_alarms", sp_id);
+    let history_key = format!("{}_alarm_history", sp_id);
+    let block_goals_key = format!("{}_alarms_block_goals", sp_id);
+    let command_key = format!("{}_dashboard_command", sp_id);
+    let step_mode_key = format!("{}_step_mode", sp_id);
+
+    let mut keys: Vec<String> = model.alarms.iter().flat_map(|a| a.variable_keys()).collect();
+    keys.extend([
+        active_key.clone(),
+        history_key.clone(),
+        block_goals_key.clone(),
+        command_key.clone(),
+        format!("{}_dashboard_command_response", sp_id),
+        step_mode_key.clone(),
+    ]);
+    keys.sort_unstable();
+    keys.dedup();
+
+    let mut interval = runner_interval();
+    let mut con = connection_manager.get_connection().await;
+
+    loop {
+        interval.tick().await;
+        let state = match StateManager::get_state_for_keys(&mut con, &keys, &log_target).await {
+            Some(s) => s,
+            None => continue,
+        };
+
+        // Both lists are seeded by `generate_runner_state_variables`; a state
+        // set up without them starts them empty.
+        let array = |key: &str| match state.contains(key) {
+            true => state.get_array_or_default_to_empty(key, &log_target),
+            false => vec![],
+        };
+        let active: Vec<ActiveAlarm> = decode(array(&active_key));
+        let command = if state.contains(&command_key) {
+            read_dashboard_command(sp_id, &state, &log_target)
+        } else {
+            DashboardCommand::UNKNOWN
+        };
+        let acknowledge = match &command {
+            DashboardCommand::Acknowledge(name) => Some(name.as_str()),
+            _ => None,
+        };
+
+        let (active, events, acknowledged) = update_alarms(
+            &model.alarms,
+            &active,
+            &state,
+            acknowledge,
+            SystemTime::now(),
+            &log_target,
+        );
+
+        let mut new_state = state.clone();
+        if let Some(name) = acknowledge {
+            let outcome = match acknowledged {
+                true => format!("acknowledged '{}'.", name),
+                false => format!("no active alarm '{}'.", name),
+            };
+            acknowledge_dashboard_command(sp_id, &mut new_state, &command, &outcome, &log_target);
+        }
+
+        for event in &events {
+            let level = match (event.kind, event.severity) {
+                (AlarmEventKind::Raised, AlarmSeverity::High) => log::Level::Error,
+                (AlarmEventKind::Raised, AlarmSeverity::Medium) => log::Level::Warn,
+                _ => log::Level::Info,
+            };
+            log::log!(target: &log_target, level,
+                "Alarm '{}' ({}) {:?}: {}", event.name, event.severity, event.kind, event.message);
+        }
+        pause_on_raise(&model.alarms, &events, &step_mode_key, &mut new_state, &log_target);
+
+        let mut history: Vec<AlarmEvent> = decode(array(&history_key));
+        history.extend(events);
+        let overflow = history.len().saturating_sub(ALARM_HISTORY_LENGTH);
+        history.drain(..overflow);
+
+        let blocks_goals = active
+            .iter()
+            .any(|a| model.alarms.iter().any(|alarm| alarm.name == a.name && alarm.blocks_goals));
+
+        new_state.extend_mut(
+            State::from_vec(&vec![
+                (av!(&&active_key), encode(&active)),
+                (av!(&&history_key), encode(&history)),
+                (bv!(&&block_goals_key), blocks_goals.to_spvalue()),
+            ]),
+            true,
+        );
+
+        let modified_state = state.get_diff_partial_state_and_add_missing(&new_state);
+        if !modified_state.state.is_empty() {
+            activity_log::log_state_diff(&log_target, &state, &modified_state);
+            StateManager::set_state(&mut con, &modified_state).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "test";
+
+    fn door(open: bool) -> State {
+        State::from_vec(&vec![(bv!("door_open"), open.to_spvalue())])
+    }
+
+    fn alarms() -> Vec<Alarm> {
+        let predicate = Predicate::EQ(bv!("door_open").wrap(), true.to_spvalue().wrap());
+        vec![
+            Alarm::new("door", predicate.clone(), AlarmSeverity::Medium, "Door open"),
+            Alarm::new("door_latched", predicate, AlarmSeverity::High, "Door was opened")
+                .requiring_acknowledgement(),
+        ]
+    }
+
+    fn tick(active: &[ActiveAlarm], open: bool, ack: Option<&str>) -> (Vec<ActiveAlarm>, Vec<AlarmEvent>, bool) {
+        update_alarms(&alarms(), active, &door(open), ack, SystemTime::UNIX_EPOCH, TARGET)
+    }
+
+    fn names(active: &[ActiveAlarm]) -> Vec<&str> {
+        active.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    fn kinds(events: &[AlarmEvent]) -> Vec<(&str, AlarmEventKind)> {
+        events.iter().map(|e| (e.name.as_str(), e.kind)).collect()
+    }
+
+    #[test]
+    fn alarms_are_raised_once_while_their_predicate_holds() {
+        let (active, events, _) = tick(&[], true, None);
+        assert_eq!(names(&active), vec!["door", "door_latched"]);
+        assert_eq!(
+            kinds(&events),
+            vec![("door", AlarmEventKind::Raised), ("door_latched", AlarmEventKind::Raised)]
+        );
+
+        let (again, events, _) = tick(&active, true, None);
+        assert_eq!(again, active);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn a_latched_alarm_waits_for_its_acknowledgement() {
+        let (active, _, _) = tick(&[], true, None);
+        let (active, events, _) = tick(&active, false, None);
+        assert_eq!(names(&active), vec!["door_latched"]);
+        assert!(!active[0].condition);
+        assert_eq!(kinds(&events), vec![("door", AlarmEventKind::Cleared)]);
+
+        let (active, events, acknowledged) = tick(&active, false, Some("door_latched"));
+        assert!(acknowledged && active.is_empty());
+        assert_eq!(
+            kinds(&events),
+            vec![
+                ("door_latched", AlarmEventKind::Acknowledged),
+                ("door_latched", AlarmEventKind::Cleared)
+            ]
+        );
+    }
+
+    #[test]
+    fn an_acknowledged_alarm_stays_while_its_condition_does() {
+        let (active, _, _) = tick(&[], true, None);
+        let (active, _, acknowledged) = tick(&active, true, Some("door_latched"));
+        assert!(acknowledged && active[1].acknowledged);
+        let (active, events, _) = tick(&active, false, None);
+        assert!(active.is_empty());
+        assert_eq!(events.len(), 2);
+
+        let (_, _, acknowledged) = tick(&[], false, Some("door_latched"));
+        assert!(!acknowledged);
+    }
+
+    #[test]
+    fn alarms_survive_their_encoding() {
+        let (active, events, _) = tick(&[], true, None);
+        let SPValue::Array(ArrayOrUnknown::Array(encoded)) = encode(&active) else {
+            panic!("not an array");
+        };
+        assert_eq!(decode::<ActiveAlarm>(encoded), active);
+        let SPValue::Array(ArrayOrUnknown::Array(encoded)) = encode(&events) else {
+            panic!("not an array");
+        };
+        assert_eq!(decode::<AlarmEvent>(encoded), events);
+    }
+
+    /// The pause is step mode, so it holds an operation that is already
+    /// `disabled` as well as one about to leave `initial`.
+    #[tokio::test]
+    async fn a_pausing_alarm_holds_an_operation_that_is_already_disabled() {
+        let mut state = door(true);
+        state.extend_mut(
+            State::from_vec(&vec![
+                (bv!("go"), true.to_spvalue()),
+                (bv!("sp_step_mode"), false.to_spvalue()),
+                (v!("sp_dashboard_command"), SPValue::String(StringOrUnknown::UNKNOWN)),
+            ]),
+            true,
+        );
+        let operation = Operation {
+            name: "op_move".to_string(),
+            preconditions: vec![Transition::parse(
+                "start",
+                "var:go == true",
+                "true",
+                vec![],
+                Vec::<&str>::new(),
+                &state,
+            )],
+            ..Default::default()
+        };
+        let names = vec![operation.name.clone()];
+        let mut state = add_operation_state_tracking_variable(&names, &state, TARGET);
+        state = add_operation_meta_tracking_variables(&names, &state, false, TARGET);
+        state.update_mut("op_move", "disabled".to_spvalue());
+
+        let alarms = vec![alarms()[0].clone().pausing()];
+        let (_, events, _) =
+            update_alarms(&alarms, &[], &state, None, SystemTime::UNIX_EPOCH, TARGET);
+        pause_on_raise(&alarms, &events, "sp_step_mode", &mut state, TARGET);
+        assert_eq!(state.get_value("sp_step_mode", TARGET), Some(true.to_spvalue()));
+
+        let state = running::process_operation::process_operation(
+            "sp",
+            state,
+            &operation,
+            running::process_operation::OperationProcessingType::Planned,
+            None,
+            10,
+            &OperationHandlers::default(),
+            TARGET,
+        )
+        .await;
+        assert_eq!(state.get_value("op_move", TARGET), Some("disabled".to_spvalue()));
+    }
+}
@@ -75,6 +75,7 @@ pub async fn auto_transition_runner(
         let modified_state = state.get_diff_partial_state(&new_state);
         if !modified_state.state.is_empty() {
             activity_log::log_state_diff(&log_target, &state, &modified_state);
+            model.warn_foreign_writes(name, &modified_state, &log_target);
             StateManager::set_state(&mut con, &modified_state).await;
         }
     }
@@ -87,9 +88,12 @@ pub async fn auto_transition_runner(
 /// a time, and writes back the state diff while deleting the bookkeeping keys of
 /// operations that terminated. `connection_manager` is the shared Redis
 /// connection; log output goes to the `{sp_id}_auto_operation_runner` target.
+/// `handlers` are fired as the operations change state; see
+/// [`OperationHandlers`].
 pub async fn auto_operation_runner(
     sp_id: &str,
     model: &Model,
+    handlers: &OperationHandlers,
     connection_manager: &Arc<ConnectionManager>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     initialize_env_logger();
@@ -174,8 +178,8 @@ pub async fn auto_operation_runner(
                 &current_active_op,
                 OperationProcessingType::Automatic,
                 None,
-                None,
                 tick_elapsed_ms,
+                handlers,
                 &log_target,
             )
             .await;
@@ -202,8 +206,8 @@ pub async fn auto_operation_runner(
                 &current_active_op,
                 OperationProcessingType::Automatic,
                 None,
-                None,
                 tick_elapsed_ms,
+                handlers,
                 &log_target,
             )
             .await;
@@ -226,6 +230,7 @@ pub async fn auto_operation_runner(
 
         let modified_state = state.get_diff_partial_state_and_add_missing(&new_state);
         activity_log::log_state_diff(&log_target, &state, &modified_state);
+        model.warn_foreign_writes(sp_id, &modified_state, &log_target);
         let active_set_changed = !new_op_ids.is_empty() || !terminated_operations.is_empty();
 
         let mut terminated_operations_meta = vec![];
@@ -361,7 +366,7 @@ mod runner_tests {
     ) -> tokio::task::JoinHandle<()> {
         let manager = Arc::clone(manager);
         tokio::spawn(async move {
-            let _ = auto_operation_runner(SP, &model, &manager).await;
+            let _ = auto_operation_runner(SP, &model, &OperationHandlers::default(), &manager).await;
         })
     }
 
@@ -706,6 +711,43 @@ mod runner_tests {
             before.get_diff_partial_state(&after)
         );
     }
+
+    /// The runner works on a state read back from Redis, which has lost the
+    /// domains the model declared; a write outside one is refused all the
+    /// same.
+    #[tokio::test]
+    #[serial]
+    async fn an_auto_transition_cannot_write_outside_a_domain() {
+        let (_container, manager) = redis().await;
+        let mut con = manager.get_connection().await;
+        let mut domain = flags(&["moved"]);
+        domain.add_mut(SPAssignment::new(v!("pos", ["a", "b"]), "a".to_spvalue()), TARGET);
+        domain.add_mut(SPAssignment::new(v!("target"), "z".to_spvalue()), TARGET);
+
+        let model = Model::new(
+            SP,
+            vec![Transition::parse(
+                "move",
+                "var:moved == false",
+                "true",
+                vec!["var:pos <- var:target", "var:moved <- true"],
+                Vec::<&str>::new(),
+                &domain,
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        deploy(&manager, &model, domain).await;
+
+        let runner = spawn_transitions(&manager, model);
+        let moved = wait_true(&mut con, "moved", 3000).await;
+        runner.abort();
+
+        assert!(moved, "the transition should have fired");
+        assert_eq!(StateManager::get_sp_value(&mut con, "pos").await, Some("a".to_spvalue()));
+    }
 }
 
 #[cfg(test)]
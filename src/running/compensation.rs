@@ -0,0 +1,275 @@
+//! Compensating the completed steps of an aborted plan or SOP.
+//!
+//! An operation can declare a [compensation](Operation::compensation): another
+//! operation that undoes its effect, such as releasing a gripper it closed or
+//! returning to home from where it moved. When a plan or a SOP ends cancelled
+//! or failed, its runner queues the compensations of the steps that had
+//! completed, last completed first, and drives them one at a time before it
+//! takes on new work.
+//!
+//! The queue lives in the state, so it survives a runner restart:
+//!
+//! * `{sp_id}_plan_compensation` / `{sp_id}_sop_compensation` - the uniquified
+//!   compensation operations still to run, in order.
+//! * `{queue}_step` - the index of the one running now.
+//! * `{sp_id}_sop_completion_order` - the operations of the running SOP that
+//!   have completed, in the order they did, which in a parallel or
+//!   alternative SOP is not the order it lists them in.
+//!
+//! A compensation that itself fails or is cancelled abandons the rest of the
+//! queue, with an error: compensating a compensation is left to the operator.
+//! A `stop` left in force on the dashboard cancels compensations like any
+//! other operation.
+
+use crate::{running::process_operation::OperationProcessingType, *};
+
+/// Every compensation operation declared in `model`, on planned and automatic
+/// operations and on the operations of its SOPs.
+pub fn compensation_operations(model: &Model) -> Vec<Operation> {
+    model
+        .all_operations()
+        .into_iter()
+        .filter_map(|operation| operation.compensation.map(|compensation| *compensation))
+        .collect()
+}
+
+/// The compensation steps queued under `queue_key`, empty if there is no queue.
+pub fn read_compensation(state: &State, queue_key: &str, log_target: &str) -> Vec<String> {
+    if !state.contains(queue_key) {
+        return vec![];
+    }
+    state
+        .get_array_or_default_to_empty(queue_key, log_target)
+        .iter()
+        .filter(|val| val.is_string())
+        .map(|val| val.to_string())
+        .collect()
+}
+
+/// Queue the compensations of `completed`, in reverse, under `queue_key`.
+///
+/// Each is uniquified like a plan step and gets its tracking variables. With
+/// nothing to compensate the state is returned unchanged.
+pub(super) fn begin_compensation(
+    queue_key: &str,
+    completed: &[&Operation],
+    state: &State,
+    log_target: &str,
+) -> State {
+    let steps: Vec<String> = completed
+        .iter()
+        .rev()
+        .filter_map(|operation| operation.compensation.as_ref())
+        .map(|compensation| {
+            format!("{}_{}", compensation.name, nanoid::nanoid!(10, &NANOID_ALPHABET))
+        })
+        .collect();
+    if steps.is_empty() {
+        return state.clone();
+    }
+    log::warn!(target: log_target, "Compensating {} completed steps: {}.", steps.len(), steps.join(", "));
+
+    let mut new_state = add_operation_state_tracking_variable(&steps, state, log_target);
+    new_state = add_operation_meta_tracking_variables(&steps, &new_state, false, log_target);
+    new_state.extend_mut(
+        State::from_vec(&vec![
+            (av!(queue_key), steps.to_spvalue()),
+            (iv!(&&format!("{}_step", queue_key)), 0.to_spvalue()),
+        ]),
+        true,
+    );
+    new_state
+}
+
+/// Drive the compensation step queued under `queue_key` for one tick.
+///
+/// `templates` are the [`compensation_operations`] the steps were made from.
+/// Returns the new state and, once the queue is done with, its steps, whose
+/// variables the caller deletes.
+pub(super) async fn process_compensation_tick(
+    sp_id: &str,
+    state: State,
+    queue_key: &str,
+    templates: &[Operation],
+    tick_elapsed_ms: i64,
+    handlers: &OperationHandlers,
+    log_target: &str,
+) -> (State, Vec<String>) {
+    let steps = read_compensation(&state, queue_key, log_target);
+    let step_key = format!("{}_step", queue_key);
+    let mut step = state.get_int_or_default_to_zero(&step_key, log_target);
+
+    let template = steps.get(step as usize).and_then(|name| {
+        templates
+            .iter()
+            .filter(|template| name.starts_with(&template.name))
+            .max_by_key(|template| template.name.len())
+    });
+    let mut outcome = PlanState::Executing.to_string();
+    let mut new_state = match template {
+        Some(template) => {
+            let mut operation = template.clone();
+            operation.name = steps[step as usize].clone();
+            running::process_operation::process_operation(
+                sp_id,
+                state,
+                &operation,
+                OperationProcessingType::Planned,
+                Some((&mut step, &mut outcome)),
+                tick_elapsed_ms,
+                handlers,
+                log_target,
+            )
+            .await
+        }
+        None if (step as usize) < steps.len() => {
+            log::error!(target: log_target, "Compensation '{}' not found in model!", steps[step as usize]);
+            outcome = PlanState::Failed.to_string();
+            state
+        }
+        None => {
+            log::info!(target: log_target, "Compensation finished.");
+            outcome = PlanState::Completed.to_string();
+            state
+        }
+    };
+
+    if outcome == PlanState::Executing.to_string() {
+        new_state.update_mut(&step_key, step.to_spvalue());
+        return (new_state, vec![]);
+    }
+    if outcome != PlanState::Completed.to_string() {
+        log::error!(target: log_target,
+            "Compensation '{}' ended {}, abandoning the rest of the compensation.",
+            steps[step as usize], outcome);
+    }
+    new_state.update_mut(queue_key, Vec::<String>::new().to_spvalue());
+    new_state.update_mut(&step_key, 0.to_spvalue());
+    (new_state, steps)
+}
+
+/// Every state variable of `steps`, for deleting them once compensated.
+pub(super) fn compensation_keys(steps: &[String]) -> Vec<String> {
+    let mut keys = vec![];
+    for step in steps {
+        push_operation_keys(&mut keys, step);
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SP: &str = "sp";
+    const TARGET: &str = "test";
+    const QUEUE: &str = "sp_plan_compensation";
+
+    fn state() -> State {
+        State::from_vec(&vec![
+            (bv!("gripper_closed"), true.to_spvalue()),
+            (v!("sp_dashboard_command"), SPValue::String(StringOrUnknown::UNKNOWN)),
+            (av!(QUEUE), Vec::<String>::new().to_spvalue()),
+            (iv!("sp_plan_compensation_step"), 0.to_spvalue()),
+        ])
+    }
+
+    fn release() -> Operation {
+        let state = state();
+        Operation {
+            name: "op_release".to_string(),
+            preconditions: vec![Transition::parse(
+                "start_release",
+                "var:gripper_closed == true",
+                "true",
+                vec!["var:gripper_closed <- false"],
+                Vec::<&str>::new(),
+                &state,
+            )],
+            postconditions: vec![Transition::parse(
+                "complete_release",
+                "true",
+                "true",
+                vec![],
+                Vec::<&str>::new(),
+                &state,
+            )],
+            ..Default::default()
+        }
+    }
+
+    fn grip() -> Operation {
+        Operation {
+            name: "op_grip".to_string(),
+            ..Default::default()
+        }
+        .compensated_by(release())
+    }
+
+    async fn tick(state: State) -> (State, Vec<String>) {
+        process_compensation_tick(
+            SP,
+            state,
+            QUEUE,
+            &[release()],
+            10,
+            &OperationHandlers::default(),
+            TARGET,
+        )
+        .await
+    }
+
+    #[test]
+    fn compensations_are_queued_last_completed_first() {
+        let plain = Operation {
+            name: "op_wait".to_string(),
+            ..Default::default()
+        };
+        let mut place = grip();
+        place.name = "op_place".to_string();
+        let queued = begin_compensation(QUEUE, &[&grip(), &plain, &place], &state(), TARGET);
+
+        let steps = read_compensation(&queued, QUEUE, TARGET);
+        assert_eq!(steps.len(), 2);
+        assert!(steps.iter().all(|step| step.starts_with("op_release_")));
+        assert_eq!(
+            queued.get_value(&steps[0], TARGET),
+            Some("initial".to_spvalue())
+        );
+        assert!(queued.contains(&format!("{}_information", steps[1])));
+
+        let nothing = begin_compensation(QUEUE, &[&plain], &state(), TARGET);
+        assert_eq!(nothing, state());
+    }
+
+    #[tokio::test]
+    async fn the_queue_runs_to_the_end_and_is_handed_back() {
+        let mut state = begin_compensation(QUEUE, &[&grip()], &state(), TARGET);
+        let steps = read_compensation(&state, QUEUE, TARGET);
+
+        let mut finished = vec![];
+        for _ in 0..10 {
+            let (next, done) = tick(state).await;
+            state = next;
+            if !done.is_empty() {
+                finished = done;
+                break;
+            }
+        }
+        assert_eq!(finished, steps);
+        assert_eq!(state.get_value("gripper_closed", TARGET), Some(false.to_spvalue()));
+        assert!(read_compensation(&state, QUEUE, TARGET).is_empty());
+        assert_eq!(compensation_keys(&steps).len(), 6);
+    }
+
+    #[tokio::test]
+    async fn a_failing_compensation_abandons_the_queue() {
+        let mut state = begin_compensation(QUEUE, &[&grip()], &state(), TARGET);
+        let steps = read_compensation(&state, QUEUE, TARGET);
+        state.update_mut(&steps[0], "fatal".to_spvalue());
+
+        let (state, finished) = tick(state).await;
+        assert_eq!(finished, steps);
+        assert!(read_compensation(&state, QUEUE, TARGET).is_empty());
+    }
+}
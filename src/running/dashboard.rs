@@ -0,0 +1,514 @@
+//! Operator commands written by the dashboard.
+//!
+//! The dashboard steers a running system through one key,
+//! `{sp_id}_dashboard_command`, and the runners report back through
+//! `{sp_id}_dashboard_command_response`. The command is a [`DashboardCommand`]
+//! in its wire form; every runner that owns operations reads it on every tick.
+//!
+//! `stop` is a mode rather than a request: it stays in force, cancelling every
+//! unfinished operation, until the dashboard writes something else. Every other
+//! command is a one-shot. The runner that applies it writes what it did to the
+//! response key and clears the command, so it is never applied twice. A
+//! one-shot that has nothing to apply to yet - `retry` while the step is still
+//! executing, say - stays pending until it does.
+//!
+//! ```
+//! use micro_sp::*;
+//!
+//! let command = DashboardCommand::parse("cancel:gripper_open");
+//! assert_eq!(command, DashboardCommand::Cancel("gripper_open".to_string()));
+//! assert_eq!(command.to_string(), "cancel:gripper_open");
+//! assert!(command.is_one_shot());
+//!
+//! // A uniquified instance answers to the name it was made from.
+//! assert!(operation_answers_to("op_gripper_open_A1b2C3d4E5", "gripper_open"));
+//! assert!(!operation_answers_to("op_gripper_open_slowly", "gripper_open"));
+//! ```
+
+use std::fmt;
+
+use crate::*;
+
+/// A command from the dashboard, read from `{sp_id}_dashboard_command`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum DashboardCommand {
+    /// Cancel every operation that has not finished; serialises to `"stop"`.
+    ///
+    /// Sticky: it is never cleared by a runner.
+    Stop,
+    /// Cancel the one operation of this name; `"cancel:<name>"`.
+    ///
+    /// The name is matched with [`operation_answers_to`], so the name from the
+    /// model reaches the uniquified instance a plan or SOP is running.
+    Cancel(String),
+    /// Send the current plan step back to `initial`; `"retry"`.
+    ///
+    /// Applies once the step is failed, timed out or fatal.
+    Retry,
+    /// Mark the current plan step as bypassed; `"bypass"`.
+    ///
+    /// Applies once the step is failed, timed out or fatal, whether or not the
+    /// operation allows bypassing by itself.
+    Bypass,
+    /// Mark the current plan step as bypassed without running it to the end;
+    /// `"skip"`.
+    Skip,
+    /// Cancel the step the plan is on, which cancels the plan; `"abort_plan"`.
+    AbortPlan,
+    /// Cancel every unfinished operation of the running SOP; `"abort_sop"`.
+    AbortSop,
+    /// Acknowledge the active [`Alarm`] of this name; `"ack:<name>"`.
+    Acknowledge(String),
+    /// No command, or nothing recognisable; `"UNKNOWN"`. Also the [`Default`].
+    #[default]
+    UNKNOWN,
+}
+
+impl DashboardCommand {
+    /// Parses the string form written to `{sp_id}_dashboard_command`.
+    ///
+    /// Anything unrecognised - including `"none"` and an empty `"cancel:"` or `"ack:"` -
+    /// becomes [`DashboardCommand::UNKNOWN`].
+    pub fn parse(x: &str) -> DashboardCommand {
+        match x {
+            "stop" => DashboardCommand::Stop,
+            "retry" => DashboardCommand::Retry,
+            "bypass" => DashboardCommand::Bypass,
+            "skip" => DashboardCommand::Skip,
+            "abort_plan" => DashboardCommand::AbortPlan,
+            "abort_sop" => DashboardCommand::AbortSop,
+            _ => match (x.strip_prefix("cancel:"), x.strip_prefix("ack:")) {
+                (Some(name), _) if !name.trim().is_empty() => {
+                    DashboardCommand::Cancel(name.trim().to_string())
+                }
+                (_, Some(name)) if !name.trim().is_empty() => {
+                    DashboardCommand::Acknowledge(name.trim().to_string())
+                }
+                _ => DashboardCommand::UNKNOWN,
+            },
+        }
+    }
+
+    /// The command as the [`SPValue`] stored in the shared state, matching
+    /// [`Display`](std::fmt::Display).
+    pub fn to_spvalue(self) -> SPValue {
+        match self {
+            DashboardCommand::UNKNOWN => SPValue::String(StringOrUnknown::UNKNOWN),
+            command => command.to_string().to_spvalue(),
+        }
+    }
+
+    /// Whether the command is cleared once a runner has applied it. Everything
+    /// except [`DashboardCommand::Stop`] is.
+    pub fn is_one_shot(&self) -> bool {
+        !matches!(self, DashboardCommand::Stop | DashboardCommand::UNKNOWN)
+    }
+}
+
+impl fmt::Display for DashboardCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DashboardCommand::Stop => write!(f, "stop"),
+            DashboardCommand::Cancel(name) => write!(f, "cancel:{}", name),
+            DashboardCommand::Retry => write!(f, "retry"),
+            DashboardCommand::Bypass => write!(f, "bypass"),
+            DashboardCommand::Skip => write!(f, "skip"),
+            DashboardCommand::AbortPlan => write!(f, "abort_plan"),
+            DashboardCommand::AbortSop => write!(f, "abort_sop"),
+            DashboardCommand::Acknowledge(name) => write!(f, "ack:{}", name),
+            DashboardCommand::UNKNOWN => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+/// The command currently in `{sp_id}_dashboard_command`.
+///
+/// # Panics
+///
+/// Panics if the key is not in `state`, like [`State::get_value`]; every runner
+/// that calls this has it in its static keys.
+pub fn read_dashboard_command(sp_id: &str, state: &State, log_target: &str) -> DashboardCommand {
+    DashboardCommand::parse(&state.get_string_or_default_to_unknown(
+        &format!("{}_dashboard_command", sp_id),
+        log_target,
+    ))
+}
+
+/// Whether the operation `operation_name` is the one the dashboard means by
+/// `requested`.
+///
+/// Matches the name itself, the name with the `op_` prefix [`Model::new`]
+/// adds, and either of those followed by the `_` plus 10-character nanoid the
+/// plan and SOP runners append to make an instance unique.
+pub fn operation_answers_to(operation_name: &str, requested: &str) -> bool {
+    let prefixed = format!("op_{}", requested);
+    [requested, prefixed.as_str()].iter().any(|template| {
+        operation_name == *template
+            || operation_name
+                .strip_prefix(template)
+                .and_then(|rest| rest.strip_prefix('_'))
+                .is_some_and(|suffix| {
+                    suffix.chars().count() == 10
+                        && suffix.chars().all(|c| NANOID_ALPHABET.contains(&c))
+                })
+    })
+}
+
+/// Records that `command` was applied and, if it is a one-shot, clears it.
+///
+/// The response key gets `"<command>: <outcome>"`. A state without the response
+/// key - one initialised before it existed - only has the command cleared.
+pub fn acknowledge_dashboard_command(
+    sp_id: &str,
+    state: &mut State,
+    command: &DashboardCommand,
+    outcome: &str,
+    log_target: &str,
+) {
+    let response = format!("{}: {}", command, outcome);
+    log::info!(target: log_target, "Dashboard command {}", response);
+
+    let response_key = format!("{}_dashboard_command_response", sp_id);
+    if state.contains(&response_key) {
+        state.update_mut(&response_key, response.to_spvalue());
+    }
+    if command.is_one_shot() {
+        state.update_mut(
+            &format!("{}_dashboard_command", sp_id),
+            DashboardCommand::UNKNOWN.to_spvalue(),
+        );
+    }
+}
+
+/// Applies a pending plan command - `retry`, `bypass`, `skip` or `abort_plan` -
+/// to `operation`, the step the plan is on, before the step is processed.
+///
+/// Returns the state unchanged when there is no plan command or the step is not
+/// in a state the command applies to; the command then stays pending. A step
+/// the command cancels raises [`OperationEvent::Cancelled`] to `handlers`.
+pub(super) fn apply_plan_step_command(
+    sp_id: &str,
+    state: State,
+    operation: &Operation,
+    handlers: &OperationHandlers,
+    log_target: &str,
+) -> State {
+    let command = read_dashboard_command(sp_id, &state, log_target);
+    let operation_state = OperationState::from_str(
+        &state.get_string_or_default_to_unknown(&operation.name, log_target),
+    );
+    let stuck = matches!(
+        operation_state,
+        OperationState::Failed | OperationState::Timedout | OperationState::Fatal
+    );
+
+    let (mut new_state, outcome) = match command {
+        DashboardCommand::Retry if stuck => {
+            let new_state = match operation_state {
+                OperationState::Fatal => operation.reinitialize(&state, log_target),
+                _ => operation.retry(&state, log_target),
+            };
+            (new_state, format!("retrying '{}'.", operation.name))
+        }
+        DashboardCommand::Bypass if stuck => (
+            operation.skip(&state, log_target),
+            format!("bypassed '{}'.", operation.name),
+        ),
+        DashboardCommand::Skip if operation.is_unfinished(&state, log_target) => (
+            operation.skip(&state, log_target),
+            format!("skipped '{}'.", operation.name),
+        ),
+        DashboardCommand::AbortPlan if operation.is_cancellable(&state, log_target) => (
+            operation.cancel(&state, log_target),
+            format!("cancelled '{}', aborting the plan.", operation.name),
+        ),
+        _ => return state,
+    };
+
+    acknowledge_dashboard_command(sp_id, &mut new_state, &command, &outcome, log_target);
+    handlers.dispatch_transition(&operation.name, &operation_state, &new_state, log_target);
+    new_state
+}
+
+/// Applies a pending `abort_sop` to the running SOP `sop`, whose instance is
+/// `sop_id`: every operation in it that is still cancellable is cancelled, and
+/// the SOP reads as cancelled once they have been processed.
+pub(super) fn apply_sop_command(
+    sp_id: &str,
+    state: State,
+    sop_id: &str,
+    sop: &SOP,
+    handlers: &OperationHandlers,
+    log_target: &str,
+) -> State {
+    let command = read_dashboard_command(sp_id, &state, log_target);
+    if command != DashboardCommand::AbortSop {
+        return state;
+    }
+
+    let mut new_state = state;
+    let mut cancelled = vec![];
+    for operation in get_all_operations_from_sop(sop) {
+        if operation.is_cancellable(&new_state, log_target) {
+            let from = OperationState::from_str(
+                &new_state.get_string_or_default_to_unknown(&operation.name, log_target),
+            );
+            new_state = operation.cancel(&new_state, log_target);
+            handlers.dispatch_transition(&operation.name, &from, &new_state, log_target);
+            cancelled.push(operation.name);
+        }
+    }
+
+    acknowledge_dashboard_command(
+        sp_id,
+        &mut new_state,
+        &command,
+        &format!(
+            "cancelled {} operation(s) of SOP '{}'.",
+            cancelled.len(),
+            sop_id
+        ),
+        log_target,
+    );
+    new_state
+}
+
+#[cfg(test)]
+mod dashboard_command_tests {
+    use crate::*;
+
+    #[test]
+    fn every_command_survives_a_round_trip() {
+        for command in [
+            DashboardCommand::Stop,
+            DashboardCommand::Cancel("op_x".to_string()),
+            DashboardCommand::Retry,
+            DashboardCommand::Bypass,
+            DashboardCommand::Skip,
+            DashboardCommand::AbortPlan,
+            DashboardCommand::AbortSop,
+            DashboardCommand::Acknowledge("door_open".to_string()),
+        ] {
+            assert_eq!(DashboardCommand::parse(&command.to_string()), command);
+        }
+    }
+
+    #[test]
+    fn anything_else_is_unknown() {
+        for wire in ["none", "", "start", "cancel:", "cancel:  ", "ack:", "STOP"] {
+            assert_eq!(
+                DashboardCommand::parse(wire),
+                DashboardCommand::UNKNOWN,
+                "'{wire}'"
+            );
+        }
+    }
+
+    #[test]
+    fn cleared_command_is_the_typed_unknown_state_init_writes() {
+        assert_eq!(
+            DashboardCommand::UNKNOWN.to_spvalue(),
+            SPValue::String(StringOrUnknown::UNKNOWN)
+        );
+    }
+
+    #[test]
+    fn stop_is_the_only_sticky_command() {
+        assert!(!DashboardCommand::Stop.is_one_shot());
+        assert!(!DashboardCommand::UNKNOWN.is_one_shot());
+        assert!(DashboardCommand::Retry.is_one_shot());
+        assert!(DashboardCommand::Cancel("x".to_string()).is_one_shot());
+    }
+
+    #[test]
+    fn names_match_the_operation_and_its_instances() {
+        assert!(operation_answers_to("op_move", "op_move"));
+        assert!(operation_answers_to("op_move", "move"));
+        assert!(operation_answers_to("op_move_abcdeFGHIJ", "move"));
+        assert!(operation_answers_to("op_move_abcdeFGHIJ", "op_move"));
+        assert!(operation_answers_to("op_move_abcdeFGHIJ", "op_move_abcdeFGHIJ"));
+
+        assert!(!operation_answers_to("op_move_fast", "move"));
+        assert!(!operation_answers_to("op_move_abcdeFGHI", "move"));
+        assert!(!operation_answers_to("op_move_abcde-GHIJ", "move"));
+        assert!(!operation_answers_to("op_remove", "move"));
+    }
+}
+
+#[cfg(test)]
+mod plan_step_command_tests {
+    use crate::*;
+
+    use super::apply_plan_step_command;
+
+    const SP_ID: &str = "sp";
+    const TARGET: &str = "test";
+
+    fn operation() -> Operation {
+        Operation {
+            name: "op_x".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn state_with(operation_state: &str, command: &str) -> State {
+        State::from_vec(&vec![
+            (
+                SPVariable::new("op_x", SPValueType::String),
+                operation_state.to_spvalue(),
+            ),
+            (
+                SPVariable::new(&format!("{SP_ID}_dashboard_command"), SPValueType::String),
+                command.to_spvalue(),
+            ),
+            (
+                SPVariable::new(
+                    &format!("{SP_ID}_dashboard_command_response"),
+                    SPValueType::String,
+                ),
+                SPValue::String(StringOrUnknown::UNKNOWN),
+            ),
+        ])
+    }
+
+    fn apply(state: State) -> State {
+        apply_plan_step_command(SP_ID, state, &operation(), &OperationHandlers::default(), TARGET)
+    }
+
+    fn step_state(state: &State) -> String {
+        state.get_string_or_default_to_unknown("op_x", TARGET)
+    }
+
+    fn pending(state: &State) -> DashboardCommand {
+        read_dashboard_command(SP_ID, state, TARGET)
+    }
+
+    fn response(state: &State) -> String {
+        state.get_string_or_default_to_unknown(&format!("{SP_ID}_dashboard_command_response"), TARGET)
+    }
+
+    #[test]
+    fn retry_sends_a_stuck_step_back_to_initial() {
+        for stuck in ["failed", "timedout", "fatal"] {
+            let state = apply(state_with(stuck, "retry"));
+            assert_eq!(step_state(&state), "initial", "from '{stuck}'");
+            assert_eq!(pending(&state), DashboardCommand::UNKNOWN);
+            assert_eq!(response(&state), "retry: retrying 'op_x'.");
+        }
+    }
+
+    #[test]
+    fn retry_waits_while_the_step_is_still_running() {
+        let before = state_with("executing", "retry");
+        let after = apply(before.clone());
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn bypass_marks_a_stuck_step_bypassed_even_if_the_operation_cannot_bypass() {
+        assert!(!operation().can_be_bypassed);
+        let state = apply(state_with("fatal", "bypass"));
+        assert_eq!(step_state(&state), "bypassed");
+        assert_eq!(pending(&state), DashboardCommand::UNKNOWN);
+    }
+
+    #[test]
+    fn skip_bypasses_a_step_that_has_not_finished() {
+        for unfinished in ["initial", "disabled", "executing", "failed", "timedout", "fatal"] {
+            let state = apply(state_with(unfinished, "skip"));
+            assert_eq!(step_state(&state), "bypassed", "from '{unfinished}'");
+        }
+        let before = state_with("completed", "skip");
+        assert_eq!(apply(before.clone()), before);
+    }
+
+    #[test]
+    fn abort_plan_cancels_the_current_step() {
+        let state = apply(state_with("executing", "abort_plan"));
+        assert_eq!(step_state(&state), "cancelled");
+        assert_eq!(pending(&state), DashboardCommand::UNKNOWN);
+    }
+
+    #[tokio::test]
+    async fn aborting_the_plan_tells_the_cancel_handlers() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut handlers = OperationHandlers::default();
+        handlers.register("x", OperationEvent::Cancelled, move |notification| {
+            let sender = sender.clone();
+            async move { sender.send(notification.operation).unwrap() }
+        });
+
+        let state = state_with("executing", "abort_plan");
+        let _ = apply_plan_step_command(SP_ID, state, &operation(), &handlers, TARGET);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        assert_eq!(receiver.try_recv().ok(), Some("op_x".to_string()));
+        assert!(receiver.try_recv().is_err(), "told once");
+    }
+
+    #[test]
+    fn stop_and_cancel_are_left_to_process_operation() {
+        for command in ["stop", "cancel:x"] {
+            let before = state_with("executing", command);
+            assert_eq!(apply(before.clone()), before);
+        }
+    }
+}
+
+#[cfg(test)]
+mod sop_command_tests {
+    use crate::*;
+
+    use super::apply_sop_command;
+
+    const SP_ID: &str = "sp";
+    const TARGET: &str = "test";
+
+    fn apply(state: State, sop: &SOP) -> State {
+        apply_sop_command(SP_ID, state, "the_sop_x", sop, &OperationHandlers::default(), TARGET)
+    }
+
+    fn op(name: &str) -> SOP {
+        SOP::Operation(Box::new(Operation {
+            name: name.to_string(),
+            ..Default::default()
+        }))
+    }
+
+    fn state_with(command: &str) -> State {
+        State::from_vec(&vec![
+            (SPVariable::new("op_a", SPValueType::String), "completed".to_spvalue()),
+            (SPVariable::new("op_b", SPValueType::String), "executing".to_spvalue()),
+            (SPVariable::new("op_c", SPValueType::String), "initial".to_spvalue()),
+            (
+                SPVariable::new(&format!("{SP_ID}_dashboard_command"), SPValueType::String),
+                command.to_spvalue(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn abort_sop_cancels_what_has_not_finished() {
+        let sop = SOP::Sequence(vec![op("op_a"), SOP::Parallel(vec![op("op_b"), op("op_c")])]);
+
+        let state = apply(state_with("abort_sop"), &sop);
+
+        assert_eq!(state.get_string_or_default_to_unknown("op_a", TARGET), "completed");
+        assert_eq!(state.get_string_or_default_to_unknown("op_b", TARGET), "cancelled");
+        assert_eq!(state.get_string_or_default_to_unknown("op_c", TARGET), "cancelled");
+        assert_eq!(read_dashboard_command(SP_ID, &state, TARGET), DashboardCommand::UNKNOWN);
+        assert_eq!(sop.get_state(&state, TARGET), SOPState::Cancelled);
+    }
+
+    #[test]
+    fn other_commands_leave_the_sop_alone() {
+        let sop = SOP::Sequence(vec![op("op_a"), op("op_b")]);
+        for command in ["none", "abort_plan", "retry"] {
+            let before = state_with(command);
+            assert_eq!(
+                apply(before.clone(), &sop),
+                before
+            );
+        }
+    }
+}
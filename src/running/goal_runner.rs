@@ -290,6 +290,7 @@ pub async fn goal_runner(
         format!("{}_replanned", sp_id),
         format!("{}_plan_current_step", sp_id),
         format!("{}_replan_for_same_goal", sp_id),
+        format!("{}_alarms_block_goals", sp_id),
     ];
 
     let mut con = connection_manager.get_connection().await;
@@ -363,7 +364,16 @@ pub async fn goal_runner(
         // what this used to do) made the serialised value differ every time, so
         // an MSET went out 10x/s for as long as anything was queued, and a
         // goal's id changed while it waited.
-        let scheduled_goals = admit_goals(scheduled_goals, incoming_goals);
+        //
+        // An active alarm can hold new goals back; they wait in the incoming
+        // list until it clears.
+        let alarms_block_goals_key = format!("{}_alarms_block_goals", sp_id);
+        let goals_blocked = state.contains(&alarms_block_goals_key)
+            && state.get_bool_or_default_to_false(&alarms_block_goals_key, log_target);
+        let scheduled_goals = match goals_blocked {
+            true => scheduled_goals,
+            false => admit_goals(scheduled_goals, incoming_goals),
+        };
 
         let scheduled_goals_sp_values: Vec<SPValue> = scheduled_goals
             .iter()
@@ -373,10 +383,12 @@ pub async fn goal_runner(
             &format!("{}_scheduled_goals", sp_id),
             scheduled_goals_sp_values.to_spvalue(),
         );
-        new_state.update_mut(
-            &format!("{}_incoming_goals", sp_id),
-            Vec::<SPValue>::new().to_spvalue(),
-        );
+        if !goals_blocked {
+            new_state.update_mut(
+                &format!("{}_incoming_goals", sp_id),
+                Vec::<SPValue>::new().to_spvalue(),
+            );
+        }
 
         match GoalState::from_str(&current_goal_state) {
             GoalState::Initial => {
@@ -15,15 +15,20 @@ use std::sync::Arc;
 /// Initialises logging and the activity log, then spawns eight detached tokio
 /// tasks - planner ticker, SOP runner, plan runner, auto transition runner, auto
 /// operation runner, timer interface, goal runner and transform interface - each
-/// of which loops forever polling Redis. Every runner reads and writes
-/// `{sp_id}_*` keys plus the model's own variables; see the individual runners
-/// for their key sets.
+/// of which loops forever polling Redis. The TTL runner is spawned as well
+/// when some model variable has a time-to-live, the watchdog runner when the
+/// model has [`Watchdog`]s and the alarm runner when it has [`Alarm`]s. Every
+/// runner reads and writes `{sp_id}_*` keys plus the model's own variables;
+/// see the individual runners for their key sets.
 ///
 /// * `sp_id` - the namespace every runner key is prefixed with.
 /// * `model` - the operations, automatic transitions and SOPs to execute. Moved
 ///   in and shared between the tasks behind an `Arc`.
 /// * `number_of_timers` - how many `{sp_id}_timer_N_*` timers the timer
 ///   interface should drive. Their variables must already exist in the state.
+/// * `handlers` - Rust callbacks on operation lifecycle changes, shared by the
+///   plan, SOP and automatic operation runners. Pass
+///   `OperationHandlers::default()` for none.
 /// * `connection_manager` - the Redis connection pool the tasks clone.
 ///
 /// The caller has to keep the process alive; the spawned tasks are dropped when
@@ -40,7 +45,7 @@ use std::sync::Arc;
 /// let connection_manager = Arc::new(ConnectionManager::new().await);
 ///
 /// // Seed Redis with the model's variables, then hand it to the runners.
-/// main_runner(&"sp".to_string(), model, 3, &connection_manager).await;
+/// main_runner(&"sp".to_string(), model, 3, OperationHandlers::default(), &connection_manager).await;
 ///
 /// // The runners are detached; keep the process alive.
 /// std::future::pending::<()>().await;
@@ -50,6 +55,7 @@ pub async fn main_runner(
     sp_id: &String,
     model: Model,
     number_of_timers: u64,
+    handlers: OperationHandlers,
     connection_manager: &Arc<ConnectionManager>,
 ) {
     initialize_env_logger();
@@ -73,8 +79,9 @@ pub async fn main_runner(
     let model_clone = Arc::clone(&model);
     let con_clone = connection_manager.clone();
     let sp_id_clone = sp_id.clone();
+    let handlers_clone = handlers.clone();
     tokio::task::spawn(async move {
-        sop_runner(&sp_id_clone, &model_clone, &con_clone)
+        sop_runner(&sp_id_clone, &model_clone, &handlers_clone, &con_clone)
             .await
             .unwrap()
     });
@@ -82,8 +89,9 @@ pub async fn main_runner(
     log::info!(target:  &format!("{sp_id}_micro_sp"), "Spawning operation runner.");
     let model_clone = Arc::clone(&model);
     let con_clone = connection_manager.clone();
+    let handlers_clone = handlers.clone();
     tokio::task::spawn(async move {
-        planned_operation_runner(&model_clone, &con_clone)
+        planned_operation_runner(&model_clone, &handlers_clone, &con_clone)
             .await
             .unwrap()
     });
@@ -104,6 +112,7 @@ pub async fn main_runner(
         auto_operation_runner(
             &model_clone.name,
             &model_clone,
+            &handlers,
             &con_clone,
         )
         .await
@@ -128,6 +137,36 @@ pub async fn main_runner(
     let con_clone = connection_manager.clone();
     let sp_id_clone = sp_id.clone();
     tokio::task::spawn(async move { tf_interface(&sp_id_clone, &con_clone).await.unwrap() });
+
+    if model.variables.iter().any(|var| var.ttl_ms.is_some()) {
+        log::info!(target: &format!("{sp_id}_micro_sp"), "Spawning TTL runner");
+        let model_clone = Arc::clone(&model);
+        let con_clone = connection_manager.clone();
+        let sp_id_clone = sp_id.clone();
+        tokio::task::spawn(async move {
+            ttl_runner(&sp_id_clone, &model_clone, &con_clone).await.unwrap()
+        });
+    }
+
+    if !model.watchdogs.is_empty() {
+        log::info!(target: &format!("{sp_id}_micro_sp"), "Spawning watchdog runner");
+        let model_clone = Arc::clone(&model);
+        let con_clone = connection_manager.clone();
+        let sp_id_clone = sp_id.clone();
+        tokio::task::spawn(async move {
+            watchdog_runner(&sp_id_clone, &model_clone, &con_clone).await.unwrap()
+        });
+    }
+
+    if !model.alarms.is_empty() {
+        log::info!(target: &format!("{sp_id}_micro_sp"), "Spawning alarm runner");
+        let model_clone = Arc::clone(&model);
+        let con_clone = connection_manager.clone();
+        let sp_id_clone = sp_id.clone();
+        tokio::task::spawn(async move {
+            alarm_runner(&sp_id_clone, &model_clone, &con_clone).await.unwrap()
+        });
+    }
 }
 
 /// The whole stack, in one process, against a real Redis.
@@ -302,7 +341,7 @@ mod tests {
         let mut con = manager.get_connection().await;
         let model = boot(&manager).await;
 
-        main_runner(&SP.to_string(), model, 1, &manager).await;
+        main_runner(&SP.to_string(), model, 1, OperationHandlers::default(), &manager).await;
 
         // The only input: a goal, exactly as an external client would post it.
         StateManager::set_sp_value(
@@ -346,7 +385,7 @@ mod tests {
         let mut con = manager.get_connection().await;
         let model = boot(&manager).await;
 
-        main_runner(&SP.to_string(), model, 1, &manager).await;
+        main_runner(&SP.to_string(), model, 1, OperationHandlers::default(), &manager).await;
 
         StateManager::set_sp_value(
             &mut con,
@@ -399,7 +438,7 @@ mod tests {
         let mut con = manager.get_connection().await;
         let model = boot(&manager).await;
 
-        main_runner(&SP.to_string(), model, 1, &manager).await;
+        main_runner(&SP.to_string(), model, 1, OperationHandlers::default(), &manager).await;
 
         StateManager::set_sp_value(
             &mut con,
@@ -433,7 +472,7 @@ mod tests {
         let mut con = manager.get_connection().await;
         let model = boot(&manager).await;
 
-        main_runner(&SP.to_string(), model, 1, &manager).await;
+        main_runner(&SP.to_string(), model, 1, OperationHandlers::default(), &manager).await;
 
         StateManager::set_sp_value(
             &mut con,
@@ -468,7 +507,7 @@ mod tests {
         let mut con = manager.get_connection().await;
         let model = boot(&manager).await;
 
-        main_runner(&SP.to_string(), model, 1, &manager).await;
+        main_runner(&SP.to_string(), model, 1, OperationHandlers::default(), &manager).await;
 
         // Let the auto transition settle first - that one does have work to do.
         assert_eq!(
@@ -7,6 +7,8 @@
 
 /// Automatic transitions and automatic operations.
 pub mod auto_runner;
+/// Raising, acknowledging and clearing alarms.
+pub mod alarm_runner;
 /// Executing the operations of the current plan.
 pub mod plan_runner;
 /// Planning towards the current goal.
@@ -15,6 +17,8 @@ pub mod planner_ticker;
 pub mod goal_runner;
 /// Executing SOPs.
 pub mod sop_runner;
+/// Undoing the completed steps of aborted plans and SOPs.
+pub mod compensation;
 /// Spawning and supervising all of the above.
 pub mod main_runner;
 /// Generating the initial state a model needs.
@@ -27,5 +31,19 @@ pub mod tick;
 pub mod runner_states;
 /// Timers a model can start, stop and read.
 pub mod time_runner;
+/// Resetting variables whose time-to-live has lapsed.
+pub mod ttl_runner;
+/// Supervising external services through their watchdogs.
+pub mod watchdog_runner;
 /// The operation state machine every runner drives.
 pub mod process_operation;
+/// Rolling runtime statistics per operation.
+pub mod operation_statistics;
+/// Operator commands from the dashboard and their acknowledgements.
+pub mod dashboard;
+/// Holding planned and SOP operations for operator confirmation.
+pub mod step_mode;
+/// Serving `{name}_request_trigger` requests from async Rust handlers.
+pub mod service_server;
+/// Rust callbacks on operation lifecycle changes.
+pub mod operation_handlers;
@@ -0,0 +1,296 @@
+//! Rust callbacks on operation lifecycle changes.
+//!
+//! Everything else in the runtime coordinates through variables in Redis. For
+//! side effects that have no business in the state - sending a notification,
+//! writing a record to a database - an [`OperationHandlers`] registry attaches
+//! plain async Rust functions to operations instead. [`main_runner`] hands the
+//! registry to every runner, which fire the matching handlers when an
+//! operation moves into one of the [`OperationEvent`]s - whether
+//! [`process_operation`] moved it or a dashboard command did.
+//!
+//! Handlers never hold up a tick: each call is a spawned task, and at most
+//! `max_concurrent` of them run at a time. A call made while all of them are
+//! taken is dropped with a warning rather than queued, so a stuck handler
+//! cannot pile up tasks behind it.
+//!
+//! [`process_operation`]: crate::running::process_operation
+//!
+//! ```no_run
+//! use micro_sp::*;
+//!
+//! let mut handlers = OperationHandlers::new(4);
+//! handlers.register("gantry_unlock", OperationEvent::Failed, |notification| async move {
+//!     log::warn!("'{}' failed, paging the line lead.", notification.operation);
+//! });
+//! ```
+
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use tokio::sync::Semaphore;
+
+use crate::*;
+
+/// How many handler calls [`OperationHandlers::default`] lets run at once.
+pub const DEFAULT_MAX_CONCURRENT_HANDLERS: usize = 8;
+
+/// The lifecycle changes a handler can be attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperationEvent {
+    /// A precondition fired and the operation is now `executing`.
+    Starting,
+    /// A postcondition fired.
+    Completed,
+    /// A failure transition fired.
+    Failed,
+    /// The operation ran, or waited, past its deadline.
+    Timedout,
+    /// The operation was cancelled.
+    Cancelled,
+}
+
+impl OperationEvent {
+    /// The event an operation going from `from` to `to` raises, if any.
+    pub fn from_transition(from: &OperationState, to: &OperationState) -> Option<OperationEvent> {
+        if from == to {
+            return None;
+        }
+        match to {
+            OperationState::Executing => Some(OperationEvent::Starting),
+            OperationState::Completed => Some(OperationEvent::Completed),
+            OperationState::Failed => Some(OperationEvent::Failed),
+            OperationState::Timedout => Some(OperationEvent::Timedout),
+            OperationState::Cancelled => Some(OperationEvent::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// What a handler is called with.
+#[derive(Debug, Clone)]
+pub struct OperationNotification {
+    /// The operation's full name, as in the state.
+    pub operation: String,
+    /// What just happened to it.
+    pub event: OperationEvent,
+    /// The state as it stood after the tick that raised the event.
+    pub state: State,
+}
+
+type Handler =
+    Arc<dyn Fn(OperationNotification) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// A registry of lifecycle handlers and the bound on how many run at once.
+///
+/// Cloning is cheap and clones share the bound.
+#[derive(Clone)]
+pub struct OperationHandlers {
+    handlers: Vec<(String, OperationEvent, Handler)>,
+    permits: Arc<Semaphore>,
+}
+
+impl Default for OperationHandlers {
+    fn default() -> Self {
+        OperationHandlers::new(DEFAULT_MAX_CONCURRENT_HANDLERS)
+    }
+}
+
+impl std::fmt::Debug for OperationHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OperationHandlers")
+            .field(
+                "handlers",
+                &self
+                    .handlers
+                    .iter()
+                    .map(|(operation, event, _)| (operation, event))
+                    .collect::<Vec<_>>(),
+            )
+            .field("available_permits", &self.permits.available_permits())
+            .finish()
+    }
+}
+
+impl OperationHandlers {
+    /// An empty registry whose handlers run at most `max_concurrent` at a time
+    /// (at least one).
+    pub fn new(max_concurrent: usize) -> OperationHandlers {
+        OperationHandlers {
+            handlers: vec![],
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Call `handler` whenever `operation` raises `event`.
+    ///
+    /// `operation` is matched with [`operation_answers_to`], so the name given
+    /// to [`Operation::new`] is enough; several handlers may share an event.
+    pub fn register<F, Fut>(&mut self, operation: &str, event: OperationEvent, handler: F)
+    where
+        F: Fn(OperationNotification) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler: Handler = Arc::new(move |notification| Box::pin(handler(notification)));
+        self.handlers.push((operation.to_string(), event, handler));
+    }
+
+    /// Whether no handler is registered.
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// Spawns every handler registered for `event` on `operation`.
+    ///
+    /// Returns at once; the state is only cloned when some handler matches. A
+    /// handler is only spawned once it holds one of the `max_concurrent`
+    /// permits; with none free it is skipped and logged.
+    pub fn dispatch(
+        &self,
+        operation: &str,
+        event: OperationEvent,
+        state: &State,
+        log_target: &str,
+    ) {
+        let matching: Vec<&Handler> = self
+            .handlers
+            .iter()
+            .filter(|(name, registered, _)| {
+                *registered == event && operation_answers_to(operation, name)
+            })
+            .map(|(_, _, handler)| handler)
+            .collect();
+        if matching.is_empty() {
+            return;
+        }
+
+        log::debug!(target: log_target,
+            "Dispatching {} handler(s) for {:?} of '{}'.", matching.len(), event, operation);
+        let notification = OperationNotification {
+            operation: operation.to_string(),
+            event,
+            state: state.clone(),
+        };
+        for handler in matching {
+            let permit = match Arc::clone(&self.permits).try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    log::warn!(target: log_target,
+                        "All handler slots are busy, dropping a {:?} handler of '{}'.", event, operation);
+                    continue;
+                }
+            };
+            let handler = Arc::clone(handler);
+            let notification = notification.clone();
+            tokio::task::spawn(async move {
+                handler(notification).await;
+                drop(permit);
+            });
+        }
+    }
+
+    /// Dispatches the event `operation` raised by going from `from` to
+    /// whatever state it has in `state`, if any.
+    ///
+    /// Everything that writes an operation's state calls this afterwards, so
+    /// handlers see a change no matter which runner or command made it.
+    pub fn dispatch_transition(
+        &self,
+        operation: &str,
+        from: &OperationState,
+        state: &State,
+        log_target: &str,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let to = match state.state.get(operation) {
+            Some(assignment) => OperationState::from_str(&assignment.val.to_string()),
+            None => return,
+        };
+        if let Some(event) = OperationEvent::from_transition(from, &to) {
+            self.dispatch(operation, event, state, log_target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+    use std::time::Duration;
+
+    const TARGET: &str = "test";
+
+    #[test]
+    fn only_lifecycle_changes_raise_events() {
+        use OperationState::*;
+        let cases = [
+            (Initial, Executing, Some(OperationEvent::Starting)),
+            (Disabled, Executing, Some(OperationEvent::Starting)),
+            (Executing, Executing, None),
+            (Executing, Completed, Some(OperationEvent::Completed)),
+            (Executing, Failed, Some(OperationEvent::Failed)),
+            (Disabled, Timedout, Some(OperationEvent::Timedout)),
+            (Initial, Cancelled, Some(OperationEvent::Cancelled)),
+            (Initial, Disabled, None),
+            (Completed, Terminated(TerminationReason::Completed), None),
+        ];
+        for (from, to, event) in cases {
+            assert_eq!(OperationEvent::from_transition(&from, &to), event, "{from:?} -> {to:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn handlers_run_for_their_own_operation_and_event() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut handlers = OperationHandlers::default();
+        let counter = Arc::clone(&calls);
+        handlers.register("unlock", OperationEvent::Failed, move |notification| {
+            let counter = Arc::clone(&counter);
+            async move {
+                assert_eq!(notification.operation, "op_unlock");
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        handlers.dispatch("op_unlock", OperationEvent::Completed, &State::new(), TARGET);
+        handlers.dispatch("op_lock", OperationEvent::Failed, &State::new(), TARGET);
+        handlers.dispatch("op_unlock", OperationEvent::Failed, &State::new(), TARGET);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn at_most_max_concurrent_handlers_run_at_once() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+        let mut handlers = OperationHandlers::new(2);
+        let (r, p, d) = (Arc::clone(&running), Arc::clone(&peak), Arc::clone(&done));
+        handlers.register("slow", OperationEvent::Starting, move |_| {
+            let (running, peak, done) = (Arc::clone(&r), Arc::clone(&p), Arc::clone(&d));
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                done.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        for _ in 0..6 {
+            handlers.dispatch("op_slow", OperationEvent::Starting, &State::new(), TARGET);
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert_eq!(done.load(Ordering::SeqCst), 2, "calls beyond the bound are dropped");
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        handlers.dispatch("op_slow", OperationEvent::Starting, &State::new(), TARGET);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(done.load(Ordering::SeqCst), 3, "a freed slot takes new calls");
+    }
+}
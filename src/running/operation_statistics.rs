@@ -0,0 +1,364 @@
+//! Rolling runtime statistics per operation.
+//!
+//! Every time an operation's run ends, [`process_operation`] records how it
+//! ended and how long it executed in `{op}_stats`, where `{op}` is the
+//! operation's name in the model: the runners give each run its own
+//! `_{nanoid}` name, and the statistics gather the runs of all of them.
+//!
+//! `{op}_stats` holds an [`OperationStatistics`] as a map. Along with what it
+//! is computed from - the number of runs ever and the last
+//! [`STATISTICS_WINDOW`] runs - it carries the derived figures, so a
+//! dashboard can read them straight from Redis. From Rust, read it with
+//! [`StateManager::get_operation_statistics`].
+//!
+//! [`process_operation`]: crate::running::process_operation
+
+use std::fmt;
+
+use crate::*;
+
+/// How many of the latest runs the rates and execution times are taken over.
+pub const STATISTICS_WINDOW: usize = 100;
+
+/// How a run of an operation ended.
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum RunOutcome {
+    /// It completed; `"completed"`.
+    Completed,
+    /// It failed or timed out and was bypassed; `"bypassed"`.
+    Bypassed,
+    /// It failed with no retries left; `"failed"`.
+    Failed,
+    /// It timed out with no retries left; `"timedout"`.
+    Timedout,
+    /// It was cancelled; `"cancelled"`.
+    Cancelled,
+}
+
+impl RunOutcome {
+    /// Parses the names written by [`fmt::Display`].
+    pub fn from_name(name: &str) -> Option<RunOutcome> {
+        match name {
+            "completed" => Some(RunOutcome::Completed),
+            "bypassed" => Some(RunOutcome::Bypassed),
+            "failed" => Some(RunOutcome::Failed),
+            "timedout" => Some(RunOutcome::Timedout),
+            "cancelled" => Some(RunOutcome::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunOutcome::Completed => write!(f, "completed"),
+            RunOutcome::Bypassed => write!(f, "bypassed"),
+            RunOutcome::Failed => write!(f, "failed"),
+            RunOutcome::Timedout => write!(f, "timedout"),
+            RunOutcome::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// One run of an operation.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct RunRecord {
+    /// How it ended.
+    pub outcome: RunOutcome,
+    /// How long its last attempt had been executing, in milliseconds.
+    pub duration_ms: i64,
+}
+
+/// The statistics of one operation; see the [module documentation](self).
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OperationStatistics {
+    /// How many runs have ended, ever.
+    pub count: i64,
+    /// The latest runs, oldest first, at most [`STATISTICS_WINDOW`].
+    pub runs: Vec<RunRecord>,
+}
+
+impl OperationStatistics {
+    /// Add a run that ended in `outcome` after executing for `duration_ms`.
+    pub fn record(&mut self, outcome: RunOutcome, duration_ms: i64) {
+        self.count += 1;
+        self.runs.push(RunRecord {
+            outcome,
+            duration_ms,
+        });
+        let overflow = self.runs.len().saturating_sub(STATISTICS_WINDOW);
+        self.runs.drain(..overflow);
+    }
+
+    /// The share of the latest runs that ended in `outcome`, `0.0` with none.
+    pub fn rate(&self, outcome: RunOutcome) -> f64 {
+        match self.runs.len() {
+            0 => 0.0,
+            n => self.runs.iter().filter(|run| run.outcome == outcome).count() as f64 / n as f64,
+        }
+    }
+
+    /// The share of the latest runs that completed.
+    pub fn success_rate(&self) -> f64 {
+        self.rate(RunOutcome::Completed)
+    }
+
+    /// The share of the latest runs that failed for good.
+    pub fn failure_rate(&self) -> f64 {
+        self.rate(RunOutcome::Failed)
+    }
+
+    /// The share of the latest runs that timed out for good.
+    pub fn timeout_rate(&self) -> f64 {
+        self.rate(RunOutcome::Timedout)
+    }
+
+    /// Execution times of the latest completed runs, shortest first. The
+    /// other outcomes are left out: how long a run took to fail says little
+    /// about how long the operation takes.
+    fn completed_durations(&self) -> Vec<i64> {
+        let mut durations: Vec<i64> = self
+            .runs
+            .iter()
+            .filter(|run| run.outcome == RunOutcome::Completed)
+            .map(|run| run.duration_ms)
+            .collect();
+        durations.sort_unstable();
+        durations
+    }
+
+    /// Mean execution time of the latest completed runs.
+    pub fn mean_ms(&self) -> Option<f64> {
+        let durations = self.completed_durations();
+        match durations.len() {
+            0 => None,
+            n => Some(durations.iter().sum::<i64>() as f64 / n as f64),
+        }
+    }
+
+    /// The `percentile` (nearest rank) execution time of the latest completed
+    /// runs.
+    pub fn percentile_ms(&self, percentile: f64) -> Option<i64> {
+        let durations = self.completed_durations();
+        if durations.is_empty() {
+            return None;
+        }
+        let rank = (percentile / 100.0 * durations.len() as f64).ceil() as usize;
+        Some(durations[rank.clamp(1, durations.len()) - 1])
+    }
+
+    /// Median execution time of the latest completed runs.
+    pub fn p50_ms(&self) -> Option<i64> {
+        self.percentile_ms(50.0)
+    }
+
+    /// 95th percentile execution time of the latest completed runs.
+    pub fn p95_ms(&self) -> Option<i64> {
+        self.percentile_ms(95.0)
+    }
+
+    /// Longest execution time of the latest completed runs.
+    pub fn max_ms(&self) -> Option<i64> {
+        self.completed_durations().last().copied()
+    }
+
+    /// The statistics as stored in `{op}_stats`, derived figures included.
+    pub fn to_spvalue(&self) -> SPValue {
+        let runs = self
+            .runs
+            .iter()
+            .map(|run| {
+                SPValue::Map(MapOrUnknown::Map(vec![
+                    ("outcome".to_spvalue(), run.outcome.to_string().to_spvalue()),
+                    ("duration_ms".to_spvalue(), run.duration_ms.to_spvalue()),
+                ]))
+            })
+            .collect::<Vec<SPValue>>();
+        SPValue::Map(MapOrUnknown::Map(vec![
+            ("count".to_spvalue(), self.count.to_spvalue()),
+            ("success_rate".to_spvalue(), self.success_rate().to_spvalue()),
+            ("failure_rate".to_spvalue(), self.failure_rate().to_spvalue()),
+            ("timeout_rate".to_spvalue(), self.timeout_rate().to_spvalue()),
+            ("mean_ms".to_spvalue(), self.mean_ms().to_spvalue()),
+            ("p50_ms".to_spvalue(), self.p50_ms().to_spvalue()),
+            ("p95_ms".to_spvalue(), self.p95_ms().to_spvalue()),
+            ("max_ms".to_spvalue(), self.max_ms().to_spvalue()),
+            ("runs".to_spvalue(), SPValue::Array(ArrayOrUnknown::Array(runs))),
+        ]))
+    }
+
+    /// Read back what [`OperationStatistics::to_spvalue`] wrote. The derived
+    /// figures are recomputed rather than read.
+    pub fn from_spvalue(value: &SPValue) -> Option<OperationStatistics> {
+        let field = |value: &SPValue, name: &str| {
+            value.get_path(&[PathSegment::Field(name.to_string())])
+        };
+        let count = match field(value, "count")? {
+            SPValue::Int64(IntOrUnknown::Int64(count)) => count,
+            _ => return None,
+        };
+        let runs = match field(value, "runs")? {
+            SPValue::Array(ArrayOrUnknown::Array(runs)) => runs
+                .iter()
+                .filter_map(|run| {
+                    let outcome = match field(run, "outcome")? {
+                        SPValue::String(StringOrUnknown::String(name)) => {
+                            RunOutcome::from_name(&name)?
+                        }
+                        _ => return None,
+                    };
+                    match field(run, "duration_ms")? {
+                        SPValue::Int64(IntOrUnknown::Int64(duration_ms)) => Some(RunRecord {
+                            outcome,
+                            duration_ms,
+                        }),
+                        _ => None,
+                    }
+                })
+                .collect(),
+            _ => return None,
+        };
+        Some(OperationStatistics { count, runs })
+    }
+}
+
+/// `{operation}_stats`, for `operation` by its name in the model.
+pub fn statistics_key(operation: &str) -> String {
+    format!("{}_stats", operation)
+}
+
+/// The operation a run named `run_name` belongs to, by its name in the model:
+/// the longest of `run_name` and its prefixes up to an `_` whose statistics
+/// `state` holds, as [`generate_operation_state_variables`] seeds them for
+/// every operation. Automatic operations run under their own name, which may
+/// well end in `_` and ten letters of its own. Without seeded statistics, the
+/// name with [`without_run_suffix`] applied.
+fn run_operation<'a>(run_name: &'a str, state: &State) -> &'a str {
+    let seeded = std::iter::once(run_name.len())
+        .chain(run_name.rmatch_indices('_').map(|(end, _)| end))
+        .map(|end| &run_name[..end])
+        .find(|operation| state.contains(&statistics_key(operation)));
+    match seeded {
+        Some(operation) => operation,
+        None => without_run_suffix(run_name),
+    }
+}
+
+/// `run_name` with the run's `_{nanoid}` suffix taken off. A name without one
+/// is taken as it is.
+fn without_run_suffix(run_name: &str) -> &str {
+    match run_name.len().checked_sub(running::plan_runner::PLAN_STEP_SUFFIX_LEN) {
+        Some(split_at) if split_at > 0 && run_name.is_char_boundary(split_at) => {
+            let (operation, suffix) = run_name.split_at(split_at);
+            let is_run_suffix = suffix.starts_with('_')
+                && suffix[1..].chars().all(|c| NANOID_ALPHABET.contains(&c));
+            match is_run_suffix {
+                true => operation,
+                false => run_name,
+            }
+        }
+        _ => run_name,
+    }
+}
+
+/// `state` with a run of `run_name` that ended in `outcome` after executing
+/// for `duration_ms` added to its operation's statistics.
+pub(super) fn record_run(
+    run_name: &str,
+    state: &State,
+    outcome: RunOutcome,
+    duration_ms: i64,
+) -> State {
+    let key = statistics_key(run_operation(run_name, state));
+    let mut statistics = state
+        .state
+        .get(&key)
+        .and_then(|assignment| OperationStatistics::from_spvalue(&assignment.val))
+        .unwrap_or_default();
+    statistics.record(outcome, duration_ms);
+    let mut new_state = state.clone();
+    new_state.extend_mut(
+        State::from_vec(&vec![(SPVariable::new(&key, SPValueType::Map), statistics.to_spvalue())]),
+        true,
+    );
+    new_state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statistics(runs: &[(RunOutcome, i64)]) -> OperationStatistics {
+        let mut statistics = OperationStatistics::default();
+        for (outcome, duration_ms) in runs {
+            statistics.record(*outcome, *duration_ms);
+        }
+        statistics
+    }
+
+    #[test]
+    fn rates_and_times_are_taken_over_the_latest_runs() {
+        let mut runs: Vec<(RunOutcome, i64)> =
+            (1..=18).map(|ms| (RunOutcome::Completed, ms * 100)).collect();
+        runs.push((RunOutcome::Failed, 50));
+        runs.push((RunOutcome::Timedout, 5000));
+        let statistics = statistics(&runs);
+
+        assert_eq!(statistics.count, 20);
+        assert_eq!(statistics.success_rate(), 0.9);
+        assert_eq!(statistics.failure_rate(), 0.05);
+        assert_eq!(statistics.timeout_rate(), 0.05);
+        assert_eq!(statistics.mean_ms(), Some(950.0));
+        assert_eq!(statistics.p50_ms(), Some(900));
+        assert_eq!(statistics.p95_ms(), Some(1800));
+        assert_eq!(statistics.max_ms(), Some(1800));
+    }
+
+    #[test]
+    fn the_window_rolls_but_the_count_does_not() {
+        let mut runs = vec![(RunOutcome::Failed, 10); STATISTICS_WINDOW];
+        runs.extend(vec![(RunOutcome::Completed, 10); STATISTICS_WINDOW]);
+        let statistics = statistics(&runs);
+
+        assert_eq!(statistics.count, 2 * STATISTICS_WINDOW as i64);
+        assert_eq!(statistics.runs.len(), STATISTICS_WINDOW);
+        assert_eq!(statistics.success_rate(), 1.0);
+        assert_eq!(OperationStatistics::default().p95_ms(), None);
+    }
+
+    #[test]
+    fn statistics_round_trip_and_gather_every_run_of_an_operation() {
+        let state = record_run("op_weigh_A1b2C3d4E5", &State::new(), RunOutcome::Completed, 300);
+        let state = record_run("op_weigh_Z9y8X7w6V5", &state, RunOutcome::Cancelled, 20);
+
+        let stored = state.get_value("op_weigh_stats", "test").unwrap();
+        let statistics = OperationStatistics::from_spvalue(&stored).unwrap();
+        let expected = self::statistics(&[(RunOutcome::Completed, 300), (RunOutcome::Cancelled, 20)]);
+        assert_eq!(statistics, expected);
+        assert_eq!(
+            stored.get_path(&[PathSegment::Field("p50_ms".to_string())]),
+            Some(300.to_spvalue())
+        );
+        assert_eq!(without_run_suffix("op_weigh"), "op_weigh");
+        assert_eq!(without_run_suffix("op_pick_everything_A1b2C3d4E5"), "op_pick_everything");
+    }
+
+    /// An automatic operation runs under its own name, and `everything` is as
+    /// good a run suffix as any: the seeded statistics say whose run it was.
+    #[test]
+    fn an_unsuffixed_run_is_recorded_under_its_own_name() {
+        let seeded = State::from_vec(&vec![
+            (mv!("op_pick_stats"), OperationStatistics::default().to_spvalue()),
+            (mv!("op_pick_everything_stats"), OperationStatistics::default().to_spvalue()),
+        ]);
+        let state = record_run("op_pick_everything", &seeded, RunOutcome::Completed, 300);
+        let state = record_run("op_pick_everything_A1b2C3d4E5", &state, RunOutcome::Failed, 20);
+
+        let statistics = |key: &str| {
+            OperationStatistics::from_spvalue(&state.get_value(key, "test").unwrap()).unwrap()
+        };
+        assert_eq!(statistics("op_pick_everything_stats").count, 2);
+        assert_eq!(statistics("op_pick_stats").count, 0);
+    }
+}
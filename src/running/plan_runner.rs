@@ -2,7 +2,9 @@
 //!
 //! The plan runner walks `{sp_id}_plan` one step at a time, driving each
 //! operation through [`process_operation`](crate::running::process_operation)
-//! and advancing `{sp_id}_plan_current_step` when it terminates.
+//! and advancing `{sp_id}_plan_current_step` when it terminates. When a plan
+//! ends cancelled or failed, the [compensations](crate::running::compensation)
+//! of its finished steps run before the next plan may start.
 
 use crate::{running::process_operation::OperationProcessingType, *};
 use crate::SPConnection;
@@ -15,9 +17,11 @@ use std::sync::Arc;
 /// `{sp_id}_plan_current_step`, and writes back the step, `{sp_id}_plan_state`
 /// and the per-operation state. The `sp_id` and the operations to look up both
 /// come from `model`, the Redis connection from `connection_manager`; log output
-/// goes to the `{sp_id}_op_runner` target.
+/// goes to the `{sp_id}_op_runner` target. `handlers` are fired as the
+/// operations change state; see [`OperationHandlers`].
 pub async fn planned_operation_runner(
     model: &Model,
+    handlers: &OperationHandlers,
     connection_manager: &Arc<ConnectionManager>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let sp_id = &model.name;
@@ -30,6 +34,7 @@ pub async fn planned_operation_runner(
     let mut con = connection_manager.get_connection().await;
 
     let static_keys = plan_runner_static_keys(sp_id, &model);
+    let compensation_key = format!("{}_plan_compensation", sp_id);
     let mut keys = static_keys.clone();
     let mut active_plan: Vec<String> = vec![];
     let read_full_state = read_full_state_enabled();
@@ -58,8 +63,10 @@ pub async fn planned_operation_runner(
         // whose bookkeeping variables have to be in the key set before
         // `process_plan_tick` reads them - reading a variable that is not in
         // the state panics - so rebuild and re-read once when it changes.
+        // Queued compensations are uniquified the same way.
         if !read_full_state {
-            let plan = read_plan(&state, sp_id, &log_target);
+            let mut plan = read_plan(&state, sp_id, &log_target);
+            plan.extend(read_compensation(&state, &compensation_key, &log_target));
             if plan != active_plan {
                 keys = keys_with_active_operations(&static_keys, &plan);
                 active_plan = plan;
@@ -77,12 +84,14 @@ pub async fn planned_operation_runner(
             &model,
             &state,
             tick_elapsed_ms,
+            handlers,
             &log_target,
         )
         .await;
-        let modified_state = state.get_diff_partial_state(&new_state);
+        let modified_state = state.get_diff_partial_state_and_add_missing(&new_state);
         if !modified_state.state.is_empty() {
             activity_log::log_state_diff(&log_target, &state, &modified_state);
+            model.warn_foreign_writes(sp_id, &modified_state, &log_target);
             StateManager::set_state(&mut con, &modified_state).await;
         }
     }
@@ -90,7 +99,7 @@ pub async fn planned_operation_runner(
 
 /// Length of the `_{nanoid}` suffix that `handle_replan_request` appends to an
 /// operation name when it instantiates a plan step.
-const PLAN_STEP_SUFFIX_LEN: usize = 1 + 10;
+pub(crate) const PLAN_STEP_SUFFIX_LEN: usize = 1 + 10;
 
 /// Find the model operation a plan step was instantiated from.
 fn find_step_operation<'a>(operations: &'a [Operation], step: &str) -> Option<&'a Operation> {
@@ -114,6 +123,23 @@ fn find_step_operation<'a>(operations: &'a [Operation], step: &str) -> Option<&'
         .max_by_key(|op| op.name.len())
 }
 
+/// Queue the compensations of the plan steps `done`, which were completed or
+/// bypassed before the plan was aborted. A bypassed step may have half-done
+/// its work, so it is compensated too.
+fn compensate_plan(
+    operations: &[Operation],
+    done: &[String],
+    compensation_key: &str,
+    state: &State,
+    log_target: &str,
+) -> State {
+    let completed: Vec<&Operation> = done
+        .iter()
+        .filter_map(|step| find_step_operation(operations, step))
+        .collect();
+    running::compensation::begin_compensation(compensation_key, &completed, state, log_target)
+}
+
 /// The current plan as a list of (uniquified) operation names.
 ///
 /// Used both by the tick itself and by the runner loop, which needs the step
@@ -133,8 +159,30 @@ async fn process_plan_tick(
     model: &Model,
     state: &State,
     tick_elapsed_ms: i64,
+    handlers: &OperationHandlers,
     log_target: &str,
 ) -> State {
+    // A plan that was cancelled or failed is compensated before anything else
+    // happens, so a new plan waits in `initial` until that is over.
+    let compensation_key = format!("{}_plan_compensation", sp_id);
+    if !read_compensation(state, &compensation_key, log_target).is_empty() {
+        let (new_state, compensated) = running::compensation::process_compensation_tick(
+            sp_id,
+            state.clone(),
+            &compensation_key,
+            &compensation_operations(model),
+            tick_elapsed_ms,
+            handlers,
+            log_target,
+        )
+        .await;
+        if !compensated.is_empty() {
+            let keys = running::compensation::compensation_keys(&compensated);
+            StateManager::apply(&mut con, &State::new(), &[&keys]).await;
+        }
+        return new_state;
+    }
+
     let mut new_state = state.clone();
     let planner_state =
         state.get_string_or_default_to_unknown(&format!("{}_planner_state", sp_id), &log_target);
@@ -174,18 +222,26 @@ async fn process_plan_tick(
                     Some(operation) => {
                         let mut uq_operation = operation.clone();
                         uq_operation.name = op_name.to_owned();
+                        // Before processing, so a `skip` or `bypass` is acted on
+                        // by the same tick that advances past the step.
+                        new_state = running::dashboard::apply_plan_step_command(
+                            sp_id,
+                            new_state,
+                            &uq_operation,
+                            handlers,
+                            log_target,
+                        );
                         new_state = running::process_operation::process_operation(
                             &sp_id,
                             new_state,
                             &uq_operation,
                             OperationProcessingType::Planned,
-                            Some(&mut plan_current_step),
-                            Some(&mut plan_state_str),
+                            Some((&mut plan_current_step, &mut plan_state_str)),
                             tick_elapsed_ms,
+                            handlers,
                             log_target,
                         )
                         .await;
-
                     }
                     None => {
                         log::error!("Operation '{}' not found in model!", op_name);
@@ -195,8 +251,32 @@ async fn process_plan_tick(
             } else {
                 plan_state_str = PlanState::Completed.to_string();
             }
+            if plan_state_str == PlanState::Failed.to_string()
+                || plan_state_str == PlanState::Cancelled.to_string()
+            {
+                new_state = compensate_plan(
+                    &model.operations,
+                    &plan[..(plan_current_step as usize).min(plan.len())],
+                    &compensation_key,
+                    &new_state,
+                    log_target,
+                );
+            }
+        }
+        _ => {
+            // An abort left pending would take down the next plan the moment
+            // it started, so with no plan running it is answered and dropped.
+            let command = read_dashboard_command(sp_id, &new_state, log_target);
+            if command == DashboardCommand::AbortPlan {
+                acknowledge_dashboard_command(
+                    sp_id,
+                    &mut new_state,
+                    &command,
+                    "no plan is executing.",
+                    log_target,
+                );
+            }
         }
-        _ => {}
     }
 
     // Guarded, like `auto_operation_runner` does it: on a tick with nothing
@@ -445,7 +525,7 @@ mod plan_runner_tests {
     fn spawn_runner(manager: &Arc<ConnectionManager>, model: Model) -> tokio::task::JoinHandle<()> {
         let manager = Arc::clone(manager);
         tokio::spawn(async move {
-            let _ = planned_operation_runner(&model, &manager).await;
+            let _ = planned_operation_runner(&model, &OperationHandlers::default(), &manager).await;
         })
     }
 
@@ -700,7 +780,7 @@ mod plan_runner_tests {
         );
 
         let tick_con = manager.get_connection().await;
-        let _ = process_plan_tick(SP, tick_con, &model, &state, 100, TARGET).await;
+        let _ = process_plan_tick(SP, tick_con, &model, &state, 100, &OperationHandlers::default(), TARGET).await;
 
         // The tick's own DEL is pipelined but still awaited inside
         // `process_plan_tick`, so no extra wait should be needed - but give it
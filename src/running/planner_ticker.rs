@@ -97,10 +97,13 @@ pub async fn planner_ticker(
             continue;
         }
 
-        let state = match StateManager::get_state_for_keys(&mut con, &keys, &log_target).await {
+        let mut state = match StateManager::get_state_for_keys(&mut con, &keys, &log_target).await {
             Some(s) => s,
             None => continue,
         };
+        // Redis only keeps names and types; the goal is checked against the
+        // domains the model declared.
+        state.declare_mut(&model.variables);
         let old_info = state.get_string_or_default_to_unknown(
             &format!("{}_planner_information", sp_id),
             &log_target,
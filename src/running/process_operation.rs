@@ -7,6 +7,7 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::running::operation_statistics::record_run;
 use crate::*;
 
 /// Which runner an operation is being processed on behalf of.
@@ -29,26 +30,119 @@ fn info_already_reads_as(info: &str, before: &str, op_name: &str, after: &str) -
         .map_or(false, |rest| rest.starts_with(after))
 }
 
+/// The failing leaves of every precondition, as `'start': ready = true (ready
+/// is false)`, one precondition after the other.
+fn blocking_preconditions(operation: &Operation, state: &State, log_target: &str) -> String {
+    let blocked: Vec<String> = operation
+        .preconditions
+        .iter()
+        .map(|precondition| {
+            let guard = Predicate::AND(vec![
+                precondition.guard.clone(),
+                precondition.runner_guard.clone(),
+            ]);
+            let explanation = guard.explain(state, log_target);
+            let leaves: Vec<String> = explanation
+                .failing_leaves()
+                .iter()
+                .map(|leaf| leaf.to_string())
+                .collect();
+            format!("'{}': {}", precondition.name, leaves.join(", "))
+        })
+        .collect();
+    match blocked.is_empty() {
+        true => "no preconditions".to_string(),
+        false => blocked.join("; "),
+    }
+}
+
+/// Where a failed or timed-out operation is in the wait its [`RetryPolicy`]
+/// puts before the next retry.
+enum RetryWait {
+    /// The wait of this many milliseconds began this tick.
+    Began(i64),
+    /// The wait is still running.
+    Waiting,
+    /// There is nothing (more) to wait for: retry now.
+    Over,
+}
+
+/// Start, age or end the wait before retry number `retry` of `operation`. The
+/// policy's recovery transition is taken as the wait begins, or right away if
+/// there is no wait.
+fn wait_for_retry(
+    operation: &Operation,
+    state: &mut State,
+    retry: i64,
+    tick_elapsed_ms: i64,
+    log_target: &str,
+) -> RetryWait {
+    let Some(policy) = &operation.retry_policy else {
+        return RetryWait::Over;
+    };
+    let remaining_ms = match state.state.get(&policy.wait().name).map(|a| &a.val) {
+        Some(SPValue::Int64(IntOrUnknown::Int64(ms))) => Some(*ms),
+        _ => None,
+    };
+    let (wait, outcome) = match remaining_ms {
+        None => {
+            *state = policy.recover(state, log_target);
+            match policy.delay_ms_before(retry) {
+                delay_ms if delay_ms > 0 => (delay_ms.to_spvalue(), RetryWait::Began(delay_ms)),
+                _ => (SPValue::Int64(IntOrUnknown::UNKNOWN), RetryWait::Over),
+            }
+        }
+        Some(ms) if ms > tick_elapsed_ms => {
+            ((ms - tick_elapsed_ms).to_spvalue(), RetryWait::Waiting)
+        }
+        Some(_) => (SPValue::Int64(IntOrUnknown::UNKNOWN), RetryWait::Over),
+    };
+    state.extend_mut(State::from_vec(&vec![(policy.wait(), wait)]), true);
+    outcome
+}
+
+/// Publish why the current attempt of `operation` failed, if it has a
+/// [`RetryPolicy`] to read it.
+fn record_failure_cause(operation: &Operation, state: &mut State, cause: &str) {
+    if let Some(policy) = &operation.retry_policy {
+        state.extend_mut(
+            State::from_vec(&vec![(policy.failure_cause(), cause.to_spvalue())]),
+            true,
+        );
+    }
+}
+
+/// Whether the [`RetryPolicy`] of `operation`, if any, lets it retry.
+fn retry_allowed(operation: &Operation, state: &State, log_target: &str) -> bool {
+    operation
+        .retry_policy
+        .as_ref()
+        .is_none_or(|policy| policy.allows_retry(state, log_target))
+}
+
 /// Advances one operation by a single tick and returns the updated state.
 ///
 /// Dispatches on the operation's current lifecycle state: starts it when it is
 /// enabled, ages the executing and disabled timers by `tick_elapsed_ms`, applies
 /// timeouts and retries, and terminates it when it reaches an end state. For
-/// [`OperationProcessingType::Planned`] it also advances `plan_current_step` and
-/// pushes failures and cancellations into `plan_state`.
+/// [`OperationProcessingType::Planned`] it also advances the plan's current step
+/// and pushes failures and cancellations into its state, the pair in `plan`. A
+/// lifecycle change this tick fires the matching `handlers`; see
+/// [`OperationHandlers`].
 pub(super) async fn process_operation(
     sp_id: &str,
     mut new_state: State,
     operation: &Operation,
     operation_processing_type: OperationProcessingType,
-    plan_current_step: Option<&mut i64>,
-    plan_state: Option<&mut String>,
+    plan: Option<(&mut i64, &mut String)>,
     // Wall-clock milliseconds the caller's tick actually took. The elapsed
     // counters advance by this, so they track real time no matter which runner
     // is driving the operation or how badly a tick slipped.
     tick_elapsed_ms: i64,
+    handlers: &OperationHandlers,
     log_target: &str,
 ) -> State {
+    let (plan_current_step, plan_state) = plan.unzip();
     let operation_state =
         new_state.get_string_or_default_to_unknown(&format!("{}", operation.name), &log_target);
 
@@ -77,16 +171,48 @@ pub(super) async fn process_operation(
         &log_target,
     );
 
+    // The attempt is published for guards to read; the wait before a retry
+    // only lives as long as the failure it follows.
+    if let Some(policy) = &operation.retry_policy {
+        let attempt = 1 + operation_failure_retry_counter + operation_timeout_retry_counter;
+        let mut published = vec![(policy.attempt(), attempt.to_spvalue())];
+        if !matches!(
+            OperationState::from_str(&operation_state),
+            OperationState::Failed | OperationState::Timedout
+        ) {
+            published.push((policy.wait(), SPValue::Int64(IntOrUnknown::UNKNOWN)));
+        }
+        new_state.extend_mut(State::from_vec(&published), true);
+    }
+
+    // Read once: every arm that can cancel checks it first, so when it holds
+    // the operation is cancelled this tick whichever arm it lands in.
+    let cancel_requested = operation.can_be_cancelled(sp_id, &new_state, log_target);
+    // Step mode holds planned and SOP operations before they start, never
+    // automatic ones.
+    let stepped = !matches!(operation_processing_type, OperationProcessingType::Automatic);
+
     let mut logging_log = "".to_string();
     let mut op_info_level = log::Level::Info;
     match OperationState::from_str(&operation_state) {
         OperationState::Initial => {
-            if operation.can_be_cancelled(&sp_id, &new_state, &log_target) {
+            if cancel_requested {
                 new_state = operation.cancel(&new_state, &log_target);
                 new_op_info = format!("Cancelling operation '{}'.", operation.name).to_string();
                 logging_log = format!("Cancelling");
                 op_info_level = log::Level::Warn;
+            } else if stepped && !step_confirmed(sp_id, &new_state, operation, log_target) {
+                // Held in `initial`, not disabled: the operator decides when
+                // to try, and the disabled timeout must not run meanwhile.
+                new_op_info = awaiting_confirmation_information(operation, &new_state, log_target);
+                if new_op_info != old_operation_information {
+                    logging_log = "Awaiting confirmation".to_string();
+                }
+                op_info_level = log::Level::Info;
             } else if operation.eval(&new_state, &log_target) {
+                if stepped {
+                    take_step_confirmation(sp_id, &mut new_state, operation, log_target);
+                }
                 new_state = operation.start(&new_state, &log_target);
                 new_op_info = format!("Starting initialized operation '{}'.", operation.name);
                 logging_log = format!("Starting");
@@ -99,19 +225,36 @@ pub(super) async fn process_operation(
             }
         }
         OperationState::Disabled => {
-            elapased_disabled_ms += tick_elapsed_ms;
-            if operation.can_be_cancelled(&sp_id, &new_state, &log_target) {
+            let ready = operation.eval(&new_state, &log_target);
+            // Disabled when step mode came on, or confirmed before its guard
+            // held: held here like in `initial` once it could start, with the
+            // disabled timeout paused.
+            let held = ready && stepped && !step_confirmed(sp_id, &new_state, operation, log_target);
+            if !held {
+                elapased_disabled_ms += tick_elapsed_ms;
+            }
+            if cancel_requested {
                 new_state = operation.cancel(&new_state, &log_target);
                 new_op_info = format!("Cancelling operation '{}'.", operation.name).to_string();
                 logging_log = format!("Cancelling");
                 op_info_level = log::Level::Warn;
+            } else if held {
+                new_op_info = awaiting_confirmation_information(operation, &new_state, log_target);
+                if new_op_info != old_operation_information {
+                    logging_log = "Awaiting confirmation".to_string();
+                }
+                op_info_level = log::Level::Info;
             } else if operation.can_be_timedout(&new_state, &log_target) {
+                record_failure_cause(operation, &mut new_state, "timeout");
                 new_state = operation.timeout(&new_state, &log_target);
                 new_op_info =
                     format!("Timeout for disabled operation '{}'.", operation.name).to_string();
                 logging_log = format!("Timeout");
                 op_info_level = log::Level::Warn;
-            } else if operation.eval(&new_state, &log_target) {
+            } else if ready {
+                if stepped {
+                    take_step_confirmation(sp_id, &mut new_state, operation, log_target);
+                }
                 new_state = operation.start(&new_state, &log_target);
                 new_op_info = format!("Starting disabled operation '{}'.", operation.name);
                 logging_log = format!("Starting");
@@ -119,15 +262,14 @@ pub(super) async fn process_operation(
             } else {
                 op_info_level = log::Level::Warn;
 
-                // Rendering the guards is a recursive tree walk, and an
-                // operation can sit here for minutes, so the message is built
-                // once and skipped for as long as it still stands.
-                if !info_already_reads_as(
-                    &old_operation_information,
-                    "Operation '",
-                    &operation.name,
-                    "' disabled.",
-                ) {
+                // What blocks the operation can change while it waits, so the
+                // failing leaves are worked out every tick. Rendering the full
+                // guard trees is a recursive walk on top of that, and an
+                // operation can sit here for minutes, so the message is only
+                // rebuilt when the blocking part of it changes.
+                let blocked_by = blocking_preconditions(operation, &new_state, log_target);
+                let prefix = format!("Operation '{}' disabled. Blocked by: {}.", operation.name, blocked_by);
+                if !old_operation_information.starts_with(&prefix) {
                     let mut or_clause = vec![];
                     let mut or_clause_full = vec![];
                     for precondition in &operation.preconditions {
@@ -138,8 +280,8 @@ pub(super) async fn process_operation(
                         ]));
                     }
                     new_op_info = format!(
-                        "Operation '{}' disabled. Please satisfy the runner guard: \n       {}\n       Debug full guard: \n       {}",
-                        operation.name,
+                        "{}\n       Please satisfy the runner guard: \n       {}\n       Debug full guard: \n       {}",
+                        prefix,
                         Predicate::OR(or_clause),
                         Predicate::OR(or_clause_full)
                     );
@@ -149,17 +291,25 @@ pub(super) async fn process_operation(
         }
         OperationState::Executing => {
             elapased_executing_ms += tick_elapsed_ms;
-            if operation.can_be_cancelled(&sp_id, &new_state, &log_target) {
+            if cancel_requested {
                 new_state = operation.cancel(&new_state, &log_target);
                 new_op_info = format!("Cancelling operation '{}'.", operation.name).to_string();
                 logging_log = format!("Cancelling");
                 op_info_level = log::Level::Warn;
             } else if operation.can_be_failed(&new_state, &log_target) {
+                if let Some(cause) = operation
+                    .failure_transitions
+                    .iter()
+                    .find(|transition| transition.eval(&new_state, log_target))
+                {
+                    record_failure_cause(operation, &mut new_state, &cause.name);
+                }
                 new_state = operation.fail(&new_state, &log_target);
                 new_op_info = format!("Failing operation '{}'.", operation.name).to_string();
                 logging_log = format!("Failing");
                 op_info_level = log::Level::Warn;
             } else if operation.can_be_timedout(&new_state, &log_target) {
+                record_failure_cause(operation, &mut new_state, "timeout");
                 new_state = operation.timeout(&new_state, &log_target);
                 new_op_info =
                     format!("Timeout for executing operation '{}'.", operation.name).to_string();
@@ -202,14 +352,34 @@ pub(super) async fn process_operation(
                     *plan_current_step += 1;
                 }
             }
+            if let Some(results) = &operation.results {
+                new_state = results.record(
+                    &new_state,
+                    std::time::SystemTime::now(),
+                    elapased_executing_ms,
+                    log_target,
+                );
+            }
             new_op_info = format!("Operation '{}' completed.", operation.name);
+            new_state = record_run(
+                &operation.name,
+                &new_state,
+                RunOutcome::Completed,
+                elapased_executing_ms,
+            );
             logging_log = format!("Completed");
             op_info_level = log::Level::Info;
 
             new_state = operation.terminate(&new_state, TerminationReason::Completed, &log_target);
         }
         OperationState::Bypassed => {
-            if operation.can_be_cancelled(&sp_id, &new_state, &log_target) {
+            new_state = record_run(
+                &operation.name,
+                &new_state,
+                RunOutcome::Bypassed,
+                elapased_executing_ms,
+            );
+            if cancel_requested {
                 new_state = operation.cancel(&new_state, &log_target);
                 new_op_info = format!("Cancelling operation '{}'.", operation.name).to_string();
                 logging_log = format!("Cancelling");
@@ -229,27 +399,43 @@ pub(super) async fn process_operation(
             new_state = operation.terminate(&new_state, TerminationReason::Bypassed, &log_target);
         }
         OperationState::Timedout => {
-            if operation.can_be_cancelled(&sp_id, &new_state, &log_target) {
+            if cancel_requested {
                 new_state = operation.cancel(&new_state, &log_target);
                 new_op_info = format!("Cancelling operation '{}'.", operation.name).to_string();
                 logging_log = format!("Cancelling");
                 op_info_level = log::Level::Warn;
-            } else if operation_timeout_retry_counter < operation.timeout_retries {
-                operation_timeout_retry_counter += 1;
-                new_op_info = format!(
-                    "Retrying operation (timeout) '{}'. Retry {} out of {}.",
-                    operation.name, operation_timeout_retry_counter, operation.timeout_retries
-                );
-                logging_log = format!(
-                    "Retrying {}/{}",
-                    operation_timeout_retry_counter, operation.timeout_retries
-                );
-                op_info_level = log::Level::Warn;
-                new_state = operation.retry(&new_state, &log_target);
-                new_state.update_mut(
-                    &format!("{}_timeout_retry_counter", operation.name),
-                    operation_timeout_retry_counter.to_spvalue(),
-                );
+            } else if operation_timeout_retry_counter < operation.timeout_retries
+                && retry_allowed(operation, &new_state, log_target)
+            {
+                let retry = operation_timeout_retry_counter + 1;
+                match wait_for_retry(operation, &mut new_state, retry, tick_elapsed_ms, log_target) {
+                    RetryWait::Began(delay_ms) => {
+                        new_op_info = format!(
+                            "Waiting {} ms to retry operation (timeout) '{}'. Retry {} out of {}.",
+                            delay_ms, operation.name, retry, operation.timeout_retries
+                        );
+                        logging_log = format!("Waiting {} ms to retry", delay_ms);
+                        op_info_level = log::Level::Warn;
+                    }
+                    RetryWait::Waiting => (),
+                    RetryWait::Over => {
+                        operation_timeout_retry_counter = retry;
+                        new_op_info = format!(
+                            "Retrying operation (timeout) '{}'. Retry {} out of {}.",
+                            operation.name, operation_timeout_retry_counter, operation.timeout_retries
+                        );
+                        logging_log = format!(
+                            "Retrying {}/{}",
+                            operation_timeout_retry_counter, operation.timeout_retries
+                        );
+                        op_info_level = log::Level::Warn;
+                        new_state = operation.retry(&new_state, &log_target);
+                        new_state.update_mut(
+                            &format!("{}_timeout_retry_counter", operation.name),
+                            operation_timeout_retry_counter.to_spvalue(),
+                        );
+                    }
+                }
             } else if operation.can_be_bypassed {
                 new_state = operation.bypass(&new_state, &log_target);
                 new_op_info = format!("Operation '{}' timedout. Bypassing.", operation.name);
@@ -257,33 +443,55 @@ pub(super) async fn process_operation(
                 op_info_level = log::Level::Warn;
             } else {
                 new_state = operation.fatal(&new_state, &log_target);
+                new_state = record_run(
+                    &operation.name,
+                    &new_state,
+                    RunOutcome::Timedout,
+                    elapased_executing_ms,
+                );
                 new_op_info = format!("Operation '{}' timedout.", operation.name);
                 logging_log = format!("Fatal timeout");
                 op_info_level = log::Level::Warn;
             }
         }
         OperationState::Failed => {
-            if operation.can_be_cancelled(&sp_id, &new_state, &log_target) {
+            if cancel_requested {
                 new_state = operation.cancel(&new_state, &log_target);
                 new_op_info = format!("Cancelling operation '{}'.", operation.name).to_string();
                 logging_log = format!("Cancelling");
                 op_info_level = log::Level::Warn;
-            } else if operation_failure_retry_counter < operation.failure_retries {
-                operation_failure_retry_counter += 1;
-                new_op_info = format!(
-                    "Retrying operation (failure) '{}'. Retry {} out of {}.",
-                    operation.name, operation_failure_retry_counter, operation.failure_retries
-                );
-                logging_log = format!(
-                    "Retrying {}/{}",
-                    operation_failure_retry_counter, operation.failure_retries
-                );
-                op_info_level = log::Level::Warn;
-                new_state = operation.retry(&new_state, &log_target);
-                new_state.update_mut(
-                    &format!("{}_failure_retry_counter", operation.name),
-                    operation_failure_retry_counter.to_spvalue(),
-                );
+            } else if operation_failure_retry_counter < operation.failure_retries
+                && retry_allowed(operation, &new_state, log_target)
+            {
+                let retry = operation_failure_retry_counter + 1;
+                match wait_for_retry(operation, &mut new_state, retry, tick_elapsed_ms, log_target) {
+                    RetryWait::Began(delay_ms) => {
+                        new_op_info = format!(
+                            "Waiting {} ms to retry operation (failure) '{}'. Retry {} out of {}.",
+                            delay_ms, operation.name, retry, operation.failure_retries
+                        );
+                        logging_log = format!("Waiting {} ms to retry", delay_ms);
+                        op_info_level = log::Level::Warn;
+                    }
+                    RetryWait::Waiting => (),
+                    RetryWait::Over => {
+                        operation_failure_retry_counter = retry;
+                        new_op_info = format!(
+                            "Retrying operation (failure) '{}'. Retry {} out of {}.",
+                            operation.name, operation_failure_retry_counter, operation.failure_retries
+                        );
+                        logging_log = format!(
+                            "Retrying {}/{}",
+                            operation_failure_retry_counter, operation.failure_retries
+                        );
+                        op_info_level = log::Level::Warn;
+                        new_state = operation.retry(&new_state, &log_target);
+                        new_state.update_mut(
+                            &format!("{}_failure_retry_counter", operation.name),
+                            operation_failure_retry_counter.to_spvalue(),
+                        );
+                    }
+                }
             } else {
                 if operation.can_be_bypassed {
                     new_state = operation.bypass(&new_state, &log_target);
@@ -295,6 +503,12 @@ pub(super) async fn process_operation(
                     op_info_level = log::Level::Warn;
                 } else {
                     new_state = operation.fatal(&new_state, &log_target);
+                    new_state = record_run(
+                        &operation.name,
+                        &new_state,
+                        RunOutcome::Failed,
+                        elapased_executing_ms,
+                    );
                     new_op_info =
                         format!("Operation '{}' has no more retries left.", operation.name);
                     logging_log = format!("Fatal failure");
@@ -328,6 +542,12 @@ pub(super) async fn process_operation(
             new_state = operation.terminate(&new_state, TerminationReason::Fatal, &log_target);
         }
         OperationState::Cancelled => {
+            new_state = record_run(
+                &operation.name,
+                &new_state,
+                RunOutcome::Cancelled,
+                elapased_executing_ms,
+            );
             new_op_info = format!(
                 "Operation '{}' cancelled. Stopping execution.",
                 operation.name
@@ -380,6 +600,17 @@ pub(super) async fn process_operation(
         }
     }
 
+    if cancel_requested {
+        let command = read_dashboard_command(sp_id, &new_state, log_target);
+        acknowledge_dashboard_command(
+            sp_id,
+            &mut new_state,
+            &command,
+            &format!("cancelled '{}'.", operation.name),
+            log_target,
+        );
+    }
+
     if new_op_info != old_operation_information {
         match op_info_level {
             log::Level::Info => log::info!(target: &log_target, "{}", new_op_info),
@@ -422,6 +653,13 @@ pub(super) async fn process_operation(
         elapased_disabled_ms.to_spvalue(),
     );
 
+    handlers.dispatch_transition(
+        &operation.name,
+        &OperationState::from_str(&operation_state),
+        &new_state,
+        log_target,
+    );
+
     new_state
 }
 
@@ -549,9 +787,9 @@ mod tests {
             operation,
             OperationProcessingType::Automatic,
             None,
-            None,
             // a 200 ms tick, matching the runners' cadence
             200,
+            &OperationHandlers::default(),
             TARGET,
         )
         .await
@@ -699,8 +937,8 @@ mod elapsed_tests {
             operation,
             OperationProcessingType::Automatic,
             None,
-            None,
             tick_elapsed_ms,
+            &OperationHandlers::default(),
             TARGET,
         )
         .await
@@ -883,8 +1121,8 @@ mod state_machine_tests {
             operation,
             OperationProcessingType::Automatic,
             None,
-            None,
             elapsed_ms,
+            &OperationHandlers::default(),
             TARGET,
         )
         .await
@@ -901,9 +1139,9 @@ mod state_machine_tests {
             state,
             operation,
             OperationProcessingType::Planned,
-            Some(step),
-            Some(plan_state),
+            Some((step, plan_state)),
             10,
+            &OperationHandlers::default(),
             TARGET,
         )
         .await
@@ -1001,9 +1239,9 @@ mod state_machine_tests {
     // --------------------------------------------------------------- Disabled
 
     /// The steady state: a disabled operation builds its "please satisfy the
-    /// runner guard" message once and then leaves it alone. This is the
-    /// allocation-free skip that the `info_already_reads_as` check exists for,
-    /// and the property it has to preserve is that the message stops changing.
+    /// runner guard" message once and then leaves it alone while what blocks
+    /// it stays the same. The property the skip has to preserve is that the
+    /// message stops changing.
     #[tokio::test]
     async fn a_disabled_operation_settles_on_one_message() {
         let (state, operation) = plain();
@@ -1022,6 +1260,31 @@ mod state_machine_tests {
         }
     }
 
+    /// The message leads with the comparisons that fail and the values they
+    /// saw, and follows the state when a different one becomes the blocker.
+    #[tokio::test]
+    async fn a_disabled_operation_names_what_blocks_it() {
+        let (state, operation) = plain();
+
+        let state = tick(state, &operation, 10).await; // -> disabled
+        let state = tick(state, &operation, 10).await;
+        assert!(
+            info(&state).starts_with(&format!(
+                "Operation '{OP}' disabled. Blocked by: 'start': go = true (go is false)."
+            )),
+            "unexpected message: {}",
+            info(&state)
+        );
+
+        let state = set(&state, "go", SPValue::Bool(BoolOrUnknown::UNKNOWN));
+        let state = tick(state, &operation, 10).await;
+        assert!(
+            info(&state).contains("Blocked by: 'start': go = true (go is UNKNOWN)."),
+            "unexpected message: {}",
+            info(&state)
+        );
+    }
+
     /// Time spent disabled accumulates in its own counter, separately from
     /// executing time.
     #[tokio::test]
@@ -1209,14 +1472,15 @@ mod state_machine_tests {
         assert_eq!(step, 4, "a planned operation completing advances the plan");
 
         let mut step = 3;
+        let mut plan_state = PlanState::Executing.to_string();
         let _ = process_operation(
             SP_ID,
             completed,
             &operation,
             OperationProcessingType::SOP,
-            Some(&mut step),
-            None,
+            Some((&mut step, &mut plan_state)),
             10,
+            &OperationHandlers::default(),
             TARGET,
         )
         .await;
@@ -1422,6 +1686,212 @@ mod state_machine_tests {
         assert_eq!(counter(&state, "failure_retry_counter"), 0);
     }
 
+    // -------------------------------------------------------- Dashboard
+
+    fn command(state: &State) -> DashboardCommand {
+        read_dashboard_command(SP_ID, state, TARGET)
+    }
+
+    /// `cancel:<name>` is a one-shot: applied to the operation it names,
+    /// acknowledged, and cleared so the next instance is left alone.
+    #[tokio::test]
+    async fn cancel_by_name_cancels_that_operation_once() {
+        let (state, operation) = plain();
+        let state = in_state(&state, "executing");
+        let state = state.add(
+            SPAssignment::new(
+                SPVariable::new(
+                    &format!("{}_dashboard_command_response", SP_ID),
+                    SPValueType::String,
+                ),
+                SPValue::String(StringOrUnknown::UNKNOWN),
+            ),
+            TARGET,
+        );
+        let state = set(
+            &state,
+            &format!("{}_dashboard_command", SP_ID),
+            "cancel:test".to_spvalue(),
+        );
+
+        let state = tick(state, &operation, 10).await;
+
+        assert_eq!(op_state(&state), "cancelled");
+        assert_eq!(command(&state), DashboardCommand::UNKNOWN);
+        assert_eq!(
+            state.get_string_or_default_to_unknown(
+                &format!("{}_dashboard_command_response", SP_ID),
+                TARGET
+            ),
+            "cancel:test: cancelled 'op_test'."
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_for_another_operation_stays_pending() {
+        let (state, operation) = plain();
+        let state = in_state(&state, "executing");
+        let state = set(
+            &state,
+            &format!("{}_dashboard_command", SP_ID),
+            "cancel:other".to_spvalue(),
+        );
+
+        let state = tick(state, &operation, 10).await;
+
+        assert_eq!(op_state(&state), "executing");
+        assert_eq!(command(&state), DashboardCommand::Cancel("other".to_string()));
+    }
+
+    #[tokio::test]
+    async fn stop_stays_in_force_after_cancelling() {
+        let (state, operation) = plain();
+        let state = stop_pressed(&in_state(&state, "executing"));
+
+        let state = tick(state, &operation, 10).await;
+
+        assert_eq!(op_state(&state), "cancelled");
+        assert_eq!(command(&state), DashboardCommand::Stop);
+    }
+
+    // -------------------------------------------------------- Step mode
+
+    fn step_mode_on(state: &State, confirmation: &str) -> State {
+        let mut state = state.clone();
+        for (name, value) in [
+            ("step_mode", true.to_spvalue()),
+            ("step_confirmation", confirmation.to_spvalue()),
+            ("step_auto_continue", 0.to_spvalue()),
+        ] {
+            state.add_mut(
+                SPAssignment::new(
+                    SPVariable::new(&format!("{SP_ID}_{name}"), value.has_type()),
+                    value,
+                ),
+                TARGET,
+            );
+        }
+        state
+    }
+
+    /// Held in `initial` - not disabled - with the preconditions on show, even
+    /// though the guard holds and it would otherwise start at once.
+    #[tokio::test]
+    async fn step_mode_holds_a_planned_operation_until_confirmed() {
+        let (state, operation) = plain();
+        let state = step_mode_on(&in_state(&set(&state, "go", true.to_spvalue()), "initial"), "none");
+        let (mut step, mut plan_state) = (0, "executing".to_string());
+
+        let state = tick_planned(state, &operation, &mut step, &mut plan_state).await;
+        assert_eq!(op_state(&state), "initial");
+        assert_eq!(
+            info(&state),
+            "Operation 'op_test' is waiting for step confirmation. Preconditions: 'start' holds."
+        );
+
+        let state = set(&state, &format!("{SP_ID}_step_confirmation"), OP.to_spvalue());
+        let state = tick_planned(state, &operation, &mut step, &mut plan_state).await;
+        assert_eq!(op_state(&state), "executing");
+    }
+
+    /// Step mode turned on while an operation sits in `disabled`: once its
+    /// guard holds it waits for confirmation there, its timeout paused.
+    #[tokio::test]
+    async fn step_mode_holds_an_operation_that_was_already_disabled() {
+        let (state, operation) = plain();
+        let state = step_mode_on(&in_state(&set(&state, "go", true.to_spvalue()), "disabled"), "none");
+        let (mut step, mut plan_state) = (0, "executing".to_string());
+
+        let state = tick_planned(state, &operation, &mut step, &mut plan_state).await;
+        assert_eq!(op_state(&state), "disabled");
+        assert_eq!(counter(&state, "elapsed_disabled_ms"), 0);
+        assert_eq!(
+            info(&state),
+            "Operation 'op_test' is waiting for step confirmation. Preconditions: 'start' holds."
+        );
+
+        let state = set(&state, &format!("{SP_ID}_step_confirmation"), OP.to_spvalue());
+        let state = tick_planned(state, &operation, &mut step, &mut plan_state).await;
+        assert_eq!(op_state(&state), "executing");
+    }
+
+    /// A confirmation given before the guard holds is not used up by the
+    /// operation going to `disabled`; it starts it once the guard does hold.
+    #[tokio::test]
+    async fn a_confirmation_is_kept_until_the_operation_starts() {
+        let (state, operation) = plain();
+        let state = step_mode_on(&in_state(&set(&state, "go", false.to_spvalue()), "initial"), OP);
+        let (mut step, mut plan_state) = (0, "executing".to_string());
+        let confirmation = |state: &State| {
+            state.get_string_or_default_to_unknown(&format!("{SP_ID}_step_confirmation"), TARGET)
+        };
+
+        let state = tick_planned(state, &operation, &mut step, &mut plan_state).await;
+        assert_eq!(op_state(&state), "disabled");
+        assert_eq!(confirmation(&state), OP);
+
+        let state = set(&state, "go", true.to_spvalue());
+        let state = tick_planned(state, &operation, &mut step, &mut plan_state).await;
+        assert_eq!(op_state(&state), "executing");
+        assert_eq!(confirmation(&state), "UNKNOWN");
+    }
+
+    #[tokio::test]
+    async fn step_mode_leaves_automatic_operations_alone() {
+        let (state, operation) = plain();
+        let state = step_mode_on(&in_state(&set(&state, "go", true.to_spvalue()), "initial"), "none");
+
+        let state = tick(state, &operation, 10).await;
+
+        assert_eq!(op_state(&state), "executing");
+    }
+
+    // -------------------------------------------------------- Handlers
+
+    /// One notification per lifecycle change, none for a tick that changes
+    /// nothing.
+    #[tokio::test]
+    async fn handlers_hear_about_lifecycle_changes_only() {
+        let (state, operation) = plain();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut handlers = OperationHandlers::default();
+        for event in [OperationEvent::Starting, OperationEvent::Completed] {
+            let sender = sender.clone();
+            handlers.register("test", event, move |notification| {
+                let sender = sender.clone();
+                async move { sender.send(notification.event).unwrap() }
+            });
+        }
+        let tick = |state: State| {
+            let handlers = handlers.clone();
+            let operation = operation.clone();
+            async move {
+                process_operation(
+                    SP_ID,
+                    state,
+                    &operation,
+                    OperationProcessingType::Automatic,
+                    None,
+                    10,
+                    &handlers,
+                    TARGET,
+                )
+                .await
+            }
+        };
+
+        let state = tick(set(&state, "go", true.to_spvalue())).await;
+        let state = tick(state).await;
+        let _ = tick(set(&state, "done", true.to_spvalue())).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let mut events = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events, vec![OperationEvent::Starting, OperationEvent::Completed]);
+    }
+
     // ------------------------------------------------------------------ Fatal
 
     /// A fatal operation fails the *plan*, which is how a dead operation
@@ -1595,4 +2065,124 @@ mod state_machine_tests {
         );
         assert_eq!(counter(&state, "timeout_retry_counter"), 1);
     }
+
+    // ---------------------------------------------------------- Retry policy
+
+    fn with_policy(operation: Operation, state: &State, policy: RetryPolicy) -> (State, Operation) {
+        let mut state = state.clone();
+        state.extend_mut(policy.state_variables(), true);
+        (state, operation.with_retry_policy(policy))
+    }
+
+    fn policy_value(state: &State, suffix: &str) -> Option<SPValue> {
+        state.get_value(&format!("grip_retry_{suffix}"), TARGET)
+    }
+
+    /// The failure is recorded, the recovery taken and the wait sat out in
+    /// `failed` before the retry, and the next attempt is numbered for guards.
+    #[tokio::test]
+    async fn a_retry_policy_waits_recovers_and_counts_attempts() {
+        let (state, operation) = operation(Some(10_000), Some(10_000), Some(2), None, false);
+        let policy = RetryPolicy::new("grip")
+            .with_delay_ms(100)
+            .with_backoff(2, None)
+            .recovering_with(vec![transition("reset", "true", vec!["var:broken <- false"], &state)]);
+        let (state, operation) = with_policy(operation, &state, policy);
+        let state = set(&in_state(&state, "executing"), "broken", true.to_spvalue());
+
+        let state = tick(state, &operation, 10).await;
+        assert_eq!(op_state(&state), "failed");
+        assert_eq!(policy_value(&state, "failure_cause"), Some("fail".to_spvalue()));
+
+        let state = tick(state, &operation, 10).await;
+        assert_eq!(op_state(&state), "failed");
+        assert_eq!(state.get_value("broken", TARGET), Some(false.to_spvalue()));
+        assert_eq!(policy_value(&state, "wait_ms"), Some(100.to_spvalue()));
+        assert_eq!(
+            info(&state),
+            format!("Waiting 100 ms to retry operation (failure) '{OP}'. Retry 1 out of 2.")
+        );
+
+        let state = tick(state, &operation, 60).await;
+        assert_eq!(op_state(&state), "failed");
+        assert_eq!(policy_value(&state, "wait_ms"), Some(40.to_spvalue()));
+
+        let state = tick(state, &operation, 60).await;
+        assert_eq!(op_state(&state), "initial");
+        assert_eq!(counter(&state, "failure_retry_counter"), 1);
+
+        let state = tick(state, &operation, 10).await;
+        assert_eq!(policy_value(&state, "attempt"), Some(2.to_spvalue()));
+        assert_eq!(policy_value(&state, "wait_ms"), Some(SPValue::Int64(IntOrUnknown::UNKNOWN)));
+    }
+
+    /// A cause the policy does not retry for is treated like running out of
+    /// retries.
+    #[tokio::test]
+    async fn a_retry_policy_only_retries_for_the_causes_it_allows() {
+        let (state, operation) = operation(Some(10_000), Some(10_000), None, Some(3), false);
+        let mut cause_state = state.clone();
+        cause_state.extend_mut(RetryPolicy::new("grip").state_variables(), true);
+        let only_failures = pred_parser::pred("var:grip_retry_failure_cause == fail", &cause_state).unwrap();
+        let (state, operation) =
+            with_policy(operation, &state, RetryPolicy::new("grip").retrying_if(only_failures));
+
+        let state = in_state(&state, "executing");
+        let state = set(&state, &format!("{OP}_elapsed_executing_ms"), 20_000.to_spvalue());
+        let state = tick(state, &operation, 10).await;
+        assert_eq!(op_state(&state), "timedout");
+        assert_eq!(policy_value(&state, "failure_cause"), Some("timeout".to_spvalue()));
+
+        let state = tick(state, &operation, 10).await;
+        assert_eq!(op_state(&state), "fatal");
+        assert_eq!(counter(&state, "timeout_retry_counter"), 0);
+    }
+
+    // ---------------------------------------------------------------- Results
+
+    #[tokio::test]
+    async fn completing_captures_the_declared_results() {
+        let (state, operation) = plain();
+        let operation = operation.with_results(vec![bv!("broken")]);
+        let mut state = state;
+        state.extend_mut(operation.results.as_ref().unwrap().state_variables(), true);
+        let state = in_state(&state, "completed");
+        let state = set(&state, &format!("{OP}_elapsed_executing_ms"), 700.to_spvalue());
+
+        let state = tick(state, &operation, 10).await;
+        let result = state.get_value(&format!("{OP}_result"), TARGET).unwrap();
+        assert_eq!(
+            result.get_path(&[PathSegment::Field("broken".to_string())]),
+            Some(false.to_spvalue())
+        );
+        let history = OperationResult::history_from_spvalue(
+            &state.get_value(&format!("{OP}_result_history"), TARGET).unwrap(),
+        );
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].duration_ms, 700);
+    }
+
+    // ------------------------------------------------------------- Statistics
+
+    #[tokio::test]
+    async fn every_run_that_ends_is_counted_once() {
+        let (state, operation) = plain();
+        let state = in_state(&state, "completed");
+        let state = set(&state, &format!("{OP}_elapsed_executing_ms"), 250.to_spvalue());
+
+        let state = tick(state, &operation, 10).await;
+        let state = tick(state, &operation, 10).await;
+        let statistics =
+            OperationStatistics::from_spvalue(&state.get_value(&format!("{OP}_stats"), TARGET).unwrap())
+                .unwrap();
+        assert_eq!(statistics.count, 1);
+        assert_eq!(statistics.max_ms(), Some(250));
+
+        let state = tick(in_state(&state, "cancelled"), &operation, 10).await;
+        let statistics =
+            OperationStatistics::from_spvalue(&state.get_value(&format!("{OP}_stats"), TARGET).unwrap())
+                .unwrap();
+        assert_eq!(statistics.count, 2);
+        assert_eq!(statistics.rate(RunOutcome::Cancelled), 0.5);
+    }
 }
@@ -108,6 +108,7 @@ pub fn model_variable_keys(model: &Model) -> Vec<String> {
         .chain(model.mutexed_auto_operations.iter())
     {
         keys.push(op.name.clone());
+        keys.push(statistics_key(&op.name));
         keys.extend(op.get_all_var_keys());
     }
 
@@ -115,6 +116,13 @@ pub fn model_variable_keys(model: &Model) -> Vec<String> {
         keys.extend(transition.get_all_var_keys());
     }
 
+    // Compensations are uniquified like plan steps, so only their variables
+    // are known up front.
+    for op in compensation_operations(model) {
+        keys.push(statistics_key(&op.name));
+        keys.extend(op.get_all_var_keys());
+    }
+
     for sop in &model.sops {
         // `{sop_id}_sop_information` is keyed by the *template* id.
         keys.push(format!("{}_sop_information", sop.id));
@@ -123,6 +131,8 @@ pub fn model_variable_keys(model: &Model) -> Vec<String> {
         keys.extend(sop.sop.get_all_var_keys());
         for op in get_all_operations_from_sop(&sop.sop) {
             keys.push(op.name.clone());
+            // Runs are named `op_{name}_{nanoid}`; see `uniquify_sop_operations`.
+            keys.push(statistics_key(&format!("op_{}", op.name)));
         }
     }
 
@@ -139,8 +149,16 @@ pub fn sop_runner_static_keys(sp_id: &str, model: &Model) -> Vec<String> {
         format!("{}_sop_state", sp_id),
         format!("{}_sop_enabled", sp_id),
         format!("{}_sop_id", sp_id),
+        format!("{}_sop_compensation", sp_id),
+        format!("{}_sop_compensation_step", sp_id),
+        format!("{}_sop_completion_order", sp_id),
         // read by `Operation::can_be_cancelled` for every operation processed
         format!("{}_dashboard_command", sp_id),
+        format!("{}_dashboard_command_response", sp_id),
+        // read by `take_step_confirmation` before an operation starts
+        format!("{}_step_mode", sp_id),
+        format!("{}_step_confirmation", sp_id),
+        format!("{}_step_auto_continue", sp_id),
     ];
     keys.extend(model_variable_keys(model));
     normalize_keys(keys)
@@ -149,7 +167,10 @@ pub fn sop_runner_static_keys(sp_id: &str, model: &Model) -> Vec<String> {
 /// Keys `auto_operation_runner` reads on every tick regardless of what is
 /// running.
 pub fn auto_operation_runner_static_keys(sp_id: &str, model: &Model) -> Vec<String> {
-    let mut keys = vec![format!("{}_dashboard_command", sp_id)];
+    let mut keys = vec![
+        format!("{}_dashboard_command", sp_id),
+        format!("{}_dashboard_command_response", sp_id),
+    ];
     keys.extend(model_variable_keys(model));
     normalize_keys(keys)
 }
@@ -169,8 +190,15 @@ pub fn plan_runner_static_keys(sp_id: &str, model: &Model) -> Vec<String> {
         format!("{}_plan_current_step", sp_id),
         format!("{}_plan", sp_id),
         format!("{}_terminated_operations", sp_id),
+        format!("{}_plan_compensation", sp_id),
+        format!("{}_plan_compensation_step", sp_id),
         // read by `Operation::can_be_cancelled` for every operation processed
         format!("{}_dashboard_command", sp_id),
+        format!("{}_dashboard_command_response", sp_id),
+        // read by `take_step_confirmation` before an operation starts
+        format!("{}_step_mode", sp_id),
+        format!("{}_step_confirmation", sp_id),
+        format!("{}_step_auto_continue", sp_id),
     ];
     keys.extend(model_variable_keys(model));
     normalize_keys(keys)
@@ -530,7 +558,12 @@ mod tests {
             "sp_sop_state",
             "sp_sop_enabled",
             "sp_sop_id",
+            "sp_sop_completion_order",
             "sp_dashboard_command",
+            "sp_dashboard_command_response",
+            "sp_step_mode",
+            "sp_step_confirmation",
+            "sp_step_auto_continue",
             "sop_one_sop_information",
             "trigger",
             "bypassed_marker",
@@ -543,6 +576,7 @@ mod tests {
             .collect();
         for key in [
             "sp_dashboard_command",
+            "sp_dashboard_command_response",
             // `Operation::eval` reads the template's own tracker every tick
             "op_auto_op",
             "op_mutexed_op",
@@ -562,6 +596,10 @@ mod tests {
             "sp_plan",
             "sp_terminated_operations",
             "sp_dashboard_command",
+            "sp_dashboard_command_response",
+            "sp_step_mode",
+            "sp_step_confirmation",
+            "sp_step_auto_continue",
             "trigger",
             "bypassed_marker",
         ] {
@@ -626,9 +664,9 @@ mod tests {
                 &operation,
                 running::process_operation::OperationProcessingType::Automatic,
                 None,
-                None,
                 // a 200 ms tick, matching the runners' cadence
                 200,
+                &OperationHandlers::default(),
                 TARGET,
             )
             .await;
@@ -646,8 +684,8 @@ mod tests {
             &operation,
             running::process_operation::OperationProcessingType::Automatic,
             None,
-            None,
             200,
+            &OperationHandlers::default(),
             TARGET,
         )
         .await;
@@ -0,0 +1,472 @@
+//! A driver-side server for the one-shot service protocol.
+//!
+//! A model calls a service by writing the request variables, setting
+//! `{name}_request_state` to `initial` and raising `{name}_request_trigger`. The
+//! driver lowers the trigger, does the work, writes the response variables and
+//! reports `succeeded` or `failed` in the request state - see
+//! [`ServiceRequestState`]. [`ServiceServer`] is that driver half, minus the
+//! work: it does the polling, the trigger reset, the state transitions, the
+//! type checks, the timeout and the error reporting, and hands the request to
+//! an async handler.
+//!
+//! ```no_run
+//! use micro_sp::*;
+//! use std::sync::Arc;
+//!
+//! # async fn example(connection_manager: Arc<ConnectionManager>) {
+//! let server = ServiceServer::new(
+//!     "gripper",
+//!     vec![SPVariable::new("gripper_command", SPValueType::String)],
+//!     vec![SPVariable::new("gripper_state", SPValueType::String)],
+//!     Some(5_000),
+//! );
+//!
+//! server
+//!     .run(&connection_manager, |request: State| async move {
+//!         let command = request.get_string_or_default_to_unknown("gripper_command", "gripper");
+//!         // ... talk to the hardware ...
+//!         ServiceResponse::succeeded(State::from_vec(&vec![(
+//!             SPVariable::new("gripper_state", SPValueType::String),
+//!             command.to_spvalue(),
+//!         )]))
+//!     })
+//!     .await;
+//! # }
+//! ```
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// What a [`ServiceServer`] handler reports back for one request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceResponse {
+    /// Whether the request was served; becomes `succeeded` or `failed`.
+    pub succeeded: bool,
+    /// Response variables to write. Written on failure too, so a driver can
+    /// report what it measured on the way down.
+    pub values: State,
+    /// A human-readable account of what happened, written to
+    /// `{name}_request_information` when the state has it.
+    pub information: String,
+}
+
+impl ServiceResponse {
+    /// The request was served and `values` hold the result.
+    pub fn succeeded(values: State) -> ServiceResponse {
+        ServiceResponse {
+            succeeded: true,
+            values,
+            information: String::new(),
+        }
+    }
+
+    /// The request could not be served, for the given reason.
+    pub fn failed(values: State, information: &str) -> ServiceResponse {
+        ServiceResponse {
+            succeeded: false,
+            values,
+            information: information.to_string(),
+        }
+    }
+}
+
+/// The driver side of one service: its name and the typed variables it reads
+/// and writes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceServer {
+    /// The service name, which prefixes `{name}_request_trigger`,
+    /// `{name}_request_state` and `{name}_request_information`.
+    pub name: String,
+    /// Variables handed to the handler. A request whose value for one of these
+    /// has a different type fails without reaching the handler.
+    pub request: Vec<SPVariable>,
+    /// Variables the handler may write. Anything else it returns is ignored
+    /// with a warning; a value of the wrong type fails the request.
+    pub response: Vec<SPVariable>,
+    /// How long the handler may take, in milliseconds, before the request is
+    /// failed. `None` waits as long as it takes.
+    pub timeout_ms: Option<u64>,
+    /// How often to poll the trigger, in milliseconds. `None`, the default from
+    /// [`ServiceServer::new`], polls at the runners' [`tick_interval_ms`].
+    pub tick_interval_ms: Option<u64>,
+}
+
+impl ServiceServer {
+    /// Define a service server.
+    pub fn new(
+        name: &str,
+        request: Vec<SPVariable>,
+        response: Vec<SPVariable>,
+        timeout_ms: Option<u64>,
+    ) -> ServiceServer {
+        ServiceServer {
+            name: name.to_string(),
+            request,
+            response,
+            timeout_ms,
+            tick_interval_ms: None,
+        }
+    }
+
+    fn trigger_key(&self) -> String {
+        format!("{}_request_trigger", self.name)
+    }
+
+    fn state_key(&self) -> String {
+        format!("{}_request_state", self.name)
+    }
+
+    fn information_key(&self) -> String {
+        format!("{}_request_information", self.name)
+    }
+
+    /// Every key the server reads: the handshake, the optional information
+    /// key, and the request and response variables.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = vec![self.trigger_key(), self.state_key(), self.information_key()];
+        keys.extend(self.request.iter().map(|v| v.name.clone()));
+        keys.extend(self.response.iter().map(|v| v.name.clone()));
+        normalize_keys(keys)
+    }
+
+    /// Serves the request in `state`, if there is one, and returns the state
+    /// to write back.
+    ///
+    /// With the trigger down nothing changes. With it up the trigger is
+    /// lowered; if the request state is not `initial` that is all, as a request
+    /// is only ever served once. Otherwise the handler runs and its outcome -
+    /// or a type error, or a timeout - lands in the request state, the response
+    /// variables and, if present, `{name}_request_information`.
+    pub async fn serve<F, Fut>(&self, state: &State, handler: &F, log_target: &str) -> State
+    where
+        F: Fn(State) -> Fut,
+        Fut: Future<Output = ServiceResponse>,
+    {
+        if !state.contains(&self.trigger_key())
+            || !state.get_bool_or_default_to_false(&self.trigger_key(), log_target)
+        {
+            return state.clone();
+        }
+
+        let mut new_state = state.update(&self.trigger_key(), false.to_spvalue());
+        let request_state = state.get_string_or_default_to_unknown(&self.state_key(), log_target);
+        if !matches!(ServiceRequestState::from_str(&request_state), ServiceRequestState::Initial) {
+            log::warn!(target: log_target,
+                "Service '{}' triggered in state '{}', expected 'initial'. Ignoring.", self.name, request_state);
+            return new_state;
+        }
+
+        let response = match self.request_state(state) {
+            Err(error) => ServiceResponse::failed(State::new(), &error),
+            Ok(request) => match self.timeout_ms {
+                None => handler(request).await,
+                Some(timeout_ms) => {
+                    match tokio::time::timeout(Duration::from_millis(timeout_ms), handler(request))
+                        .await
+                    {
+                        Ok(response) => response,
+                        Err(_) => ServiceResponse::failed(
+                            State::new(),
+                            &format!("Timed out after {} ms.", timeout_ms),
+                        ),
+                    }
+                }
+            },
+        };
+
+        let response = match self.check_response(&response.values, log_target) {
+            Ok(()) => response,
+            Err(error) => ServiceResponse::failed(State::new(), &error),
+        };
+
+        for variable in &self.response {
+            if let Some(assignment) = response.values.state.get(&variable.name) {
+                if new_state.contains(&variable.name) {
+                    if let Err(e) = new_state.update_in_domain_mut(variable, assignment.val.clone()) {
+                        log::error!(target: log_target, "Service '{}' {}", self.name, e);
+                    }
+                } else {
+                    log::error!(target: log_target,
+                        "Service '{}' response variable '{}' is not in the state.", self.name, variable.name);
+                }
+            }
+        }
+
+        let outcome = match response.succeeded {
+            true => {
+                log::info!(target: log_target, "Service '{}' succeeded. {}", self.name, response.information);
+                ServiceRequestState::Succeeded
+            }
+            false => {
+                log::error!(target: log_target, "Service '{}' failed. {}", self.name, response.information);
+                ServiceRequestState::Failed
+            }
+        };
+        new_state.update_mut(&self.state_key(), outcome.to_string().to_spvalue());
+        if new_state.contains(&self.information_key()) {
+            new_state.update_mut(&self.information_key(), response.information.to_spvalue());
+        }
+        new_state
+    }
+
+    /// Runs the server until the process ends.
+    ///
+    /// Polls `{name}_request_trigger` every tick - `tick_interval_ms` if set,
+    /// the runners' period otherwise - and, when it is up, reads the rest of
+    /// [`ServiceServer::keys`], calls [`ServiceServer::serve`] and writes back
+    /// what changed. Log output goes to the `{name}_service_server` target.
+    pub async fn run<F, Fut>(&self, connection_manager: &Arc<ConnectionManager>, handler: F)
+    where
+        F: Fn(State) -> Fut,
+        Fut: Future<Output = ServiceResponse>,
+    {
+        let mut interval = match self.tick_interval_ms {
+            Some(ms) => {
+                let mut interval = tokio::time::interval(Duration::from_millis(ms));
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                interval
+            }
+            None => runner_interval(),
+        };
+        let log_target = format!("{}_service_server", self.name);
+        let keys = self.keys();
+        let trigger_key = self.trigger_key();
+
+        log::info!(target: &log_target, "Online.");
+
+        let mut con = connection_manager.get_connection().await;
+
+        loop {
+            interval.tick().await;
+
+            // An idle tick costs one `GET`, as in `tf_interface`.
+            match StateManager::get_sp_value(&mut con, &trigger_key).await {
+                Some(SPValue::Bool(BoolOrUnknown::Bool(true))) => (),
+                _ => continue,
+            }
+
+            let state = match StateManager::get_state_for_keys(&mut con, &keys, &log_target).await {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let new_state = self.serve(&state, &handler, &log_target).await;
+            let modified_state = state.get_diff_partial_state(&new_state);
+            if !modified_state.state.is_empty() {
+                activity_log::log_state_diff(&log_target, &state, &modified_state);
+                StateManager::set_state(&mut con, &modified_state).await;
+            }
+        }
+    }
+
+    /// The request variables out of `state`, or why they cannot be served.
+    fn request_state(&self, state: &State) -> Result<State, String> {
+        let mut request = State::new();
+        for variable in &self.request {
+            let Some(assignment) = state.state.get(&variable.name) else {
+                return Err(format!("Request variable '{}' is not in the state.", variable.name));
+            };
+            if assignment.val.has_type() != variable.value_type {
+                return Err(format!(
+                    "Request variable '{}' is {}, expected {}.",
+                    variable.name,
+                    assignment.val.has_type(),
+                    variable.value_type
+                ));
+            }
+            request
+                .state
+                .insert(variable.name.clone(), assignment.clone());
+        }
+        Ok(request)
+    }
+
+    fn check_response(&self, values: &State, log_target: &str) -> Result<(), String> {
+        for (name, assignment) in &values.state {
+            match self.response.iter().find(|v| &v.name == name) {
+                None => log::warn!(target: log_target,
+                    "Service '{}' handler returned '{}', which is not a response variable. Ignoring.", self.name, name),
+                Some(variable) if assignment.val.has_type() != variable.value_type => {
+                    return Err(format!(
+                        "Response variable '{}' is {}, expected {}.",
+                        name,
+                        assignment.val.has_type(),
+                        variable.value_type
+                    ));
+                }
+                Some(SPVariable { domain: Some(domain), .. }) if !domain.contains(&assignment.val) => {
+                    return Err(format!(
+                        "Response variable '{}' is {}, outside its domain {}.",
+                        name, assignment.val, domain
+                    ));
+                }
+                Some(_) => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const TARGET: &str = "test";
+
+    fn server(timeout_ms: Option<u64>) -> ServiceServer {
+        ServiceServer::new(
+            "gripper",
+            vec![SPVariable::new("gripper_command", SPValueType::String)],
+            vec![SPVariable::new("gripper_state", SPValueType::String)],
+            timeout_ms,
+        )
+    }
+
+    fn state(trigger: bool, request_state: &str, command: SPValue) -> State {
+        State::from_vec(&vec![
+            (
+                SPVariable::new("gripper_request_trigger", SPValueType::Bool),
+                trigger.to_spvalue(),
+            ),
+            (
+                SPVariable::new("gripper_request_state", SPValueType::String),
+                request_state.to_spvalue(),
+            ),
+            (
+                SPVariable::new("gripper_request_information", SPValueType::String),
+                SPValue::String(StringOrUnknown::UNKNOWN),
+            ),
+            (
+                SPVariable::new("gripper_command", SPValueType::String),
+                command,
+            ),
+            (
+                SPVariable::new("gripper_state", SPValueType::String),
+                "open".to_spvalue(),
+            ),
+        ])
+    }
+
+    fn gripper_state(value: &str) -> State {
+        State::from_vec(&vec![(
+            SPVariable::new("gripper_state", SPValueType::String),
+            value.to_spvalue(),
+        )])
+    }
+
+    async fn echo(request: State) -> ServiceResponse {
+        let command = request.get_string_or_default_to_unknown("gripper_command", TARGET);
+        ServiceResponse::succeeded(gripper_state(&command))
+    }
+
+    fn read(state: &State, key: &str) -> String {
+        state.get_string_or_default_to_unknown(key, TARGET)
+    }
+
+    #[tokio::test]
+    async fn a_request_is_served_once_and_the_trigger_lowered() {
+        let state = state(true, "initial", "closed".to_spvalue());
+
+        let state = server(None).serve(&state, &echo, TARGET).await;
+
+        assert_eq!(read(&state, "gripper_request_state"), "succeeded");
+        assert_eq!(read(&state, "gripper_state"), "closed");
+        assert_eq!(
+            state.get_value("gripper_request_trigger", TARGET),
+            Some(false.to_spvalue())
+        );
+    }
+
+    #[tokio::test]
+    async fn nothing_happens_without_the_trigger() {
+        let before = state(false, "initial", "closed".to_spvalue());
+        assert_eq!(server(None).serve(&before, &echo, TARGET).await, before);
+    }
+
+    #[tokio::test]
+    async fn a_trigger_outside_initial_is_only_lowered() {
+        let before = state(true, "succeeded", "closed".to_spvalue());
+
+        let after = server(None).serve(&before, &echo, TARGET).await;
+
+        assert_eq!(after, before.update("gripper_request_trigger", false.to_spvalue()));
+    }
+
+    #[tokio::test]
+    async fn a_handler_failure_is_reported_with_its_values() {
+        let state = state(true, "initial", "closed".to_spvalue());
+        let jammed = |_: State| async { ServiceResponse::failed(gripper_state("jammed"), "Jammed.") };
+
+        let state = server(None).serve(&state, &jammed, TARGET).await;
+
+        assert_eq!(read(&state, "gripper_request_state"), "failed");
+        assert_eq!(read(&state, "gripper_state"), "jammed");
+        assert_eq!(read(&state, "gripper_request_information"), "Jammed.");
+    }
+
+    #[tokio::test]
+    async fn a_request_of_the_wrong_type_never_reaches_the_handler() {
+        let state = state(true, "initial", 3.to_spvalue());
+        let unreachable = |_: State| async { panic!("the handler must not run") };
+
+        let state = server(None).serve(&state, &unreachable, TARGET).await;
+
+        assert_eq!(read(&state, "gripper_request_state"), "failed");
+        assert_eq!(
+            read(&state, "gripper_request_information"),
+            "Request variable 'gripper_command' is i64, expected string."
+        );
+    }
+
+    #[tokio::test]
+    async fn a_response_of_the_wrong_type_fails_and_writes_nothing() {
+        let state = state(true, "initial", "closed".to_spvalue());
+        let wrong = |_: State| async {
+            ServiceResponse::succeeded(State::from_vec(&vec![(
+                SPVariable::new("gripper_state", SPValueType::Bool),
+                true.to_spvalue(),
+            )]))
+        };
+
+        let state = server(None).serve(&state, &wrong, TARGET).await;
+
+        assert_eq!(read(&state, "gripper_request_state"), "failed");
+        assert_eq!(read(&state, "gripper_state"), "open");
+    }
+
+    #[tokio::test]
+    async fn a_response_outside_its_domain_fails_and_writes_nothing() {
+        let state = state(true, "initial", "closed".to_spvalue());
+        let server = ServiceServer::new(
+            "gripper",
+            vec![SPVariable::new("gripper_command", SPValueType::String)],
+            vec![SPVariable::new("gripper_state", SPValueType::String)
+                .with_domain(vec!["open".to_spvalue(), "closed".to_spvalue()])],
+            None,
+        );
+        let jammed = |_: State| async { ServiceResponse::succeeded(gripper_state("jammed")) };
+
+        let state = server.serve(&state, &jammed, TARGET).await;
+
+        assert_eq!(read(&state, "gripper_request_state"), "failed");
+        assert_eq!(read(&state, "gripper_state"), "open");
+    }
+
+    #[tokio::test]
+    async fn a_slow_handler_times_out() {
+        let state = state(true, "initial", "closed".to_spvalue());
+        let slow = |request: State| async move {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            echo(request).await
+        };
+
+        let state = server(Some(10)).serve(&state, &slow, TARGET).await;
+
+        assert_eq!(read(&state, "gripper_request_state"), "failed");
+        assert_eq!(read(&state, "gripper_state"), "open");
+        assert_eq!(read(&state, "gripper_request_information"), "Timed out after 10 ms.");
+    }
+}
@@ -3,7 +3,9 @@
 //! The SOP runner picks up whichever SOP the state points at, walks its
 //! sequence/parallel/alternative tree, and drives the operations it contains
 //! through [`process_operation`](crate::running::process_operation) until the
-//! whole procedure completes, fails or is cancelled.
+//! whole procedure completes, fails or is cancelled. A SOP that fails or is
+//! cancelled has the [compensations](crate::running::compensation) of its
+//! completed operations run before the next SOP may start.
 
 use crate::*;
 use log::Level;
@@ -17,10 +19,12 @@ use std::sync::Arc;
 /// operations, and writes back `{sp_id}_sop_state`, `{sp_id}_sop_current_step`
 /// and the per-operation state. `model` supplies the SOPs to look up,
 /// `connection_manager` the shared Redis connection; log output goes to the
-/// `{sp_id}_sop_runner` target.
+/// `{sp_id}_sop_runner` target. `handlers` are fired as the operations change
+/// state; see [`OperationHandlers`].
 pub async fn sop_runner(
     sp_id: &str,
     model: &Model,
+    handlers: &OperationHandlers,
     connection_manager: &Arc<ConnectionManager>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     initialize_env_logger();
@@ -41,6 +45,12 @@ pub async fn sop_runner(
     // is rebuilt there rather than computed once here.
     let static_keys = sop_runner_static_keys(sp_id, model);
     let mut keys = static_keys.clone();
+    let compensations = compensation_operations(model);
+    let compensation_key = format!("{}_sop_compensation", sp_id);
+    // The operations of the active SOP that have completed, in the order they
+    // did, for compensating them last completed first.
+    let completion_order_key = format!("{}_sop_completion_order", sp_id);
+    let mut active_compensation: Vec<String> = vec![];
     let read_full_state = read_full_state_enabled();
     if read_full_state {
         log::warn!(target: log_target, "MICRO_SP_READ_FULL_STATE is set: reading the whole keyspace every tick.");
@@ -62,11 +72,47 @@ pub async fn sop_runner(
             true => StateManager::get_full_state(&mut con).await,
             false => StateManager::get_state_for_keys(&mut con, &keys, &log_target).await,
         };
-        let state = match read {
+        let mut state = match read {
             Some(s) => s,
             None => continue,
         };
 
+        // A cancelled or fatal SOP leaves the compensations of its completed
+        // operations queued, and they run to the end before anything else.
+        // Their uniquified names are only known from the queue, so the key set
+        // follows it like it follows an activated SOP.
+        let compensation = read_compensation(&state, &compensation_key, log_target);
+        if !read_full_state && compensation != active_compensation {
+            keys = keys_with_active_operations(&static_keys, &compensation);
+            active_compensation = compensation.clone();
+            state = match StateManager::get_state_for_keys(&mut con, &keys, log_target).await {
+                Some(s) => s,
+                None => continue,
+            };
+        }
+        if !compensation.is_empty() {
+            let (new_state, compensated) = running::compensation::process_compensation_tick(
+                sp_id,
+                state.clone(),
+                &compensation_key,
+                &compensations,
+                tick_elapsed_ms,
+                handlers,
+                log_target,
+            )
+            .await;
+            if !compensated.is_empty() {
+                let keys = running::compensation::compensation_keys(&compensated);
+                StateManager::apply(&mut con, &State::new(), &[&keys]).await;
+            }
+            let modified_state = state.get_diff_partial_state_and_add_missing(&new_state);
+            if !modified_state.state.is_empty() {
+                activity_log::log_state_diff(log_target, &state, &modified_state);
+                StateManager::set_state(&mut con, &modified_state).await;
+            }
+            continue;
+        }
+
         let mut new_state = state.clone();
         let mut sop_state =
             state.get_string_or_default_to_unknown(&format!("{}_sop_state", sp_id), &log_target);
@@ -105,6 +151,7 @@ pub async fn sop_runner(
 
                     let unique_sop = uniquify_sop_operations(sop_template.sop.clone());
                     active_sop_container = Some(unique_sop.clone());
+                    new_state.update_mut(&completion_order_key, Vec::<SPValue>::new().to_spvalue());
                     let ops_in_sop = get_all_operations_from_sop(&unique_sop);
                     let op_names: Vec<String> =
                         ops_in_sop.iter().map(|x| x.name.clone()).collect();
@@ -128,6 +175,20 @@ pub async fn sop_runner(
                     new_state =
                         new_state.update(&format!("{}_sop_enabled", sp_id), false.to_spvalue());
                 } else {
+                    // Left pending, an abort would cancel the next SOP the
+                    // moment it started, so it is answered and dropped here.
+                    let command = read_dashboard_command(sp_id, &new_state, log_target);
+                    if command == DashboardCommand::AbortSop {
+                        acknowledge_dashboard_command(
+                            sp_id,
+                            &mut new_state,
+                            &command,
+                            "no SOP is executing.",
+                            log_target,
+                        );
+                        StateManager::set_state(&mut con, &state.get_diff_partial_state(&new_state))
+                            .await;
+                    }
                     continue;
                 }
             }
@@ -146,15 +207,30 @@ pub async fn sop_runner(
                     let con_clone = con.clone();
                     new_sop_info = format!("Executing SOP '{active_sop}'.");
                     sop_info_level = log::Level::Info;
+                    new_state = running::dashboard::apply_sop_command(
+                        sp_id,
+                        new_state,
+                        active_sop,
+                        active_sop_container.as_ref().unwrap(),
+                        handlers,
+                        log_target,
+                    );
                     new_state = process_sop_node_tick(
                         sp_id,
                         new_state,
                         active_sop_container.as_ref().unwrap(),
                         con_clone,
                         tick_elapsed_ms,
+                        handlers,
                         &log_target,
                     )
                     .await;
+                    record_completions(
+                        active_sop_container.as_ref().unwrap(),
+                        &mut new_state,
+                        &completion_order_key,
+                        log_target,
+                    );
 
                     let calculated_root_state = active_sop_container
                         .as_ref()
@@ -176,6 +252,13 @@ pub async fn sop_runner(
                     sop_state = SOPState::Fatal.to_string();
 
                     if let Some(unique_sop) = active_sop_container {
+                        new_state = compensate_sop(
+                            &unique_sop,
+                            &completion_order_key,
+                            &compensation_key,
+                            &new_state,
+                            log_target,
+                        );
                         let con_clone = con.clone();
                         remove_operations_from_state(active_sop, &unique_sop, con_clone).await;
                     }
@@ -212,6 +295,13 @@ pub async fn sop_runner(
                     sop_state = SOPState::Cancelled.to_string();
 
                     if let Some(unique_sop) = active_sop_container {
+                        new_state = compensate_sop(
+                            &unique_sop,
+                            &completion_order_key,
+                            &compensation_key,
+                            &new_state,
+                            log_target,
+                        );
                         let con_clone = con.clone();
                         remove_operations_from_state(active_sop, &unique_sop, con_clone).await;
                     }
@@ -281,12 +371,68 @@ pub async fn sop_runner(
 
         if !modified_state.state.is_empty() {
             activity_log::log_state_diff(&log_target, &state, &modified_state);
+            model.warn_foreign_writes(sp_id, &modified_state, log_target);
             StateManager::set_state(&mut con, &modified_state).await;
         }
 
     }
 }
 
+fn is_completed(operation: &Operation, state: &State, log_target: &str) -> bool {
+    OperationState::from_str(&state.get_string_or_default_to_unknown(&operation.name, log_target))
+        == OperationState::Terminated(TerminationReason::Completed)
+}
+
+/// The operations recorded under `order_key`, in the order they completed.
+fn read_completion_order(state: &State, order_key: &str, log_target: &str) -> Vec<String> {
+    state
+        .get_array_or_default_to_empty(order_key, log_target)
+        .iter()
+        .filter(|val| val.is_string())
+        .map(|val| val.to_string())
+        .collect()
+}
+
+/// Append the operations of `unique_sop` that have completed since the last
+/// call to the array under `order_key`, which lives in the state so that it
+/// survives a runner restart. Operations that completed on the same tick are
+/// appended in the order the SOP lists them.
+fn record_completions(unique_sop: &SOP, state: &mut State, order_key: &str, log_target: &str) {
+    let mut completion_order = read_completion_order(state, order_key, log_target);
+    let recorded = completion_order.len();
+    for operation in get_all_operations_from_sop(unique_sop) {
+        if is_completed(&operation, state, log_target) && !completion_order.contains(&operation.name) {
+            completion_order.push(operation.name);
+        }
+    }
+    if completion_order.len() != recorded {
+        state.update_mut(order_key, completion_order.to_spvalue());
+    }
+}
+
+/// Queue the compensations of the operations of `unique_sop` that completed,
+/// last completed first as [`record_completions`] saw them. In a parallel or
+/// alternative SOP that is not the order the SOP lists them in.
+fn compensate_sop(
+    unique_sop: &SOP,
+    order_key: &str,
+    compensation_key: &str,
+    state: &State,
+    log_target: &str,
+) -> State {
+    let completion_order = read_completion_order(state, order_key, log_target);
+    let operations = get_all_operations_from_sop(unique_sop);
+    let mut completed: Vec<&Operation> = operations
+        .iter()
+        .filter(|operation| is_completed(operation, state, log_target))
+        .collect();
+    // Stable, so anything completed unseen counts as first, in listing order.
+    completed.sort_by_key(|operation| {
+        completion_order.iter().position(|name| name == &operation.name)
+    });
+    running::compensation::begin_compensation(compensation_key, &completed, state, log_target)
+}
+
 async fn remove_operations_from_state(sop_id: &str, unique_sop: &SOP, mut con: SPConnection) {
     let ops_in_sop = get_all_operations_from_sop(&unique_sop);
     let mut op_ids_meta = vec![];
@@ -314,6 +460,7 @@ async fn process_sop_node_tick(
     sop: &SOP,
     con: crate::SPConnection,
     tick_elapsed_ms: i64,
+    handlers: &OperationHandlers,
     log_target: &str,
 ) -> State {
     match sop {
@@ -324,8 +471,8 @@ async fn process_sop_node_tick(
                 operation,
                 running::process_operation::OperationProcessingType::SOP,
                 None,
-                None,
                 tick_elapsed_ms,
+                handlers,
                 log_target,
             )
             .await;
@@ -338,7 +485,7 @@ async fn process_sop_node_tick(
 
             if let Some(child) = active_child {
                 state = Box::pin(process_sop_node_tick(
-                    sp_id, state, child, con, tick_elapsed_ms, log_target,
+                    sp_id, state, child, con, tick_elapsed_ms, handlers, log_target,
                 ))
                 .await;
             }
@@ -352,6 +499,7 @@ async fn process_sop_node_tick(
                     child,
                     con.clone(),
                     tick_elapsed_ms,
+                    handlers,
                     log_target,
                 ))
                 .await;
@@ -367,7 +515,7 @@ async fn process_sop_node_tick(
             // If a path is active, keep processing it
             if let Some(child) = active_child {
                 state = Box::pin(process_sop_node_tick(
-                    sp_id, state, child, con, tick_elapsed_ms, log_target,
+                    sp_id, state, child, con, tick_elapsed_ms, handlers, log_target,
                 ))
                 .await;
             } else {
@@ -383,6 +531,7 @@ async fn process_sop_node_tick(
                         path_to_start,
                         con,
                         tick_elapsed_ms,
+                        handlers,
                         log_target,
                     ))
                     .await;
@@ -589,7 +738,7 @@ mod tests {
     ) -> tokio::task::JoinHandle<()> {
         let manager = Arc::clone(manager);
         tokio::spawn(async move {
-            let _ = sop_runner(SP, &model, &manager).await;
+            let _ = sop_runner(SP, &model, &OperationHandlers::default(), &manager).await;
         })
     }
 
@@ -1277,4 +1426,41 @@ mod tests {
             "and the step after the bypassed one is never reached"
         );
     }
+
+    /// A parallel SOP is compensated in the order its operations completed,
+    /// not the order it lists them in.
+    #[test]
+    fn a_parallel_sop_is_compensated_last_completed_first() {
+        let branch = |name: &str| {
+            Operation {
+                name: name.to_string(),
+                ..Default::default()
+            }
+            .compensated_by(Operation {
+                name: format!("undo_{name}"),
+                ..Default::default()
+            })
+        };
+        let sop = SOP::Parallel(vec![
+            SOP::Operation(Box::new(branch("a"))),
+            SOP::Operation(Box::new(branch("b"))),
+        ]);
+        let completed = OperationState::Terminated(TerminationReason::Completed).to_string();
+        let order = format!("{SP}_sop_completion_order");
+        let mut state = State::from_vec(&vec![
+            (v!("a"), "executing".to_spvalue()),
+            (v!("b"), completed.to_spvalue()),
+            (av!(&&order), Vec::<SPValue>::new().to_spvalue()),
+        ]);
+        record_completions(&sop, &mut state, &order, TARGET);
+        state.update_mut("a", completed.to_spvalue());
+        record_completions(&sop, &mut state, &order, TARGET);
+        assert_eq!(read_completion_order(&state, &order, TARGET), vec!["b", "a"]);
+
+        let queue = format!("{SP}_sop_compensation");
+        let queued = compensate_sop(&sop, &order, &queue, &state, TARGET);
+        let steps = read_compensation(&queued, &queue, TARGET);
+        assert_eq!(steps.len(), 2);
+        assert!(steps[0].starts_with("undo_a_") && steps[1].starts_with("undo_b_"));
+    }
 }
@@ -142,6 +142,45 @@ pub fn generate_runner_state_variables(
         &log_target,
     );
 
+    // What the runners did with the last one-shot dashboard command
+    let sp_dashboard_command_response = v!(&&format!("{}_dashboard_command_response", name));
+    state.add_mut(
+        assign!(
+            sp_dashboard_command_response,
+            SPValue::String(StringOrUnknown::UNKNOWN)
+        ),
+        log_target,
+    );
+
+    // Step-by-step execution, off until the dashboard turns it on
+    let step_mode = bv!(&&format!("{}_step_mode", name));
+    let step_confirmation = v!(&&format!("{}_step_confirmation", name));
+    let step_auto_continue = iv!(&&format!("{}_step_auto_continue", name));
+    state.add_mut(assign!(step_mode, false.to_spvalue()), log_target);
+    state.add_mut(
+        assign!(step_confirmation, SPValue::String(StringOrUnknown::UNKNOWN)),
+        log_target,
+    );
+    state.add_mut(assign!(step_auto_continue, 0.to_spvalue()), log_target);
+
+    // Alarms, kept by the alarm runner
+    let active_alarms = av!(&&format!("{}_active_alarms", name));
+    let alarm_history = av!(&&format!("{}_alarm_history", name));
+    let alarms_block_goals = bv!(&&format!("{}_alarms_block_goals", name));
+    state.add_mut(assign!(active_alarms, Vec::<SPValue>::new().to_spvalue()), log_target);
+    state.add_mut(assign!(alarm_history, Vec::<SPValue>::new().to_spvalue()), log_target);
+    state.add_mut(assign!(alarms_block_goals, false.to_spvalue()), log_target);
+
+    // Compensation queues, kept by the plan and SOP runners
+    for runner in ["plan", "sop"] {
+        let queue = av!(&&format!("{}_{}_compensation", name, runner));
+        let queue_step = iv!(&&format!("{}_{}_compensation_step", name, runner));
+        state.add_mut(assign!(queue, Vec::<SPValue>::new().to_spvalue()), log_target);
+        state.add_mut(assign!(queue_step, 0.to_spvalue()), log_target);
+    }
+    let sop_completion_order = av!(&&format!("{}_sop_completion_order", name));
+    state.add_mut(assign!(sop_completion_order, Vec::<SPValue>::new().to_spvalue()), log_target);
+
     // Initialize values
     state.add_mut(
         assign!(runner_state, SPValue::String(StringOrUnknown::UNKNOWN)),
@@ -424,7 +463,10 @@ fn get_all_operations_recursive(sop: &SOP, operations: &mut Vec<Operation>) {
 }
 
 /// Builds one `initial` lifecycle variable per operation in the model, including
-/// the operations nested in its SOPs, plus an information variable per SOP.
+/// the operations nested in its SOPs, plus an information variable per SOP and
+/// empty `{op}_stats` per operation and compensation. Every watchdog starts
+/// alive, with no fault, every retry policy on its first attempt, and every
+/// declared result empty.
 ///
 /// With `coverability_tracking` set, also adds a counter per automatic transition
 /// recording how many times it has been taken.
@@ -435,6 +477,7 @@ pub fn generate_operation_state_variables(
 ) -> State {
     let mut state = State::new();
     let mut operation_trackers: Vec<String> = vec!();
+    let mut statistics: Vec<String> = vec!();
     // operations should be put in the initial state once they are part of the plan
 
     for sop in &model.sops {
@@ -444,7 +487,11 @@ pub fn generate_operation_state_variables(
             assign!(sop_information, SPValue::String(StringOrUnknown::UNKNOWN)),
             &log_target,
         );
-        ops_in_sop.iter().for_each(|x| operation_trackers.push(x.name.clone()));
+        ops_in_sop.iter().for_each(|x| {
+            operation_trackers.push(x.name.clone());
+            // SOP runs are named `op_{name}_{nanoid}`, see `uniquify_sop_operations`.
+            statistics.push(statistics_key(&format!("op_{}", x.name)));
+        });
     }
 
     model.operations.iter().for_each(|x| operation_trackers.push(x.name.clone()));
@@ -458,6 +505,35 @@ pub fn generate_operation_state_variables(
         &log_target,
     );
 
+    operation_trackers
+        .iter()
+        .for_each(|x| statistics.push(statistics_key(x)));
+    compensation_operations(model)
+        .iter()
+        .for_each(|x| statistics.push(statistics_key(&x.name)));
+    statistics.sort();
+    statistics.dedup();
+    for key in &statistics {
+        state.add_mut(
+            assign!(mv!(&key), OperationStatistics::default().to_spvalue()),
+            log_target,
+        );
+    }
+
+    for watchdog in &model.watchdogs {
+        state.extend_mut(watchdog.state_variables(), true);
+    }
+
+    let operations = model.all_operations().into_iter().chain(compensation_operations(model));
+    for operation in operations {
+        if let Some(policy) = &operation.retry_policy {
+            state.extend_mut(policy.state_variables(), true);
+        }
+        if let Some(results) = &operation.results {
+            state.extend_mut(results.state_variables(), true);
+        }
+    }
+
     for transition in &model.auto_transitions {
         if coverability_tracking {
             let taken = iv!(&&format!("transition_{}_taken", transition.name));
@@ -610,12 +686,18 @@ mod state_init_tests {
             vec![operation("mutexed", "b", state)],
             vec![SOPStruct {
                 id: "the_sop".to_string(),
-                sop: SOP::Sequence(vec![SOP::Operation(Box::new(operation(
-                    "in_sop", "a", state,
-                )))]),
+                sop: SOP::Sequence(vec![SOP::Operation(Box::new(
+                    operation("in_sop", "a", state).with_results(vec![bv!("b")]),
+                ))]),
             }],
-            vec![operation("planned", "b", state)],
+            vec![operation("planned", "b", state).with_retry_policy(RetryPolicy::new("planned"))],
         )
+        .with_watchdogs(vec![Watchdog::heartbeat(
+            "gantry",
+            iv!("gantry_heartbeat"),
+            1000,
+            vec!["planned", "auto"],
+        )])
     }
 
     /// The contract between initialisation and every runner's read set. A key
@@ -0,0 +1,196 @@
+//! Step-by-step execution, for trying a model out on real hardware.
+//!
+//! With `{sp_id}_step_mode` set, the plan and SOP runners hold every operation
+//! in `initial` instead of starting it, until the operator confirms that step by
+//! writing its name into `{sp_id}_step_confirmation`. One already `disabled`
+//! when step mode comes on is held there the same way once its guard holds,
+//! with its disabled timeout paused. The confirmation is only used up when the
+//! operation starts. While it waits, the
+//! operation's `_information` says so and shows which of its preconditions
+//! currently hold, so the operator can see what would happen before saying yes.
+//!
+//! `{sp_id}_step_auto_continue` lets the next N steps through unconfirmed; each
+//! step that starts that way counts it down by one. Automatic operations are
+//! never held: they react to the world rather than follow a script.
+//!
+//! All three keys are read with [`State::contains`] first, so a state set up
+//! without them simply runs with step mode off.
+
+use crate::*;
+
+/// Whether step mode lets `operation` start this tick.
+///
+/// Always true with step mode off. Otherwise there has to be some
+/// `{sp_id}_step_auto_continue` left, or a confirmation naming this operation,
+/// matched with [`operation_answers_to`]. Nothing is used up here; see
+/// [`take_step_confirmation`].
+pub fn step_confirmed(sp_id: &str, state: &State, operation: &Operation, log_target: &str) -> bool {
+    let mode_key = format!("{}_step_mode", sp_id);
+    if !state.contains(&mode_key) || !state.get_bool_or_default_to_false(&mode_key, log_target) {
+        return true;
+    }
+    let auto_continue_key = format!("{}_step_auto_continue", sp_id);
+    if state.contains(&auto_continue_key)
+        && state.get_int_or_default_to_zero(&auto_continue_key, log_target) > 0
+    {
+        return true;
+    }
+    let confirmation_key = format!("{}_step_confirmation", sp_id);
+    state.contains(&confirmation_key)
+        && operation_answers_to(
+            &operation.name,
+            &state.get_string_or_default_to_unknown(&confirmation_key, log_target),
+        )
+}
+
+/// Whether `operation` may start this tick, using up what let it: one unit of
+/// `{sp_id}_step_auto_continue` if there is any, or else the confirmation
+/// naming it, which is cleared so it confirms one step only. Call it as the
+/// operation starts, so that a confirmation is not spent on an operation
+/// whose guard then does not hold. A step held back leaves the state
+/// unchanged.
+pub fn take_step_confirmation(
+    sp_id: &str,
+    state: &mut State,
+    operation: &Operation,
+    log_target: &str,
+) -> bool {
+    let mode_key = format!("{}_step_mode", sp_id);
+    if !state.contains(&mode_key) || !state.get_bool_or_default_to_false(&mode_key, log_target) {
+        return true;
+    }
+
+    let auto_continue_key = format!("{}_step_auto_continue", sp_id);
+    if state.contains(&auto_continue_key) {
+        let remaining = state.get_int_or_default_to_zero(&auto_continue_key, log_target);
+        if remaining > 0 {
+            state.update_mut(&auto_continue_key, (remaining - 1).to_spvalue());
+            log::info!(target: log_target,
+                "Step '{}' auto-continued, {} left.", operation.name, remaining - 1);
+            return true;
+        }
+    }
+
+    let confirmation_key = format!("{}_step_confirmation", sp_id);
+    if state.contains(&confirmation_key) {
+        let confirmation = state.get_string_or_default_to_unknown(&confirmation_key, log_target);
+        if operation_answers_to(&operation.name, &confirmation) {
+            state.update_mut(
+                &confirmation_key,
+                SPValue::String(StringOrUnknown::UNKNOWN),
+            );
+            log::info!(target: log_target, "Step '{}' confirmed.", operation.name);
+            return true;
+        }
+    }
+
+    false
+}
+
+/// The information string of an operation held for confirmation: which of its
+/// preconditions hold right now, by name.
+pub fn awaiting_confirmation_information(
+    operation: &Operation,
+    state: &State,
+    log_target: &str,
+) -> String {
+    let preconditions: Vec<String> = operation
+        .preconditions
+        .iter()
+        .map(|precondition| {
+            let holds = if precondition.eval(state, log_target) {
+                "holds"
+            } else {
+                "does not hold"
+            };
+            format!("'{}' {}", precondition.name, holds)
+        })
+        .collect();
+    let preconditions = match preconditions.is_empty() {
+        true => "none".to_string(),
+        false => preconditions.join(", "),
+    };
+    format!(
+        "Operation '{}' is waiting for step confirmation. Preconditions: {}.",
+        operation.name, preconditions
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const SP_ID: &str = "sp";
+    const TARGET: &str = "test";
+
+    fn operation() -> Operation {
+        Operation {
+            name: "op_x_A1b2C3d4E5".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn state(step_mode: bool, confirmation: &str, auto_continue: i64) -> State {
+        State::from_vec(&vec![
+            (
+                SPVariable::new(&format!("{SP_ID}_step_mode"), SPValueType::Bool),
+                step_mode.to_spvalue(),
+            ),
+            (
+                SPVariable::new(&format!("{SP_ID}_step_confirmation"), SPValueType::String),
+                confirmation.to_spvalue(),
+            ),
+            (
+                SPVariable::new(&format!("{SP_ID}_step_auto_continue"), SPValueType::Int64),
+                auto_continue.to_spvalue(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn with_step_mode_off_every_step_goes() {
+        let mut state = state(false, "none", 0);
+        assert!(take_step_confirmation(SP_ID, &mut state, &operation(), TARGET));
+        assert!(take_step_confirmation(SP_ID, &mut State::new(), &operation(), TARGET));
+    }
+
+    #[test]
+    fn a_step_waits_for_its_own_confirmation() {
+        let mut state = state(true, "op_y", 0);
+        let before = state.clone();
+        assert!(!take_step_confirmation(SP_ID, &mut state, &operation(), TARGET));
+        assert_eq!(state, before);
+
+        for confirmation in ["op_x", "x", "op_x_A1b2C3d4E5"] {
+            let mut state = self::state(true, confirmation, 0);
+            assert!(take_step_confirmation(SP_ID, &mut state, &operation(), TARGET));
+            assert_eq!(
+                state.get_value(&format!("{SP_ID}_step_confirmation"), TARGET),
+                Some(SPValue::String(StringOrUnknown::UNKNOWN)),
+                "a confirmation is good for one step"
+            );
+        }
+    }
+
+    #[test]
+    fn checking_a_confirmation_does_not_use_it_up() {
+        let state = state(true, "op_x", 0);
+        assert!(step_confirmed(SP_ID, &state, &operation(), TARGET));
+        assert!(step_confirmed(SP_ID, &state, &operation(), TARGET));
+        assert!(!step_confirmed(SP_ID, &self::state(true, "op_y", 0), &operation(), TARGET));
+        assert!(step_confirmed(SP_ID, &self::state(true, "none", 1), &operation(), TARGET));
+        assert!(step_confirmed(SP_ID, &State::new(), &operation(), TARGET));
+    }
+
+    #[test]
+    fn auto_continue_lets_n_steps_through() {
+        let mut state = state(true, "none", 2);
+        assert!(take_step_confirmation(SP_ID, &mut state, &operation(), TARGET));
+        assert!(take_step_confirmation(SP_ID, &mut state, &operation(), TARGET));
+        assert!(!take_step_confirmation(SP_ID, &mut state, &operation(), TARGET));
+        assert_eq!(
+            state.get_int_or_default_to_zero(&format!("{SP_ID}_step_auto_continue"), TARGET),
+            0
+        );
+    }
+}
@@ -3,6 +3,9 @@
 //! A model asks for a timer by writing a command and a duration into the
 //! `{sp_id}_timer_{n}_*` variables; this runner keeps their elapsed times up to
 //! date so guards can refer to them like any other variable.
+//!
+//! A plain timeout or staleness check does not need a timer: record the time
+//! with `var:last_seen <- now()` and guard on `age(var:last_seen) > dur:5s`.
 
 use std::sync::Arc;
 
@@ -0,0 +1,45 @@
+//! Expiring measurements that have not been refreshed.
+//!
+//! A variable declared with [`SPVariable::with_ttl`] holds its value only for
+//! that long after the last write. This runner reconciles them every tick
+//! through [`StateManager::expire_stale`], so a sensor that stops reporting
+//! leaves its variables `UNKNOWN` rather than at their last reading. Guards
+//! such as `var:tool != UNKNOWN` then stop holding, and whatever operation
+//! senses the value again becomes the planner's way to restore them.
+
+use std::sync::Arc;
+
+use crate::*;
+
+/// Runs the TTL reconciliation until the process ends.
+///
+/// The variables it watches are the model's declarations with a `ttl_ms`
+/// (see [`Model::variables`]); with none it returns straight away. Every reset
+/// is logged to the `{sp_id}_ttl_runner` target.
+pub async fn ttl_runner(
+    sp_id: &str,
+    model: &Model,
+    connection_manager: &Arc<ConnectionManager>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let log_target = format!("{}_ttl_runner", sp_id);
+    let expiring: Vec<SPVariable> = model
+        .variables
+        .iter()
+        .filter(|var| var.ttl_ms.is_some())
+        .cloned()
+        .collect();
+    if expiring.is_empty() {
+        return Ok(());
+    }
+
+    log::info!(target: &log_target, "Online, watching {} variables.", expiring.len());
+
+    let mut interval = runner_interval();
+    let mut con = connection_manager.get_connection().await;
+    loop {
+        interval.tick().await;
+        for name in StateManager::expire_stale(&mut con, &expiring, &log_target).await {
+            log::info!(target: &log_target, "'{}' was not updated within its TTL, reset to UNKNOWN.", name);
+        }
+    }
+}
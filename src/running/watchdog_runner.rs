@@ -0,0 +1,150 @@
+//! Keeping the model's [`Watchdog`]s up to date.
+//!
+//! Every tick this runner reads each watchdog's signal, measures how long it
+//! has gone unchanged and trips or restores `{name}_watchdog_alive` and
+//! `{name}_watchdog_fault` accordingly. What that does to the supervised
+//! operations is wired into the model by [`Model::with_watchdogs`]; this
+//! runner only reports.
+
+use std::sync::Arc;
+
+use crate::*;
+
+/// How long one watchdog's signal has gone unchanged.
+///
+/// Silence only accumulates while the signal is expected to move - always
+/// for a heartbeat, while a request is pending for request progress - so an
+/// idle service does not trip on the first request it gets. The first
+/// observation counts as a change, which gives a freshly started runner a
+/// full `silence_ms` before it can trip.
+#[derive(Debug, Default)]
+pub struct WatchdogMonitor {
+    last: Option<Vec<Option<SPValue>>>,
+    silent_ms: i64,
+}
+
+impl WatchdogMonitor {
+    /// Record this tick's `signal`, `elapsed_ms` after the previous one, and
+    /// return how long it has now been silent.
+    pub fn observe(&mut self, signal: Vec<Option<SPValue>>, pending: bool, elapsed_ms: i64) -> i64 {
+        if self.last.as_ref() != Some(&signal) {
+            self.last = Some(signal);
+            self.silent_ms = 0;
+        } else if pending {
+            self.silent_ms += elapsed_ms;
+        } else {
+            self.silent_ms = 0;
+        }
+        self.silent_ms
+    }
+}
+
+/// Runs the watchdogs of `model` until the process ends.
+///
+/// With no watchdogs it returns straight away. Trips are logged as errors and
+/// recoveries as info to the `{sp_id}_watchdog_runner` target.
+pub async fn watchdog_runner(
+    sp_id: &str,
+    model: &Model,
+    connection_manager: &Arc<ConnectionManager>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if model.watchdogs.is_empty() {
+        return Ok(());
+    }
+    let log_target = format!("{}_watchdog_runner", sp_id);
+    log::info!(target: &log_target, "Online.");
+
+    let mut keys: Vec<String> = vec![];
+    for watchdog in &model.watchdogs {
+        keys.extend(watchdog.signal_keys());
+        keys.extend(watchdog.pending_key());
+        keys.push(watchdog.alive().name);
+        keys.push(watchdog.fault().name);
+    }
+    keys.sort_unstable();
+    keys.dedup();
+
+    let mut monitors: Vec<WatchdogMonitor> =
+        model.watchdogs.iter().map(|_| WatchdogMonitor::default()).collect();
+    let mut interval = runner_interval();
+    let mut tick_clock = TickClock::new();
+    let mut con = connection_manager.get_connection().await;
+
+    loop {
+        interval.tick().await;
+        let elapsed_ms = tick_clock.elapsed_ms();
+        let state = match StateManager::get_state_for_keys(&mut con, &keys, &log_target).await {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let mut new_state = state.clone();
+        for (watchdog, monitor) in model.watchdogs.iter().zip(monitors.iter_mut()) {
+            let value_of = |key: &str| state.state.get(key).map(|a| a.val.clone());
+            let signal = watchdog.signal_keys().iter().map(|key| value_of(key)).collect();
+            let pending = watchdog
+                .pending_key()
+                .is_none_or(|key| value_of(&key) == Some(true.to_spvalue()));
+            let silent_ms = monitor.observe(signal, pending, elapsed_ms);
+
+            let alive = silent_ms <= watchdog.silence_ms;
+            let was_alive = value_of(&watchdog.alive().name) != Some(false.to_spvalue());
+            let fault = if was_alive && !alive {
+                let fault = WatchdogFault {
+                    watchdog: watchdog.name.clone(),
+                    signal: watchdog.signal_keys(),
+                    silent_ms,
+                    operations: watchdog.operations.clone(),
+                };
+                log::error!(target: &log_target,
+                    "Watchdog '{}' tripped: no sign of life for {} ms.", watchdog.name, silent_ms);
+                serde_json::to_string(&fault)?.to_spvalue()
+            } else if !was_alive && alive {
+                log::info!(target: &log_target, "Watchdog '{}': the service is back.", watchdog.name);
+                SPValue::String(StringOrUnknown::UNKNOWN)
+            } else {
+                continue;
+            };
+            new_state.extend_mut(
+                State::from_vec(&vec![
+                    (watchdog.alive(), alive.to_spvalue()),
+                    (watchdog.fault(), fault),
+                ]),
+                true,
+            );
+        }
+
+        let modified_state = state.get_diff_partial_state_and_add_missing(&new_state);
+        if !modified_state.state.is_empty() {
+            activity_log::log_state_diff(&log_target, &state, &modified_state);
+            StateManager::set_state(&mut con, &modified_state).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_silent_heartbeat_accumulates_until_it_moves_again() {
+        let mut monitor = WatchdogMonitor::default();
+        let beat = |n: i64| vec![Some(n.to_spvalue())];
+
+        assert_eq!(monitor.observe(beat(1), true, 100), 0);
+        assert_eq!(monitor.observe(beat(1), true, 100), 100);
+        assert_eq!(monitor.observe(beat(1), true, 250), 350);
+        assert_eq!(monitor.observe(beat(2), true, 100), 0);
+    }
+
+    #[test]
+    fn silence_only_counts_while_something_is_pending() {
+        let mut monitor = WatchdogMonitor::default();
+        let idle = || vec![Some("initial".to_spvalue()), None];
+
+        monitor.observe(idle(), false, 100);
+        assert_eq!(monitor.observe(idle(), false, 5000), 0);
+        assert_eq!(monitor.observe(idle(), true, 300), 300);
+        assert_eq!(monitor.observe(idle(), false, 300), 0);
+    }
+}